@@ -9,7 +9,8 @@ use hypercore::{Feed, Storage};
 
 async fn create_feed(page_size: usize) -> Result<Feed<RandomAccessMemory>, Error> {
     let storage =
-        Storage::new(|_| Box::pin(async move { Ok(RandomAccessMemory::new(page_size)) })).await?;
+        Storage::new(move |_| Box::pin(async move { Ok(RandomAccessMemory::new(page_size)) }))
+            .await?;
     Feed::with_storage(storage).await
 }
 
@@ -0,0 +1,38 @@
+//! Print the merkle tree of a feed stored on disk.
+//!
+//! Usage: `cargo run --example tree -- <dir> [start..end] [--json]`
+
+use anyhow::{Context, Result};
+use async_std::task;
+use hypercore::Feed;
+use std::io::{self, Write};
+
+async fn dump(dir: &str, range: Option<&str>, json: bool) -> Result<()> {
+    let mut feed = Feed::open_read_only(dir).await?;
+    let range = match range {
+        Some(range) => {
+            let (start, end) = range.split_once("..").context("range must be start..end")?;
+            start.parse()?..end.parse()?
+        }
+        None => 0..feed.len(),
+    };
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    if json {
+        feed.dump_tree_json(range, &mut out).await?;
+        writeln!(out)?;
+    } else {
+        feed.dump_tree(range, &mut out).await?;
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    let mut args = args.iter().filter(|arg| *arg != "--json");
+    let dir = args
+        .next()
+        .context("usage: tree <dir> [start..end] [--json]")?;
+    task::block_on(dump(dir, args.next().map(String::as_str), json))
+}
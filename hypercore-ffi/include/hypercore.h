@@ -0,0 +1,50 @@
+#ifndef HYPERCORE_H
+#define HYPERCORE_H
+
+/* C API for the Rust hypercore implementation. See src/lib.rs for the
+ * ownership rules of each function. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define HYPERCORE_OK 0
+#define HYPERCORE_ERR_NULL 1
+#define HYPERCORE_ERR_UTF8 2
+#define HYPERCORE_ERR_NOT_FOUND 3
+#define HYPERCORE_ERR_VERIFY 4
+/* Any other failure, including a panic in the library. */
+#define HYPERCORE_ERR_FAILED 5
+
+typedef struct HypercoreFeed HypercoreFeed;
+
+typedef struct HypercoreBuffer {
+  uint8_t *data;
+  size_t len;
+} HypercoreBuffer;
+
+const char *hypercore_last_error(void);
+
+int hypercore_create(HypercoreFeed **out);
+int hypercore_open(const char *path, HypercoreFeed **out);
+void hypercore_close(HypercoreFeed *feed);
+
+int hypercore_append(HypercoreFeed *feed, const uint8_t *data, size_t len);
+int hypercore_get(HypercoreFeed *feed, uint64_t index, HypercoreBuffer *out);
+int hypercore_signature(HypercoreFeed *feed, uint64_t index, HypercoreBuffer *out);
+int hypercore_verify(HypercoreFeed *feed, uint64_t index, const uint8_t *signature, size_t len);
+
+uint64_t hypercore_len(const HypercoreFeed *feed);
+uint64_t hypercore_byte_len(const HypercoreFeed *feed);
+int hypercore_public_key(const HypercoreFeed *feed, uint8_t *out);
+
+void hypercore_buffer_free(HypercoreBuffer *buffer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,406 @@
+#![forbid(bad_style, future_incompatible)]
+#![forbid(rust_2018_idioms, rust_2018_compatibility)]
+#![forbid(missing_debug_implementations)]
+#![forbid(missing_docs)]
+#![cfg_attr(test, deny(warnings))]
+
+//! ## Introduction
+//! A stable C API for [hypercore], so applications written in other
+//! languages can embed the Rust implementation.
+//!
+//! Every function returns one of the `HYPERCORE_*` status codes. On failure,
+//! `hypercore_last_error()` describes what went wrong. Panics are caught
+//! and reported as `HYPERCORE_ERR_FAILED`. Buffers handed out by
+//! the library must be released with `hypercore_buffer_free()`, and feeds
+//! with `hypercore_close()`. The matching C header lives in
+//! `include/hypercore.h`.
+//!
+//! The bindings are a crate of their own rather than a feature of
+//! [hypercore], which forbids unsafe code.
+//!
+//! [hypercore]: https://docs.rs/hypercore
+
+use hypercore::{Feed, Signature};
+use random_access_disk::RandomAccessDisk;
+use random_access_memory::RandomAccessMemory;
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::fmt::Display;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+/// The call succeeded.
+pub const HYPERCORE_OK: c_int = 0;
+/// A required pointer argument was null.
+pub const HYPERCORE_ERR_NULL: c_int = 1;
+/// A string argument was not valid UTF-8.
+pub const HYPERCORE_ERR_UTF8: c_int = 2;
+/// The requested block is not stored locally.
+pub const HYPERCORE_ERR_NOT_FOUND: c_int = 3;
+/// A signature did not verify.
+pub const HYPERCORE_ERR_VERIFY: c_int = 4;
+/// Any other failure, see `hypercore_last_error()`.
+pub const HYPERCORE_ERR_FAILED: c_int = 5;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// An open feed, owned by the caller until passed to `hypercore_close()`.
+// Handles are always boxed, so the variants' sizes don't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum HypercoreFeed {
+    /// A feed persisted to a directory.
+    Disk(Feed<RandomAccessDisk>),
+    /// A feed held in memory.
+    Memory(Feed<RandomAccessMemory>),
+}
+
+/// Run the same expression against either kind of feed.
+macro_rules! with_feed {
+    ($handle:expr, $feed:ident => $body:expr) => {
+        match $handle {
+            HypercoreFeed::Disk($feed) => $body,
+            HypercoreFeed::Memory($feed) => $body,
+        }
+    };
+}
+
+/// A byte buffer owned by the library, released with
+/// `hypercore_buffer_free()`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct HypercoreBuffer {
+    /// Pointer to the first byte.
+    pub data: *mut u8,
+    /// Number of bytes.
+    pub len: usize,
+}
+
+impl HypercoreBuffer {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let mut bytes = bytes.into_boxed_slice();
+        let buffer = Self {
+            data: bytes.as_mut_ptr(),
+            len: bytes.len(),
+        };
+        std::mem::forget(bytes);
+        buffer
+    }
+}
+
+fn set_last_error(err: impl Display) {
+    let message = err.to_string().replace('\0', "");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+}
+
+fn fail(err: impl Display) -> c_int {
+    set_last_error(err);
+    HYPERCORE_ERR_FAILED
+}
+
+/// Run `body`, returning `on_panic` instead of unwinding into the caller,
+/// which is undefined behavior across the C boundary.
+fn catch<T>(on_panic: T, body: impl FnOnce() -> T) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(value) => value,
+        Err(payload) => {
+            let message = match payload.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => match payload.downcast_ref::<String>() {
+                    Some(message) => message.clone(),
+                    None => "unknown panic".to_string(),
+                },
+            };
+            set_last_error(format!("panicked: {}", message));
+            on_panic
+        }
+    }
+}
+
+/// Get the message of the last error on this thread, or null if there was
+/// none. The string stays valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn hypercore_last_error() -> *const c_char {
+    catch(ptr::null(), || {
+        LAST_ERROR.with(|last| match &*last.borrow() {
+            Some(message) => message.as_ptr(),
+            None => ptr::null(),
+        })
+    })
+}
+
+/// Create a new feed held in memory.
+///
+/// # Safety
+/// `out` must be a valid pointer to write the feed handle to.
+#[no_mangle]
+pub unsafe extern "C" fn hypercore_create(out: *mut *mut HypercoreFeed) -> c_int {
+    catch(HYPERCORE_ERR_FAILED, || {
+        if out.is_null() {
+            return HYPERCORE_ERR_NULL;
+        }
+        let feed = async_std::task::block_on(async {
+            let storage = hypercore::Storage::new_memory().await?;
+            Feed::with_storage(storage).await
+        });
+        match feed {
+            Ok(feed) => {
+                *out = Box::into_raw(Box::new(HypercoreFeed::Memory(feed)));
+                HYPERCORE_OK
+            }
+            Err(err) => fail(err),
+        }
+    })
+}
+
+/// Open the feed stored in the directory at `path`, creating it if needed.
+///
+/// # Safety
+/// `path` must be a valid nul-terminated string and `out` a valid pointer to
+/// write the feed handle to.
+#[no_mangle]
+pub unsafe extern "C" fn hypercore_open(
+    path: *const c_char,
+    out: *mut *mut HypercoreFeed,
+) -> c_int {
+    catch(HYPERCORE_ERR_FAILED, || {
+        if path.is_null() || out.is_null() {
+            return HYPERCORE_ERR_NULL;
+        }
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(path) => path,
+            Err(err) => {
+                set_last_error(err);
+                return HYPERCORE_ERR_UTF8;
+            }
+        };
+        match async_std::task::block_on(Feed::open(path)) {
+            Ok(feed) => {
+                *out = Box::into_raw(Box::new(HypercoreFeed::Disk(feed)));
+                HYPERCORE_OK
+            }
+            Err(err) => fail(err),
+        }
+    })
+}
+
+/// Close a feed, releasing its handle. Passing null is a no-op.
+///
+/// # Safety
+/// `feed` must be null or a handle returned by this library that was not
+/// closed before.
+#[no_mangle]
+pub unsafe extern "C" fn hypercore_close(feed: *mut HypercoreFeed) {
+    catch((), || {
+        if !feed.is_null() {
+            drop(Box::from_raw(feed));
+        }
+    })
+}
+
+/// Append `len` bytes starting at `data` to the feed.
+///
+/// # Safety
+/// `feed` must be a valid handle and `data` must point to `len` readable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hypercore_append(
+    feed: *mut HypercoreFeed,
+    data: *const u8,
+    len: usize,
+) -> c_int {
+    catch(HYPERCORE_ERR_FAILED, || {
+        let feed = match feed.as_mut() {
+            Some(feed) => feed,
+            None => return HYPERCORE_ERR_NULL,
+        };
+        if data.is_null() && len > 0 {
+            return HYPERCORE_ERR_NULL;
+        }
+        let data = if len == 0 {
+            &[][..]
+        } else {
+            slice::from_raw_parts(data, len)
+        };
+        match with_feed!(feed, feed => async_std::task::block_on(feed.append(data))) {
+            Ok(_) => HYPERCORE_OK,
+            Err(err) => fail(err),
+        }
+    })
+}
+
+/// Get the block at `index`, writing it into `out`.
+///
+/// # Safety
+/// `feed` must be a valid handle and `out` a valid pointer to write the
+/// buffer to.
+#[no_mangle]
+pub unsafe extern "C" fn hypercore_get(
+    feed: *mut HypercoreFeed,
+    index: u64,
+    out: *mut HypercoreBuffer,
+) -> c_int {
+    catch(HYPERCORE_ERR_FAILED, || {
+        let feed = match feed.as_mut() {
+            Some(feed) => feed,
+            None => return HYPERCORE_ERR_NULL,
+        };
+        if out.is_null() {
+            return HYPERCORE_ERR_NULL;
+        }
+        match with_feed!(feed, feed => async_std::task::block_on(feed.get(index))) {
+            Ok(Some(data)) => {
+                *out = HypercoreBuffer::from_vec(data);
+                HYPERCORE_OK
+            }
+            Ok(None) => HYPERCORE_ERR_NOT_FOUND,
+            Err(err) => fail(err),
+        }
+    })
+}
+
+/// Get the signature covering the feed up to `index`, writing it into `out`.
+///
+/// # Safety
+/// `feed` must be a valid handle and `out` a valid pointer to write the
+/// buffer to.
+#[no_mangle]
+pub unsafe extern "C" fn hypercore_signature(
+    feed: *mut HypercoreFeed,
+    index: u64,
+    out: *mut HypercoreBuffer,
+) -> c_int {
+    catch(HYPERCORE_ERR_FAILED, || {
+        let feed = match feed.as_mut() {
+            Some(feed) => feed,
+            None => return HYPERCORE_ERR_NULL,
+        };
+        if out.is_null() {
+            return HYPERCORE_ERR_NULL;
+        }
+        match with_feed!(feed, feed => async_std::task::block_on(feed.signature(index))) {
+            Ok(signature) => {
+                *out = HypercoreBuffer::from_vec(signature.to_bytes().to_vec());
+                HYPERCORE_OK
+            }
+            Err(err) => fail(err),
+        }
+    })
+}
+
+/// Verify a 64 byte `signature` against the feed up to `index`.
+///
+/// # Safety
+/// `feed` must be a valid handle and `signature` must point to `len`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hypercore_verify(
+    feed: *mut HypercoreFeed,
+    index: u64,
+    signature: *const u8,
+    len: usize,
+) -> c_int {
+    catch(HYPERCORE_ERR_FAILED, || {
+        let feed = match feed.as_mut() {
+            Some(feed) => feed,
+            None => return HYPERCORE_ERR_NULL,
+        };
+        if signature.is_null() {
+            return HYPERCORE_ERR_NULL;
+        }
+        let signature = match Signature::from_bytes(slice::from_raw_parts(signature, len)) {
+            Ok(signature) => signature,
+            Err(err) => {
+                set_last_error(err);
+                return HYPERCORE_ERR_VERIFY;
+            }
+        };
+        match with_feed!(feed, feed => async_std::task::block_on(feed.verify(index, &signature))) {
+            Ok(()) => HYPERCORE_OK,
+            Err(err) => {
+                set_last_error(err);
+                HYPERCORE_ERR_VERIFY
+            }
+        }
+    })
+}
+
+/// Get the number of blocks in the feed, or 0 for a null handle.
+///
+/// # Safety
+/// `feed` must be null or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn hypercore_len(feed: *const HypercoreFeed) -> u64 {
+    catch(0, || match feed.as_ref() {
+        Some(feed) => with_feed!(feed, feed => feed.len()),
+        None => 0,
+    })
+}
+
+/// Get the number of bytes in the feed, or 0 for a null handle.
+///
+/// # Safety
+/// `feed` must be null or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn hypercore_byte_len(feed: *const HypercoreFeed) -> u64 {
+    catch(0, || match feed.as_ref() {
+        Some(feed) => with_feed!(feed, feed => feed.byte_len()),
+        None => 0,
+    })
+}
+
+/// Copy the 32 byte public key of the feed into `out`.
+///
+/// # Safety
+/// `feed` must be a valid handle and `out` must point to 32 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hypercore_public_key(feed: *const HypercoreFeed, out: *mut u8) -> c_int {
+    catch(HYPERCORE_ERR_FAILED, || {
+        let feed = match feed.as_ref() {
+            Some(feed) => feed,
+            None => return HYPERCORE_ERR_NULL,
+        };
+        if out.is_null() {
+            return HYPERCORE_ERR_NULL;
+        }
+        let key = with_feed!(feed, feed => feed.public_key().to_bytes());
+        ptr::copy_nonoverlapping(key.as_ptr(), out, key.len());
+        HYPERCORE_OK
+    })
+}
+
+/// Release a buffer handed out by the library. Empty buffers are a no-op.
+///
+/// # Safety
+/// `buffer` must be null or point to a buffer written by this library that
+/// was not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn hypercore_buffer_free(buffer: *mut HypercoreBuffer) {
+    catch((), || {
+        let buffer = match buffer.as_mut() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        if !buffer.data.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                buffer.data,
+                buffer.len,
+            )));
+        }
+        buffer.data = ptr::null_mut();
+        buffer.len = 0;
+    })
+}
+
+#[test]
+fn should_catch_panics() {
+    let code = catch(HYPERCORE_ERR_FAILED, || panic!("oops"));
+    assert_eq!(code, HYPERCORE_ERR_FAILED);
+    let message = unsafe { CStr::from_ptr(hypercore_last_error()) };
+    assert_eq!(message.to_str().unwrap(), "panicked: oops");
+}
@@ -0,0 +1,75 @@
+use hypercore_ffi::*;
+
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::slice;
+
+#[test]
+fn append_get_verify() {
+    unsafe {
+        let mut feed = ptr::null_mut();
+        assert_eq!(hypercore_create(&mut feed), HYPERCORE_OK);
+
+        assert_eq!(hypercore_append(feed, b"hello".as_ptr(), 5), HYPERCORE_OK);
+        assert_eq!(hypercore_append(feed, b"world".as_ptr(), 5), HYPERCORE_OK);
+        assert_eq!(hypercore_len(feed), 2);
+        assert_eq!(hypercore_byte_len(feed), 10);
+
+        let mut buffer = HypercoreBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        assert_eq!(hypercore_get(feed, 1, &mut buffer), HYPERCORE_OK);
+        assert_eq!(slice::from_raw_parts(buffer.data, buffer.len), b"world");
+        hypercore_buffer_free(&mut buffer);
+        assert!(buffer.data.is_null());
+        assert_eq!(hypercore_get(feed, 2, &mut buffer), HYPERCORE_ERR_NOT_FOUND);
+
+        assert_eq!(hypercore_signature(feed, 1, &mut buffer), HYPERCORE_OK);
+        assert_eq!(buffer.len, 64);
+        assert_eq!(
+            hypercore_verify(feed, 1, buffer.data, buffer.len),
+            HYPERCORE_OK
+        );
+        assert_eq!(
+            hypercore_verify(feed, 0, buffer.data, buffer.len),
+            HYPERCORE_ERR_VERIFY
+        );
+        hypercore_buffer_free(&mut buffer);
+
+        let mut key = [0u8; 32];
+        assert_eq!(hypercore_public_key(feed, key.as_mut_ptr()), HYPERCORE_OK);
+        assert_ne!(key, [0u8; 32]);
+
+        hypercore_close(feed);
+    }
+}
+
+#[test]
+fn open_reports_errors() {
+    unsafe {
+        let dir = tempfile::tempdir().unwrap();
+        let path = CString::new(dir.path().to_str().unwrap()).unwrap();
+
+        let mut feed = ptr::null_mut();
+        assert_eq!(hypercore_open(path.as_ptr(), &mut feed), HYPERCORE_OK);
+        assert_eq!(hypercore_append(feed, b"hello".as_ptr(), 5), HYPERCORE_OK);
+
+        // The directory is locked by the writer above.
+        let mut second = ptr::null_mut();
+        assert_eq!(
+            hypercore_open(path.as_ptr(), &mut second),
+            HYPERCORE_ERR_FAILED
+        );
+        assert!(!hypercore_last_error().is_null());
+        let message = CStr::from_ptr(hypercore_last_error()).to_str().unwrap();
+        assert!(!message.is_empty());
+
+        hypercore_close(feed);
+        assert_eq!(hypercore_open(path.as_ptr(), &mut feed), HYPERCORE_OK);
+        assert_eq!(hypercore_len(feed), 1);
+        hypercore_close(feed);
+
+        assert_eq!(hypercore_open(ptr::null(), &mut feed), HYPERCORE_ERR_NULL);
+    }
+}
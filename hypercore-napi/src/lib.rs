@@ -0,0 +1,155 @@
+#![forbid(future_incompatible)]
+#![forbid(rust_2018_idioms, rust_2018_compatibility)]
+
+//! ## Introduction
+//! Node.js bindings for [hypercore], built with napi-rs. The module exports
+//! a `Feed` class mirroring the Rust API, so JavaScript applications can use
+//! the Rust storage and crypto engine.
+//!
+//! ```js
+//! const { Feed } = require('./hypercore.node')
+//! const feed = Feed.open('./feed.db')
+//! feed.append(Buffer.from('hello'))
+//! feed.get(0) // <Buffer 68 65 6c 6c 6f>
+//! ```
+//!
+//! Calls run to completion on the calling thread.
+//!
+//! [hypercore]: https://docs.rs/hypercore
+
+use hypercore::Signature;
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Result};
+use napi_derive::napi;
+use random_access_disk::RandomAccessDisk;
+use random_access_memory::RandomAccessMemory;
+
+use std::fmt::Display;
+
+#[derive(Debug)]
+// One feed per object, so the variants' sizes don't matter.
+#[allow(clippy::large_enum_variant)]
+enum Inner {
+    Disk(hypercore::Feed<RandomAccessDisk>),
+    Memory(hypercore::Feed<RandomAccessMemory>),
+}
+
+/// Run the same expression against either kind of feed.
+macro_rules! with_feed {
+    ($inner:expr, $feed:ident => $body:expr) => {
+        match $inner {
+            Inner::Disk($feed) => $body,
+            Inner::Memory($feed) => $body,
+        }
+    };
+}
+
+fn to_error(err: impl Display) -> Error {
+    Error::from_reason(err.to_string())
+}
+
+fn to_index(index: i64) -> Result<u64> {
+    if index < 0 {
+        return Err(Error::from_reason(format!("Invalid index {}", index)));
+    }
+    Ok(index as u64)
+}
+
+/// An append-only log.
+#[napi]
+#[derive(Debug)]
+pub struct Feed {
+    inner: Inner,
+}
+
+#[napi]
+impl Feed {
+    /// Create a new feed held in memory.
+    #[napi(factory)]
+    pub fn create() -> Result<Self> {
+        let feed = async_std::task::block_on(async {
+            let storage = hypercore::Storage::new_memory().await?;
+            hypercore::Feed::with_storage(storage).await
+        })
+        .map_err(to_error)?;
+        Ok(Self {
+            inner: Inner::Memory(feed),
+        })
+    }
+
+    /// Open the feed stored in the directory at `path`, creating it if
+    /// needed.
+    #[napi(factory)]
+    pub fn open(path: String) -> Result<Self> {
+        let feed = async_std::task::block_on(hypercore::Feed::open(path)).map_err(to_error)?;
+        Ok(Self {
+            inner: Inner::Disk(feed),
+        })
+    }
+
+    /// Append a block to the feed.
+    #[napi]
+    pub fn append(&mut self, data: Buffer) -> Result<()> {
+        with_feed!(&mut self.inner, feed => async_std::task::block_on(feed.append(&data)))
+            .map(|_| ())
+            .map_err(to_error)
+    }
+
+    /// Get the block at `index`, or `null` if it is not stored locally.
+    #[napi]
+    pub fn get(&mut self, index: i64) -> Result<Option<Buffer>> {
+        let index = to_index(index)?;
+        let data = with_feed!(&mut self.inner, feed => async_std::task::block_on(feed.get(index)))
+            .map_err(to_error)?;
+        Ok(data.map(Buffer::from))
+    }
+
+    /// Get the signature covering the feed up to `index`.
+    #[napi]
+    pub fn signature(&mut self, index: i64) -> Result<Buffer> {
+        let index = to_index(index)?;
+        let signature =
+            with_feed!(&mut self.inner, feed => async_std::task::block_on(feed.signature(index)))
+                .map_err(to_error)?;
+        Ok(signature.to_bytes().to_vec().into())
+    }
+
+    /// Check whether `signature` is valid for the feed up to `index`.
+    #[napi]
+    pub fn verify(&mut self, index: i64, signature: Buffer) -> Result<bool> {
+        let index = to_index(index)?;
+        let signature = match Signature::from_bytes(&signature) {
+            Ok(signature) => signature,
+            Err(_) => return Ok(false),
+        };
+        let result = with_feed!(
+            &mut self.inner,
+            feed => async_std::task::block_on(feed.verify(index, &signature))
+        );
+        Ok(result.is_ok())
+    }
+
+    /// The number of blocks in the feed.
+    #[napi(getter)]
+    pub fn length(&self) -> i64 {
+        with_feed!(&self.inner, feed => feed.len()) as i64
+    }
+
+    /// The number of bytes in the feed.
+    #[napi(getter)]
+    pub fn byte_length(&self) -> i64 {
+        with_feed!(&self.inner, feed => feed.byte_len()) as i64
+    }
+
+    /// The public key of the feed.
+    #[napi(getter)]
+    pub fn key(&self) -> Buffer {
+        with_feed!(&self.inner, feed => feed.public_key().to_bytes().to_vec()).into()
+    }
+
+    /// Whether this feed can be appended to.
+    #[napi(getter)]
+    pub fn writable(&self) -> bool {
+        with_feed!(&self.inner, feed => feed.secret_key().is_some())
+    }
+}
@@ -0,0 +1,30 @@
+// Run after building the addon:
+//   cargo build -p hypercore-napi && cp target/debug/libhypercore_napi.so hypercore-napi/hypercore.node
+//   node hypercore-napi/test/feed.js
+const assert = require('assert')
+const os = require('os')
+const path = require('path')
+const fs = require('fs')
+const { Feed } = require('../hypercore.node')
+
+const feed = Feed.create()
+assert.ok(feed.writable)
+feed.append(Buffer.from('hello'))
+feed.append(Buffer.from('world'))
+assert.strictEqual(feed.length, 2)
+assert.strictEqual(feed.byteLength, 10)
+assert.strictEqual(feed.key.length, 32)
+assert.deepStrictEqual(feed.get(1), Buffer.from('world'))
+assert.strictEqual(feed.get(2), null)
+
+const signature = feed.signature(1)
+assert.ok(feed.verify(1, signature))
+assert.ok(!feed.verify(0, signature))
+
+const dir = fs.mkdtempSync(path.join(os.tmpdir(), 'hypercore-napi-'))
+const disk = Feed.open(dir)
+disk.append(Buffer.from('persisted'))
+assert.deepStrictEqual(disk.get(0), Buffer.from('persisted'))
+assert.throws(() => Feed.open(dir), /lock/i)
+
+console.log('ok')
@@ -0,0 +1,221 @@
+#![forbid(future_incompatible)]
+#![forbid(rust_2018_idioms, rust_2018_compatibility)]
+// Triggered by the code `#[pymethods]` generates for `PyResult` returns.
+#![allow(clippy::useless_conversion)]
+
+//! ## Introduction
+//! Python bindings for [hypercore], built with PyO3. The `hypercore_py`
+//! module, named after the library so Python finds its init function,
+//! exports a `Feed` class mirroring the Rust API.
+//!
+//! ```python
+//! import hypercore_py as hypercore
+//! feed = hypercore.Feed.open("./feed.db")
+//! feed.append(b"hello")
+//! bytes(feed.get(0))  # b'hello'
+//! ```
+//!
+//! Blocks are returned as read-only `memoryview`s over the buffer read from
+//! storage, so they can be handed to numpy and friends without a copy.
+//!
+//! Replication is not exposed yet, as the Rust crate has no replication
+//! protocol to bind to.
+//!
+//! [hypercore]: https://docs.rs/hypercore
+
+use hypercore::Signature;
+use pyo3::exceptions::{PyBufferError, PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyMemoryView};
+use pyo3::{ffi, PyErr};
+use random_access_disk::RandomAccessDisk;
+use random_access_memory::RandomAccessMemory;
+
+use std::fmt::Display;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+
+// One feed per object, so the variants' sizes don't matter.
+#[allow(clippy::large_enum_variant)]
+enum Inner {
+    Disk(hypercore::Feed<RandomAccessDisk>),
+    Memory(hypercore::Feed<RandomAccessMemory>),
+}
+
+/// Run the same expression against either kind of feed.
+macro_rules! with_feed {
+    ($inner:expr, $feed:ident => $body:expr) => {
+        match $inner {
+            Inner::Disk($feed) => $body,
+            Inner::Memory($feed) => $body,
+        }
+    };
+}
+
+fn to_error(err: impl Display) -> PyErr {
+    PyIOError::new_err(err.to_string())
+}
+
+/// An append-only log.
+#[pyclass(module = "hypercore_py")]
+struct Feed {
+    inner: Inner,
+}
+
+#[pymethods]
+impl Feed {
+    /// Create a new feed held in memory.
+    #[staticmethod]
+    fn create() -> PyResult<Self> {
+        let feed = async_std::task::block_on(async {
+            let storage = hypercore::Storage::new_memory().await?;
+            hypercore::Feed::with_storage(storage).await
+        })
+        .map_err(to_error)?;
+        Ok(Self {
+            inner: Inner::Memory(feed),
+        })
+    }
+
+    /// Open the feed stored in the directory at `path`, creating it if
+    /// needed.
+    #[staticmethod]
+    fn open(path: &str) -> PyResult<Self> {
+        let feed = async_std::task::block_on(hypercore::Feed::open(path)).map_err(to_error)?;
+        Ok(Self {
+            inner: Inner::Disk(feed),
+        })
+    }
+
+    /// Append a block to the feed.
+    fn append(&mut self, data: &[u8]) -> PyResult<()> {
+        with_feed!(&mut self.inner, feed => async_std::task::block_on(feed.append(data)))
+            .map(|_| ())
+            .map_err(to_error)
+    }
+
+    /// Get the block at `index` as a read-only `memoryview`, or `None` if it
+    /// is not stored locally.
+    fn get<'py>(
+        &mut self,
+        py: Python<'py>,
+        index: u64,
+    ) -> PyResult<Option<Bound<'py, PyMemoryView>>> {
+        let data = with_feed!(&mut self.inner, feed => async_std::task::block_on(feed.get(index)))
+            .map_err(to_error)?;
+        match data {
+            Some(data) => {
+                let block = Bound::new(py, Block { data })?;
+                Ok(Some(PyMemoryView::from_bound(block.as_any())?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Get the signature covering the feed up to `index`.
+    fn signature<'py>(&mut self, py: Python<'py>, index: u64) -> PyResult<Bound<'py, PyBytes>> {
+        let signature =
+            with_feed!(&mut self.inner, feed => async_std::task::block_on(feed.signature(index)))
+                .map_err(to_error)?;
+        Ok(PyBytes::new_bound(py, &signature.to_bytes()))
+    }
+
+    /// Check whether `signature` is valid for the feed up to `index`.
+    fn verify(&mut self, index: u64, signature: &[u8]) -> PyResult<bool> {
+        let signature =
+            Signature::from_bytes(signature).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let result = with_feed!(
+            &mut self.inner,
+            feed => async_std::task::block_on(feed.verify(index, &signature))
+        );
+        Ok(result.is_ok())
+    }
+
+    /// The number of bytes in the feed.
+    #[getter]
+    fn byte_length(&self) -> u64 {
+        with_feed!(&self.inner, feed => feed.byte_len())
+    }
+
+    /// The public key of the feed.
+    #[getter]
+    fn key<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let key = with_feed!(&self.inner, feed => feed.public_key().to_bytes());
+        PyBytes::new_bound(py, &key)
+    }
+
+    /// Whether this feed can be appended to.
+    #[getter]
+    fn writable(&self) -> bool {
+        with_feed!(&self.inner, feed => feed.secret_key().is_some())
+    }
+
+    fn __len__(&self) -> usize {
+        with_feed!(&self.inner, feed => feed.len()) as usize
+    }
+}
+
+/// An immutable block, exposed through the buffer protocol.
+#[pyclass(module = "hypercore_py", frozen)]
+struct Block {
+    data: Vec<u8>,
+}
+
+#[pymethods]
+impl Block {
+    unsafe fn __getbuffer__(
+        slf: Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("View is null"));
+        }
+        if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("Blocks are read-only"));
+        }
+
+        // The block is frozen, so its data stays put for as long as the view
+        // holds a reference to it.
+        let data = &slf.get().data;
+        (*view).buf = data.as_ptr() as *mut c_void;
+        (*view).len = data.len() as isize;
+        (*view).obj = slf.into_any().into_ptr();
+        (*view).readonly = 1;
+        (*view).itemsize = 1;
+        (*view).format = if (flags & ffi::PyBUF_FORMAT) == ffi::PyBUF_FORMAT {
+            b"B\0".as_ptr() as *mut _
+        } else {
+            ptr::null_mut()
+        };
+        (*view).ndim = 1;
+        (*view).shape = if (flags & ffi::PyBUF_ND) == ffi::PyBUF_ND {
+            &mut (*view).len
+        } else {
+            ptr::null_mut()
+        };
+        (*view).strides = if (flags & ffi::PyBUF_STRIDES) == ffi::PyBUF_STRIDES {
+            &mut (*view).itemsize
+        } else {
+            ptr::null_mut()
+        };
+        (*view).suboffsets = ptr::null_mut();
+        (*view).internal = ptr::null_mut();
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(&self, _view: *mut ffi::Py_buffer) {}
+
+    fn __len__(&self) -> usize {
+        self.data.len()
+    }
+}
+
+/// Python bindings for hypercore.
+#[pymodule]
+#[pyo3(name = "hypercore_py")]
+fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Feed>()?;
+    m.add_class::<Block>()?;
+    Ok(())
+}
@@ -0,0 +1,40 @@
+# Run after building the extension module:
+#   cargo build -p hypercore-py && cp target/debug/libhypercore_py.so hypercore-py/hypercore_py.so
+#   PYTHONPATH=hypercore-py python3 -m unittest discover hypercore-py/test
+import tempfile
+import unittest
+
+import hypercore_py as hypercore
+
+
+class FeedTest(unittest.TestCase):
+    def test_append_get_verify(self):
+        feed = hypercore.Feed.create()
+        self.assertTrue(feed.writable)
+        feed.append(b"hello")
+        feed.append(b"world")
+        self.assertEqual(len(feed), 2)
+        self.assertEqual(feed.byte_length, 10)
+        self.assertEqual(len(feed.key), 32)
+
+        block = feed.get(1)
+        self.assertIsInstance(block, memoryview)
+        self.assertTrue(block.readonly)
+        self.assertEqual(block.tobytes(), b"world")
+        self.assertIsNone(feed.get(2))
+
+        signature = feed.signature(1)
+        self.assertTrue(feed.verify(1, signature))
+        self.assertFalse(feed.verify(0, signature))
+
+    def test_open(self):
+        with tempfile.TemporaryDirectory() as dir:
+            feed = hypercore.Feed.open(dir)
+            feed.append(b"persisted")
+            self.assertEqual(bytes(feed.get(0)), b"persisted")
+            with self.assertRaises(IOError):
+                hypercore.Feed.open(dir)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,206 @@
+//! Bounded log of block reads, to tell hot blocks from cold ones.
+
+use crate::feed::Feed;
+use crate::ranges::Ranges;
+use anyhow::{bail, Result};
+use random_access_storage::RandomAccess;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Debug};
+use std::time::{Duration, SystemTime};
+
+/// Reads of a block recorded by `Feed::record_accesses()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockAccess {
+    /// Number of reads since the block was last added to the log.
+    pub count: u64,
+    /// Time of the last read.
+    pub last: SystemTime,
+}
+
+/// Tier a block moved to, reported to the hook set with
+/// `Feed::on_tier_change()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    /// The block was read the hook's `min_count` times.
+    Hot,
+    /// A hot block was forgotten by the access log, having been read less
+    /// recently than every block it holds.
+    Cold,
+}
+
+/// Hook called as blocks cross the hot threshold.
+struct TierHook {
+    min_count: u64,
+    call: Box<dyn FnMut(u64, Tier) + Send + Sync>,
+}
+
+/// Reads of at most `capacity` blocks, forgetting the least recently read
+/// block once full.
+pub(crate) struct AccessLog {
+    capacity: usize,
+    blocks: HashMap<u64, (BlockAccess, u64)>,
+    /// Blocks by the sequence number of their last read.
+    order: BTreeMap<u64, u64>,
+    sequence: u64,
+    hook: Option<TierHook>,
+}
+
+impl Debug for AccessLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLog")
+            .field("capacity", &self.capacity)
+            .field("blocks", &self.blocks)
+            .field("order", &self.order)
+            .field("sequence", &self.sequence)
+            .field("hook", &self.hook.as_ref().map(|hook| hook.min_count))
+            .finish()
+    }
+}
+
+impl AccessLog {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            blocks: HashMap::new(),
+            order: BTreeMap::new(),
+            sequence: 0,
+            hook: None,
+        }
+    }
+
+    /// Record a read of the block at `index`.
+    pub(crate) fn record(&mut self, index: u64) {
+        self.sequence += 1;
+        let (access, sequence) = self.blocks.entry(index).or_insert((
+            BlockAccess {
+                count: 0,
+                last: SystemTime::UNIX_EPOCH,
+            },
+            0,
+        ));
+        self.order.remove(sequence);
+        access.count += 1;
+        access.last = SystemTime::now();
+        *sequence = self.sequence;
+        self.order.insert(self.sequence, index);
+        let count = access.count;
+        if let Some(hook) = &mut self.hook {
+            if count == hook.min_count {
+                (hook.call)(index, Tier::Hot);
+            }
+        }
+
+        if self.blocks.len() > self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                let forgotten = self.blocks.remove(&oldest);
+                if let (Some(hook), Some((access, _))) = (&mut self.hook, forgotten) {
+                    if access.count >= hook.min_count {
+                        (hook.call)(oldest, Tier::Cold);
+                    }
+                }
+            }
+        }
+    }
+
+    fn get(&self, index: u64) -> Option<BlockAccess> {
+        self.blocks.get(&index).map(|(access, _)| *access)
+    }
+}
+
+impl<T> Feed<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    /// Count the reads of up to `capacity` blocks by `.get()` and
+    /// `.get_slice()`, with the time of the last one, forgetting the least
+    /// recently read blocks first. The log is kept in memory only. A
+    /// capacity of 0 turns it off, which is the default.
+    ///
+    /// Embedders tiering a feed's storage use `.cold_blocks()` and
+    /// `.hot_blocks()` to pick the ranges to move, or `.on_tier_change()` to
+    /// be told as reads happen.
+    pub fn record_accesses(&mut self, capacity: usize) {
+        self.access_log = match capacity {
+            0 => None,
+            capacity => Some(AccessLog::new(capacity)),
+        };
+    }
+
+    /// Call `hook` with the index of a block when it has been read
+    /// `min_count` times, as [Tier::Hot], and when the access log forgets a
+    /// block it had reported hot, as [Tier::Cold]. Blocks that only go idle
+    /// are not reported; poll `.cold_blocks()` for those. The hook runs
+    /// inside `.get()`, so it should hand the work of moving blocks off
+    /// rather than do it. Needs the access log, and is dropped along with
+    /// it by `.record_accesses()`.
+    pub fn on_tier_change<F>(&mut self, min_count: u64, hook: F) -> Result<()>
+    where
+        F: FnMut(u64, Tier) + Send + Sync + 'static,
+    {
+        match &mut self.access_log {
+            Some(log) => {
+                log.hook = Some(TierHook {
+                    min_count: min_count.max(1),
+                    call: Box::new(hook),
+                });
+                Ok(())
+            }
+            None => bail!("The access log is off, see `.record_accesses()`"),
+        }
+    }
+
+    /// Get the recorded reads of the block at `index`, if it is in the
+    /// access log.
+    pub fn block_access(&self, index: u64) -> Option<BlockAccess> {
+        self.access_log.as_ref()?.get(index)
+    }
+
+    /// Get the blocks stored locally that were not read in the last `idle`,
+    /// including those never read or forgotten by the access log. Without
+    /// an access log, every stored block is cold.
+    pub fn cold_blocks(&mut self, idle: Duration) -> Ranges {
+        let since = SystemTime::now().checked_sub(idle);
+        let log = self.access_log.as_ref();
+        let mut cold = Ranges::new();
+        for index in 0..self.length {
+            if !self.bitfield.get(index) {
+                continue;
+            }
+            let last = log.and_then(|log| log.get(index)).map(|access| access.last);
+            let read_since = match (last, since) {
+                (Some(last), Some(since)) => last >= since,
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+            if !read_since {
+                cold.add(index..index + 1);
+            }
+        }
+        cold
+    }
+
+    /// Get the blocks in the access log read at least `min_count` times.
+    pub fn hot_blocks(&self, min_count: u64) -> Ranges {
+        let mut hot = Ranges::new();
+        if let Some(log) = &self.access_log {
+            for (index, (access, _)) in &log.blocks {
+                if access.count >= min_count {
+                    hot.add(*index..*index + 1);
+                }
+            }
+        }
+        hot
+    }
+}
+
+#[test]
+fn should_forget_least_recently_read_blocks() {
+    let mut log = AccessLog::new(2);
+    log.record(1);
+    log.record(2);
+    log.record(1);
+    log.record(3);
+    assert_eq!(log.get(1).map(|access| access.count), Some(2));
+    assert_eq!(log.get(2), None);
+    assert_eq!(log.get(3).map(|access| access.count), Some(1));
+}
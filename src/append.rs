@@ -0,0 +1,40 @@
+use crate::Signature;
+
+/// The outcome of an append, returned by the `.append()` method.
+///
+/// The blocks are readable as soon as the outcome is returned: a following
+/// `.get(outcome.index)` sees them without any flush, on every storage
+/// backend.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AppendOutcome {
+    /// The index of the first block appended.
+    pub index: u64,
+    /// The number of blocks in the feed after the append.
+    pub length: u64,
+    /// The number of bytes in the feed after the append.
+    pub byte_length: u64,
+    /// The signature covering the feed after the append.
+    pub signature: Signature,
+}
+
+impl AppendOutcome {
+    /// Access the `index` field from the outcome.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Access the `length` field from the outcome.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Access the `byte_length` field from the outcome.
+    pub fn byte_length(&self) -> u64 {
+        self.byte_length
+    }
+
+    /// Access the `signature` field from the outcome.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+}
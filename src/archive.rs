@@ -0,0 +1,211 @@
+//! Archival copies of a feed, verifying every block along the way.
+
+use crate::download::Source;
+use crate::feed::Feed;
+use crate::proof::Proof;
+use anyhow::{ensure, Result};
+use ed25519_dalek::PublicKey;
+use random_access_disk::RandomAccessDisk;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Outcome of verifying a single block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockStatus {
+    /// The block hashes up to the signed roots of the feed.
+    Verified,
+    /// The block, its proof or its tree nodes failed to verify.
+    Invalid(String),
+    /// The block or a node needed to verify it is not available.
+    Missing(String),
+}
+
+/// Per-block result of `Feed::clone_verify()` and `Feed::verify_range()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockReport {
+    /// Index of the block.
+    pub index: u64,
+    /// Number of bytes in the block, if it was received.
+    pub bytes: Option<u64>,
+    /// Whether the block verified.
+    pub status: BlockStatus,
+}
+
+/// Report of an archival copy, created by `Feed::clone_verify()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveReport {
+    /// Number of blocks the source claimed to have.
+    pub length: u64,
+    /// Result of every block, in order.
+    pub blocks: Vec<BlockReport>,
+    /// Whether the signature covering the whole copy verified.
+    pub signature_verified: bool,
+}
+
+impl ArchiveReport {
+    /// Number of blocks that verified.
+    pub fn verified(&self) -> u64 {
+        self.blocks
+            .iter()
+            .filter(|block| block.status == BlockStatus::Verified)
+            .count() as u64
+    }
+
+    /// Check whether every block and the final signature verified.
+    pub fn is_complete(&self) -> bool {
+        self.signature_verified && self.verified() == self.length
+    }
+
+    /// Encode the report as JSON, for archiving workflows that keep it
+    /// next to the copy.
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            "{{\"length\":{},\"signatureVerified\":{},\"blocks\":[",
+            self.length, self.signature_verified
+        );
+        for (i, block) in self.blocks.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let (status, error) = match &block.status {
+                BlockStatus::Verified => ("verified", None),
+                BlockStatus::Invalid(error) => ("invalid", Some(error)),
+                BlockStatus::Missing(error) => ("missing", Some(error)),
+            };
+            write!(
+                json,
+                "{{\"index\":{},\"status\":\"{}\"",
+                block.index, status
+            )
+            .unwrap();
+            if let Some(bytes) = block.bytes {
+                write!(json, ",\"bytes\":{}", bytes).unwrap();
+            }
+            if let Some(error) = error {
+                write!(json, ",\"error\":\"{}\"", escape_json(error)).unwrap();
+            }
+            json.push('}');
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+impl Feed<RandomAccessDisk> {
+    /// Copy every block of the feed with `public_key` from `source` into a
+    /// new directory at `path`, verifying each one against the signed
+    /// tree, and report on every block. Unlike `download_to_path()`, a
+    /// block that is missing or fails to verify doesn't stop the copy.
+    pub async fn clone_verify<P, S>(
+        public_key: PublicKey,
+        path: P,
+        source: &mut S,
+    ) -> Result<(Self, ArchiveReport)>
+    where
+        P: AsRef<Path>,
+        S: Source + ?Sized,
+    {
+        let mut feed = Self::open_replica(public_key, path.as_ref()).await?;
+        ensure!(
+            feed.is_empty(),
+            "Archives must be copied into an empty directory"
+        );
+
+        let length = source.remote_length().await?;
+        let mut blocks = Vec::with_capacity(length as usize);
+        for index in 0..length {
+            let request = feed.request(index);
+            let message = match source.request(&request).await {
+                Ok(message) => message,
+                Err(err) => {
+                    blocks.push(BlockReport {
+                        index,
+                        bytes: None,
+                        status: BlockStatus::Missing(err.to_string()),
+                    });
+                    continue;
+                }
+            };
+            let (bytes, result) = match Proof::decode(&message) {
+                Ok((proof, data)) => {
+                    let bytes = data.as_ref().map(|data| data.len() as u64);
+                    let result = if proof.index != index {
+                        Err(anyhow::anyhow!("Got block {}", proof.index))
+                    } else if data.is_none() {
+                        Err(anyhow::anyhow!("Got no data"))
+                    } else {
+                        feed.put(index, data.as_deref(), proof).await
+                    };
+                    (bytes, result)
+                }
+                Err(err) => (None, Err(err)),
+            };
+            blocks.push(BlockReport {
+                index,
+                bytes,
+                status: match result {
+                    Ok(()) => BlockStatus::Verified,
+                    Err(err) => BlockStatus::Invalid(err.to_string()),
+                },
+            });
+        }
+
+        let signature_verified = match length.checked_sub(1) {
+            Some(last) => match feed.signature(last).await {
+                Ok(signature) => feed.verify(last, &signature).await.is_ok(),
+                Err(_) => false,
+            },
+            None => true,
+        };
+        let report = ArchiveReport {
+            length,
+            blocks,
+            signature_verified,
+        };
+        Ok((feed, report))
+    }
+}
+
+/// Escape a string for a JSON string literal.
+fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[test]
+fn should_encode_reports_as_json() {
+    let report = ArchiveReport {
+        length: 2,
+        blocks: vec![
+            BlockReport {
+                index: 0,
+                bytes: Some(5),
+                status: BlockStatus::Verified,
+            },
+            BlockReport {
+                index: 1,
+                bytes: None,
+                status: BlockStatus::Missing("no \"data\"\n".into()),
+            },
+        ],
+        signature_verified: false,
+    };
+    assert_eq!(
+        report.to_json(),
+        concat!(
+            r#"{"length":2,"signatureVerified":false,"blocks":["#,
+            r#"{"index":0,"status":"verified","bytes":5},"#,
+            r#"{"index":1,"status":"missing","error":"no \"data\"\n"}]}"#
+        )
+    );
+    assert!(!report.is_complete());
+}
@@ -0,0 +1,48 @@
+//! Copying the files of a feed while it is open.
+
+use crate::feed::Feed;
+use anyhow::Result;
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
+use std::ops::Deref;
+
+/// A feed frozen for a backup by `Feed::freeze_for_backup()`.
+///
+/// The guard borrows the feed, so nothing can be appended or stored until
+/// it is dropped, and every store was flushed when it was created: while it
+/// lives, the files in the feed's directory are consistent with each other
+/// and can be copied. The guard only derefs to `&Feed`, so methods taking
+/// `&self` such as `.len()`, `.byte_len()` and `.public_key()` are
+/// available, while reading blocks with `.get()` waits until it is dropped.
+/// Feeds shared behind a mutex stay locked for as long as the guard is
+/// held, so copy the files and drop it promptly.
+#[derive(Debug)]
+pub struct BackupGuard<'a, T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    feed: &'a mut Feed<T>,
+}
+
+impl<T> Deref for BackupGuard<'_, T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    type Target = Feed<T>;
+
+    fn deref(&self) -> &Feed<T> {
+        self.feed
+    }
+}
+
+impl<T> Feed<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    /// Flush every store and freeze the feed until the returned guard is
+    /// dropped, to back up its directory without closing it.
+    pub async fn freeze_for_backup(&mut self) -> Result<BackupGuard<'_, T>> {
+        self.storage.sync_all().await?;
+        Ok(BackupGuard { feed: self })
+    }
+}
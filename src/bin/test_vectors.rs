@@ -0,0 +1,77 @@
+//! Print JSON test vectors for a feed derived from a seed, so other
+//! implementations can cross-check their hashes, signatures and proofs.
+//!
+//! ```sh
+//! cargo run --features test-vectors --bin test_vectors -- <seed> [blocks]
+//! ```
+//!
+//! The feed is built by `Feed::test_with_seed()`: the secret key is the 32
+//! byte BLAKE2b hash of the seed, and block `i` holds the bytes of
+//! `"<seed>-<i>"`.
+
+use anyhow::{Context, Result};
+use async_std::task;
+use data_encoding::HEXLOWER;
+use hypercore::{Feed, Node, NodeTrait};
+use serde_json::{json, Value};
+
+fn node_json(node: &Node) -> Value {
+    json!({
+        "index": node.index(),
+        "hash": HEXLOWER.encode(node.hash()),
+        "size": node.len(),
+    })
+}
+
+async fn vectors(seed: &str, blocks: u64) -> Result<Value> {
+    let mut feed = Feed::test_with_seed(seed, blocks).await?;
+    let (public, secret) = (*feed.public_key(), feed.secret_key().as_ref().unwrap());
+    let secret = HEXLOWER.encode(secret.as_bytes());
+
+    let mut data = vec![];
+    for index in 0..blocks {
+        data.push(HEXLOWER.encode(format!("{}-{}", seed, index).as_bytes()));
+    }
+
+    let tree = feed.nodes(0..2 * blocks).await?;
+    let mut proofs = vec![];
+    for index in 0..blocks {
+        let block = feed.get(index).await?;
+        let proof = feed.proof(index, false).await?;
+        proofs.push(json!({
+            "index": index,
+            "nodes": proof.nodes().iter().map(|node| node.index()).collect::<Vec<_>>(),
+            "data": HEXLOWER.encode(&proof.encode(block.as_deref())),
+        }));
+    }
+    let (roots, signature) = match blocks {
+        0 => (vec![], None),
+        _ => (
+            feed.root_hashes(blocks - 1).await?,
+            Some(HEXLOWER.encode(&feed.signature(blocks - 1).await?.to_bytes())),
+        ),
+    };
+
+    Ok(json!({
+        "seed": seed,
+        "publicKey": HEXLOWER.encode(public.as_bytes()),
+        "secretKey": secret,
+        "blocks": data,
+        "tree": tree.iter().map(node_json).collect::<Vec<_>>(),
+        "roots": roots.iter().map(node_json).collect::<Vec<_>>(),
+        "signature": signature,
+        "proofs": proofs,
+    }))
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let seed = args.next().context("usage: test_vectors <seed> [blocks]")?;
+    let blocks = match args.next() {
+        Some(blocks) => blocks.parse().context("blocks must be a number")?,
+        None => 8,
+    };
+    let vectors = task::block_on(vectors(&seed, blocks))?;
+    println!("{}", serde_json::to_string_pretty(&vectors)?);
+    Ok(())
+}
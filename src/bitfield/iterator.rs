@@ -30,7 +30,7 @@ impl<'a> Iterator<'a> {
     pub fn range(&mut self, start: u64, end: u64) {
         self.start = start;
         self.end = end;
-        self.index_end = 2 * ((end + 31) / 32);
+        self.index_end = 2 * end.div_ceil(32);
 
         if self.end > self.bitfield.length {
             self.bitfield.expand(self.end);
@@ -93,6 +93,11 @@ impl Bitfield {
         self.data.get(index as usize)
     }
 
+    /// Get the byte of the data bitfield that holds the bit for `index`.
+    pub fn data_byte(&self, index: u64) -> u8 {
+        self.data.get_byte((index / 8) as usize)
+    }
+
     /// Calculate the total for the whole data.
     pub fn total(&mut self) -> u8 {
         let len = self.data.len() as u64;
@@ -250,15 +255,15 @@ impl Bitfield {
             let index = p as usize;
             let page = self.data.pages.get(index);
             if let Some(page) = page {
-                if page.len() != 0 {
+                if !page.is_empty() {
                     buf.set_position((p * page_size - offset) as u64);
-                    buf.write_all(&page)?;
+                    buf.write_all(page)?;
                 }
             }
             p += 1.0;
         }
 
-        Ok(bitfield_rle::encode(&buf.into_inner()))
+        Ok(bitfield_rle::encode(buf.into_inner()))
     }
 
     /// Constructs an iterator from start to end
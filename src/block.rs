@@ -0,0 +1,60 @@
+use crate::feed::Feed;
+use anyhow::Result;
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
+
+/// Metadata of a block, returned by `Feed::block()`.
+///
+/// Creating a block only reads its node from the tree; the data is loaded
+/// by `.data()`, so consumers that only need the metadata don't read the
+/// payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub(crate) index: u64,
+    pub(crate) byte_offset: u64,
+    pub(crate) len: u64,
+    pub(crate) hash: Vec<u8>,
+    pub(crate) is_downloaded: bool,
+}
+
+impl Block {
+    /// Get the index of the block.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Get the byte offset of the block within the feed.
+    pub fn byte_offset(&self) -> u64 {
+        self.byte_offset
+    }
+
+    /// Get the length of the block in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Check whether the block is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the hash of the block, as stored in the tree.
+    pub fn hash(&self) -> &[u8] {
+        &self.hash
+    }
+
+    /// Check whether the data of the block was stored locally when the
+    /// block was created.
+    pub fn is_downloaded(&self) -> bool {
+        self.is_downloaded
+    }
+
+    /// Load the data of the block from `feed`. Returns `None` if the data is
+    /// not stored locally.
+    pub async fn data<T>(&self, feed: &mut Feed<T>) -> Result<Option<Vec<u8>>>
+    where
+        T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+    {
+        feed.get(self.index).await
+    }
+}
@@ -0,0 +1,250 @@
+//! Compatibility with feed directories written by the JavaScript
+//! implementation.
+
+use crate::crypto::{Hash, PublicKey, SecretKey};
+use crate::feed::{hash_with_length_as_bytes, verify_compat};
+use crate::storage::Node;
+use anyhow::{ensure, Result};
+use ed25519_dalek::Signature;
+use flat_tree as flat;
+use sleep_parser::{create_bitfield, create_signatures, create_tree};
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const HEADER_LEN: u64 = 32;
+const NODE_LEN: u64 = 40;
+const SIGNATURE_LEN: u64 = 64;
+
+/// A way in which a feed directory deviates from the layout written by the
+/// JavaScript implementation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Deviation {
+    /// A file every feed directory should contain is missing.
+    MissingFile(&'static str),
+    /// The `key` file should contain only the 32 byte public key.
+    KeyLength(u64),
+    /// The `secret_key` file should contain a 64 byte ed25519 keypair.
+    SecretKeyLength(u64),
+    /// A SLEEP header does not match the expected header.
+    InvalidHeader(&'static str),
+    /// A file body is not a whole number of entries.
+    TrailingBytes(&'static str, u64),
+    /// The tree store does not contain the nodes covered by the signatures.
+    MissingNodes {
+        /// Number of nodes expected for the signed length.
+        expected: u64,
+        /// Number of nodes found.
+        actual: u64,
+    },
+    /// The data store length does not match the length of the tree roots.
+    DataLength {
+        /// Byte length according to the tree roots.
+        expected: u64,
+        /// Byte length of the data store.
+        actual: u64,
+    },
+    /// The latest signature does not verify against the tree roots.
+    InvalidSignature(u64),
+}
+
+impl Display for Deviation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Deviation::MissingFile(name) => write!(f, "missing file `{}`", name),
+            Deviation::KeyLength(len) => {
+                write!(f, "`key` is {} bytes, expected a 32 byte public key", len)
+            }
+            Deviation::SecretKeyLength(len) => {
+                write!(
+                    f,
+                    "`secret_key` is {} bytes, expected a 64 byte keypair",
+                    len
+                )
+            }
+            Deviation::InvalidHeader(name) => write!(f, "`{}` has an invalid SLEEP header", name),
+            Deviation::TrailingBytes(name, len) => {
+                write!(
+                    f,
+                    "`{}` has {} trailing bytes after its last entry",
+                    name, len
+                )
+            }
+            Deviation::MissingNodes { expected, actual } => write!(
+                f,
+                "`tree` holds {} nodes, expected {} for the signed length",
+                actual, expected
+            ),
+            Deviation::DataLength { expected, actual } => write!(
+                f,
+                "`data` is {} bytes, the tree roots cover {} bytes",
+                actual, expected
+            ),
+            Deviation::InvalidSignature(index) => {
+                write!(f, "signature {} does not match the tree roots", index)
+            }
+        }
+    }
+}
+
+/// The compatibility report for a feed directory, created by
+/// `CompatReport::for_dir()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompatReport {
+    /// The number of signed blocks found.
+    pub length: u64,
+    /// Deviations from the JavaScript layout.
+    pub deviations: Vec<Deviation>,
+}
+
+impl CompatReport {
+    /// Inspect a feed directory without modifying it, listing every
+    /// deviation from the layout the JavaScript implementation writes.
+    pub fn for_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut deviations = vec![];
+
+        for name in &["key", "tree", "data", "bitfield", "signatures"] {
+            if !dir.join(name).exists() {
+                deviations.push(Deviation::MissingFile(name));
+            }
+        }
+        if !deviations.is_empty() {
+            return Ok(Self {
+                length: 0,
+                deviations,
+            });
+        }
+
+        let key_len = file_len(dir, "key")?;
+        if key_len != 32 {
+            deviations.push(Deviation::KeyLength(key_len));
+        }
+        if dir.join("secret_key").exists() {
+            let len = file_len(dir, "secret_key")?;
+            if len != 64 {
+                deviations.push(Deviation::SecretKeyLength(len));
+            }
+        }
+
+        let headers = [
+            ("tree", create_tree().to_vec()),
+            ("signatures", create_signatures().to_vec()),
+            ("bitfield", create_bitfield().to_vec()),
+        ];
+        for (name, expected) in headers.iter() {
+            if file_len(dir, name)? < HEADER_LEN || read_at(dir, name, 0, HEADER_LEN)? != *expected
+            {
+                deviations.push(Deviation::InvalidHeader(name));
+            }
+        }
+
+        let tree_len = file_len(dir, "tree")?.saturating_sub(HEADER_LEN);
+        if tree_len % NODE_LEN != 0 {
+            deviations.push(Deviation::TrailingBytes("tree", tree_len % NODE_LEN));
+        }
+        let signatures_len = file_len(dir, "signatures")?.saturating_sub(HEADER_LEN);
+        if signatures_len % SIGNATURE_LEN != 0 {
+            deviations.push(Deviation::TrailingBytes(
+                "signatures",
+                signatures_len % SIGNATURE_LEN,
+            ));
+        }
+
+        let length = signatures_len / SIGNATURE_LEN;
+        if length == 0 {
+            return Ok(Self { length, deviations });
+        }
+
+        let nodes = tree_len / NODE_LEN;
+        if nodes < 2 * length - 1 {
+            deviations.push(Deviation::MissingNodes {
+                expected: 2 * length - 1,
+                actual: nodes,
+            });
+            return Ok(Self { length, deviations });
+        }
+
+        let mut indexes = vec![];
+        flat::full_roots(2 * length, &mut indexes);
+        let mut roots = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            let buf = read_at(dir, "tree", HEADER_LEN + NODE_LEN * index, NODE_LEN)?;
+            roots.push(Node::from_bytes(index, &buf)?);
+        }
+
+        let byte_length = roots.iter().map(|root| root.length).sum();
+        let data_len = file_len(dir, "data")?;
+        if data_len != byte_length {
+            deviations.push(Deviation::DataLength {
+                expected: byte_length,
+                actual: data_len,
+            });
+        }
+
+        let public_key = PublicKey::from_bytes(&read_at(dir, "key", 0, 32)?)?;
+        let offset = HEADER_LEN + SIGNATURE_LEN * (length - 1);
+        let signature = Signature::from_bytes(&read_at(dir, "signatures", offset, SIGNATURE_LEN)?);
+        let message = hash_with_length_as_bytes(Hash::from_roots(&roots), length);
+        let valid = match signature {
+            Ok(signature) => verify_compat(&public_key, &message, Some(&signature)).is_ok(),
+            Err(_) => false,
+        };
+        if !valid {
+            deviations.push(Deviation::InvalidSignature(length - 1));
+        }
+
+        Ok(Self { length, deviations })
+    }
+
+    /// Check if no deviations were found.
+    pub fn is_compatible(&self) -> bool {
+        self.deviations.is_empty()
+    }
+}
+
+/// Read the secret key from the `secret_key` file the JavaScript
+/// implementation writes, if there is one.
+pub(crate) fn read_secret_key(dir: &Path, public_key: &PublicKey) -> Result<Option<SecretKey>> {
+    if !dir.join("secret_key").exists() {
+        return Ok(None);
+    }
+    let buf = read_at(dir, "secret_key", 0, 64)?;
+    let secret_key = SecretKey::from_bytes(&buf[..32])?;
+    ensure!(
+        PublicKey::from(&secret_key) == *public_key,
+        "`secret_key` does not belong to the public key in `key`"
+    );
+    Ok(Some(secret_key))
+}
+
+/// Overwrite and remove the `secret_key` file the JavaScript implementation
+/// writes, if there is one.
+#[cfg(feature = "encryption")]
+pub(crate) fn remove_secret_key(dir: &Path) -> Result<()> {
+    use std::io::Write;
+
+    let path = dir.join("secret_key");
+    if !path.exists() {
+        return Ok(());
+    }
+    let len = file_len(dir, "secret_key")?;
+    let mut file = std::fs::OpenOptions::new().write(true).open(&path)?;
+    file.write_all(&vec![0; len as usize])?;
+    file.sync_all()?;
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+fn file_len(dir: &Path, name: &str) -> Result<u64> {
+    Ok(dir.join(name).metadata()?.len())
+}
+
+fn read_at(dir: &Path, name: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+    let mut file = File::open(dir.join(name))?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0; len as usize];
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
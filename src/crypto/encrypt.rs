@@ -0,0 +1,95 @@
+//! Encryption of the secret key at rest, with a key derived from a
+//! passphrase with Argon2id and sealed with XChaCha20-Poly1305.
+
+use super::key_pair::{PublicKey, SecretKey};
+use crate::storage::ENCRYPTED_KEY_MAGIC as MAGIC;
+use anyhow::{anyhow, ensure, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use ed25519_dalek::SECRET_KEY_LENGTH;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+/// Length of an encrypted secret key, including the magic bytes.
+pub(crate) const ENCRYPTED_KEY_LEN: usize = 4 + SALT_LEN + NONCE_LEN + SECRET_KEY_LENGTH + TAG_LEN;
+
+/// Encrypt `secret` with a key derived from `passphrase`. The public key is
+/// authenticated along with it, so the result can't be moved to another
+/// feed.
+pub(crate) fn encrypt_secret_key(
+    public: &PublicKey,
+    secret: &SecretKey,
+    passphrase: &[u8],
+) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let cipher = cipher(passphrase, &salt)?;
+    let payload = Payload {
+        msg: secret.as_bytes(),
+        aad: public.as_bytes(),
+    };
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), payload)
+        .map_err(|_| anyhow!("Failed to encrypt the secret key"))?;
+
+    let mut buf = Vec::with_capacity(ENCRYPTED_KEY_LEN);
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&salt);
+    buf.extend_from_slice(&nonce);
+    buf.extend_from_slice(&ciphertext);
+    Ok(buf)
+}
+
+/// Decrypt a secret key encrypted by `encrypt_secret_key()`.
+pub(crate) fn decrypt_secret_key(
+    public: &PublicKey,
+    buf: &[u8],
+    passphrase: &[u8],
+) -> Result<SecretKey> {
+    ensure!(
+        buf.len() == ENCRYPTED_KEY_LEN && &buf[..4] == MAGIC,
+        "Secret key is not encrypted"
+    );
+    let (salt, rest) = buf[4..].split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let cipher = cipher(passphrase, salt)?;
+    let payload = Payload {
+        msg: ciphertext,
+        aad: public.as_bytes(),
+    };
+    let plaintext = cipher
+        .decrypt(XNonce::from_slice(nonce), payload)
+        .map_err(|_| anyhow!("Wrong passphrase for the secret key"))?;
+    Ok(SecretKey::from_bytes(&plaintext)?)
+}
+
+/// Derive the cipher for a passphrase and salt.
+fn cipher(passphrase: &[u8], salt: &[u8]) -> Result<XChaCha20Poly1305> {
+    let mut key = [0; 32];
+    Argon2::default()
+        .hash_password_into(passphrase, salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive a key from the passphrase: {}", e))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+#[test]
+fn can_encrypt_secret_keys() {
+    let keypair = super::key_pair::generate();
+    let buf = encrypt_secret_key(&keypair.public, &keypair.secret, b"hunter2").unwrap();
+    assert_eq!(buf.len(), ENCRYPTED_KEY_LEN);
+
+    let secret = decrypt_secret_key(&keypair.public, &buf, b"hunter2").unwrap();
+    assert_eq!(secret.as_bytes(), keypair.secret.as_bytes());
+    assert!(decrypt_secret_key(&keypair.public, &buf, b"hunter3").is_err());
+
+    let other = super::key_pair::generate();
+    assert!(decrypt_secret_key(&other.public, &buf, b"hunter2").is_err());
+}
@@ -24,13 +24,22 @@ pub struct Hash {
 impl Hash {
     /// Hash a `Leaf` node.
     pub fn from_leaf(data: &[u8]) -> Self {
-        let size = u64_as_be(data.len() as u64);
+        let mut hasher = Self::leaf_hasher(data.len() as u64);
+        hasher.update(data);
+        Self::from_hasher(hasher)
+    }
 
+    /// Start hashing a `Leaf` node of `len` bytes, for data fed in chunks.
+    /// The hash is taken with `Hash::from_hasher()`.
+    pub(crate) fn leaf_hasher(len: u64) -> Blake2b {
         let mut hasher = Blake2b::new(32);
         hasher.update(&LEAF_TYPE);
-        hasher.update(&size);
-        hasher.update(data);
+        hasher.update(&u64_as_be(len));
+        hasher
+    }
 
+    /// Finish a hash started with `Hash::leaf_hasher()`.
+    pub(crate) fn from_hasher(hasher: Blake2b) -> Self {
         Self {
             hash: hasher.finalize(),
         }
@@ -44,7 +53,7 @@ impl Hash {
             (right, left)
         };
 
-        let size = u64_as_be((node1.length + node2.length) as u64);
+        let size = u64_as_be(node1.length + node2.length);
 
         let mut hasher = Blake2b::new(32);
         hasher.update(&PARENT_TYPE);
@@ -76,8 +85,8 @@ impl Hash {
         for node in roots {
             let node = node.as_ref();
             hasher.update(node.hash());
-            hasher.update(&u64_as_be((node.index()) as u64));
-            hasher.update(&u64_as_be((node.len()) as u64));
+            hasher.update(&u64_as_be(node.index()));
+            hasher.update(&u64_as_be(node.len()));
         }
 
         Self {
@@ -0,0 +1,107 @@
+//! Deterministic derivation of feed key pairs from a single seed.
+
+use super::key_pair::{Keypair, PublicKey, SecretKey};
+use blake2_rfc::blake2b::blake2b;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::fmt;
+
+/// Domain separation for deriving a child chain.
+const CHILD: &[u8] = b"hypercore key chain child";
+/// Domain separation for deriving the key pair of a chain.
+const KEYPAIR: &[u8] = b"hypercore key chain keypair";
+
+/// A tree of key pairs derived from a 32 byte seed.
+///
+/// Every node has a key pair, and children derived by label. Backing up the
+/// seed of the root is enough to recover every key pair derived from it:
+///
+/// ```rust
+/// use hypercore::KeyChain;
+///
+/// let root = KeyChain::generate();
+/// let photos = root.derive("apps").derive("photos").keypair();
+///
+/// let restored = KeyChain::from_seed(*root.seed());
+/// assert_eq!(restored.path("apps/photos").keypair().public, photos.public);
+/// ```
+///
+/// Derivation is one-way: a child does not reveal its parent or siblings.
+pub struct KeyChain {
+    seed: [u8; 32],
+}
+
+impl KeyChain {
+    /// Create the root of a chain from a seed.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        Self { seed }
+    }
+
+    /// Create the root of a chain from a random seed.
+    pub fn generate() -> Self {
+        let mut seed = [0; 32];
+        OsRng.fill_bytes(&mut seed);
+        Self { seed }
+    }
+
+    /// Get the seed, to back up the chain.
+    pub fn seed(&self) -> &[u8; 32] {
+        &self.seed
+    }
+
+    /// Derive the child chain with `label`.
+    pub fn derive(&self, label: &str) -> Self {
+        let mut message = CHILD.to_vec();
+        message.extend_from_slice(label.as_bytes());
+        let mut seed = [0; 32];
+        seed.copy_from_slice(blake2b(32, &self.seed, &message).as_bytes());
+        Self { seed }
+    }
+
+    /// Derive a descendant along a `/` separated path of labels. Empty
+    /// labels are skipped, so `"a//b/"` is the same path as `"a/b"`.
+    pub fn path(&self, path: &str) -> Self {
+        path.split('/')
+            .filter(|label| !label.is_empty())
+            .fold(Self::from_seed(self.seed), |chain, label| {
+                chain.derive(label)
+            })
+    }
+
+    /// Get the key pair of this chain.
+    pub fn keypair(&self) -> Keypair {
+        let hash = blake2b(32, &self.seed, KEYPAIR);
+        let secret = SecretKey::from_bytes(hash.as_bytes()).expect("32 byte secret key");
+        let public = PublicKey::from(&secret);
+        Keypair { secret, public }
+    }
+}
+
+// Keep the seed out of logs.
+impl fmt::Debug for KeyChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyChain").finish_non_exhaustive()
+    }
+}
+
+#[test]
+fn should_derive_deterministically() {
+    let root = KeyChain::from_seed([7; 32]);
+    let a = root.derive("a").keypair();
+    assert_eq!(
+        a.public,
+        KeyChain::from_seed([7; 32]).derive("a").keypair().public
+    );
+    assert_ne!(a.public, root.derive("b").keypair().public);
+    assert_ne!(a.public, root.keypair().public);
+    assert_ne!(
+        a.public,
+        KeyChain::from_seed([8; 32]).derive("a").keypair().public
+    );
+
+    let nested = root.derive("a").derive("b").keypair();
+    assert_eq!(root.path("a/b").keypair().public, nested.public);
+    assert_eq!(root.path("/a//b/").keypair().public, nested.public);
+    assert_eq!(root.path("").seed(), root.seed());
+    assert_eq!(format!("{:?}", root), "KeyChain { .. }");
+}
@@ -0,0 +1,183 @@
+//! Encoding of keys as strings, for URIs and for people to copy around.
+
+use super::hash::Hash;
+use super::key_pair::PublicKey;
+use anyhow::{anyhow, bail, ensure, Result};
+use blake2_rfc::blake2b::Blake2b;
+
+/// Length of public and discovery keys.
+pub const KEY_LENGTH: usize = 32;
+
+/// Length of the checksum appended to keys by `encode_key()`.
+const CHECKSUM_LENGTH: usize = 4;
+
+/// Alphabet of z-base-32.
+const Z_BASE_32: &[u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+/// How `encode_key()` encodes a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// Lowercase hex, 64 characters.
+    Hex,
+    /// z-base-32, 52 characters, as used in `hyper://` URIs.
+    ZBase32,
+}
+
+/// Encode a public or discovery key. With `checksum`, the first bytes of
+/// the key's BLAKE2b hash are appended, so `decode_key()` catches typos:
+/// 72 hex or 58 z-base-32 characters in total.
+pub fn encode_key(key: &[u8; KEY_LENGTH], format: KeyFormat, checksum: bool) -> String {
+    let mut bytes = key.to_vec();
+    if checksum {
+        bytes.extend_from_slice(&key_checksum(key));
+    }
+    match format {
+        KeyFormat::Hex => to_hex(&bytes),
+        KeyFormat::ZBase32 => to_z_base_32(&bytes),
+    }
+}
+
+/// Decode a key encoded by `encode_key()`, in either format, with or
+/// without a checksum. The format is told apart by the length, and the
+/// checksum is verified when present.
+pub fn decode_key(s: &str) -> Result<[u8; KEY_LENGTH]> {
+    let hex_len = KEY_LENGTH * 2;
+    let z_base_32_len = |len: usize| (len * 8).div_ceil(5);
+    let len = s.chars().count();
+    let (bytes, checksummed) = if len == hex_len || len == hex_len + CHECKSUM_LENGTH * 2 {
+        (from_hex(s)?, len > hex_len)
+    } else if len == z_base_32_len(KEY_LENGTH) || len == z_base_32_len(KEY_LENGTH + CHECKSUM_LENGTH)
+    {
+        (from_z_base_32(s)?, len > z_base_32_len(KEY_LENGTH))
+    } else {
+        bail!(
+            "Key has {} characters, expected {} or {} hex, or {} or {} z-base-32 characters",
+            len,
+            hex_len,
+            hex_len + CHECKSUM_LENGTH * 2,
+            z_base_32_len(KEY_LENGTH),
+            z_base_32_len(KEY_LENGTH + CHECKSUM_LENGTH)
+        );
+    };
+    let mut key = [0; KEY_LENGTH];
+    key.copy_from_slice(&bytes[..KEY_LENGTH]);
+    if checksummed {
+        ensure!(
+            bytes[KEY_LENGTH..] == key_checksum(&key),
+            "Key checksum doesn't match, the key was probably mistyped"
+        );
+    }
+    Ok(key)
+}
+
+/// Decode a public key, see `decode_key()`.
+pub fn decode_public_key(s: &str) -> Result<PublicKey> {
+    let key = decode_key(s)?;
+    PublicKey::from_bytes(&key).map_err(|_| anyhow!("Key {} is not a valid public key", s))
+}
+
+/// Get the discovery key of a feed, which peers announce to find each other
+/// without revealing the public key.
+pub fn discovery_key(public_key: &PublicKey) -> [u8; KEY_LENGTH] {
+    let mut key = [0; KEY_LENGTH];
+    key.copy_from_slice(Hash::for_discovery_key(*public_key).as_bytes());
+    key
+}
+
+fn key_checksum(key: &[u8; KEY_LENGTH]) -> [u8; CHECKSUM_LENGTH] {
+    let mut hasher = Blake2b::new(32);
+    hasher.update(key);
+    let mut checksum = [0; CHECKSUM_LENGTH];
+    checksum.copy_from_slice(&hasher.finalize().as_bytes()[..CHECKSUM_LENGTH]);
+    checksum
+}
+
+/// Encode bytes as lowercase hex.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decode hex, ignoring case.
+pub(crate) fn from_hex(s: &str) -> Result<Vec<u8>> {
+    if let Some((i, c)) = s.chars().enumerate().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        bail!("Invalid hex character {:?} at offset {}", c, i);
+    }
+    ensure!(s.len().is_multiple_of(2), "Invalid hex, odd length");
+    (0..s.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&s[i..i + 2], 16)?))
+        .collect()
+}
+
+/// Encode bytes as z-base-32, without padding.
+pub(crate) fn to_z_base_32(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let (mut buffer, mut bits) = (0u16, 0);
+    for byte in bytes {
+        buffer = (buffer << 8) | u16::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(Z_BASE_32[usize::from((buffer >> bits) & 31)] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(Z_BASE_32[usize::from((buffer << (5 - bits)) & 31)] as char);
+    }
+    out
+}
+
+/// Decode z-base-32 without padding, ignoring case.
+pub(crate) fn from_z_base_32(s: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u16, 0);
+    for (i, c) in s.chars().enumerate() {
+        let value = Z_BASE_32
+            .iter()
+            .position(|&x| x as char == c.to_ascii_lowercase())
+            .ok_or_else(|| anyhow!("Invalid z-base-32 character {:?} at offset {}", c, i))?;
+        buffer = (buffer << 5) | value as u16;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    ensure!(
+        buffer & ((1 << bits) - 1) == 0,
+        "Invalid z-base-32, trailing bits are not zero"
+    );
+    Ok(out)
+}
+
+#[test]
+fn should_encode_z_base_32() {
+    assert_eq!(to_z_base_32(&[0xf0, 0xbf, 0xc7]), "6n9hq");
+    assert_eq!(from_z_base_32("6n9hq").unwrap(), vec![0xf0, 0xbf, 0xc7]);
+    assert_eq!(from_z_base_32("6N9HQ").unwrap(), vec![0xf0, 0xbf, 0xc7]);
+    assert!(from_z_base_32("6n9hl").is_err());
+    assert_eq!(from_hex("00fF").unwrap(), vec![0, 255]);
+    assert!(from_hex("0g").is_err());
+    assert!(from_hex("+1").is_err());
+}
+
+#[test]
+fn should_encode_keys() {
+    let key: [u8; KEY_LENGTH] = *super::key_pair::generate().public.as_bytes();
+    for &format in &[KeyFormat::Hex, KeyFormat::ZBase32] {
+        for &checksum in &[false, true] {
+            let encoded = encode_key(&key, format, checksum);
+            assert_eq!(decode_key(&encoded).unwrap(), key);
+            assert_eq!(decode_key(&encoded.to_uppercase()).unwrap(), key);
+        }
+    }
+    assert_eq!(encode_key(&key, KeyFormat::Hex, true).len(), 72);
+    assert_eq!(encode_key(&key, KeyFormat::ZBase32, true).len(), 58);
+
+    let mut typo = encode_key(&key, KeyFormat::Hex, true);
+    let c = if typo.starts_with('0') { "1" } else { "0" };
+    typo.replace_range(..1, c);
+    let err = decode_key(&typo).unwrap_err().to_string();
+    assert!(err.contains("checksum"), "{}", err);
+    assert!(decode_key("abc").is_err());
+}
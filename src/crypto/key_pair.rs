@@ -3,15 +3,41 @@
 pub use ed25519_dalek::{ExpandedSecretKey, Keypair, PublicKey, SecretKey, Signature};
 
 use anyhow::{bail, ensure, Result};
+use ed25519_dalek::Verifier;
 use rand::rngs::{OsRng, StdRng};
 use rand::SeedableRng;
+use std::fmt::{self, Display};
+
+/// Error returned when a secret key does not belong to the public key it is
+/// stored with. Can be told apart from other errors with
+/// `err.downcast_ref::<KeyMismatch>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyMismatch;
+
+impl Display for KeyMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret key does not belong to the public key")
+    }
+}
+
+impl std::error::Error for KeyMismatch {}
 
 /// Generate a new `Ed25519` key pair.
 pub fn generate() -> Keypair {
-    let mut rng = StdRng::from_rng(OsRng::default()).unwrap();
+    let mut rng = StdRng::from_rng(OsRng).unwrap();
     Keypair::generate(&mut rng)
 }
 
+/// Derive an `Ed25519` key pair from a seed: the secret key is the 32 byte
+/// BLAKE2b hash of the seed. Only meant for tests and test vectors, as
+/// anyone knowing the seed knows the secret key.
+pub fn from_seed(seed: &[u8]) -> Keypair {
+    let hash = blake2_rfc::blake2b::blake2b(32, &[], seed);
+    let secret = SecretKey::from_bytes(hash.as_bytes()).unwrap();
+    let public = PublicKey::from(&secret);
+    Keypair { secret, public }
+}
+
 /// Sign a byte slice using a keypair's private key.
 pub fn sign(public_key: &PublicKey, secret: &SecretKey, msg: &[u8]) -> Signature {
     ExpandedSecretKey::from(secret).sign(msg, public_key)
@@ -31,6 +57,17 @@ pub fn verify(public: &PublicKey, msg: &[u8], sig: Option<&Signature>) -> Result
     }
 }
 
+/// Check that `secret` belongs to `public`, by signing a probe message and
+/// verifying it.
+pub fn validate_key_pair(public: &PublicKey, secret: &SecretKey) -> Result<()> {
+    let probe = b"hypercore key pair probe";
+    let signature = sign(public, secret, probe);
+    match public.verify(probe, &signature) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(KeyMismatch.into()),
+    }
+}
+
 #[test]
 fn can_verify_messages() {
     let keypair = generate();
@@ -39,3 +76,12 @@ fn can_verify_messages() {
     verify(&keypair.public, from, Some(&sig)).unwrap();
     verify(&keypair.public, b"oops", Some(&sig)).unwrap_err();
 }
+
+#[test]
+fn can_validate_key_pairs() {
+    let keypair = generate();
+    validate_key_pair(&keypair.public, &keypair.secret).unwrap();
+    let other = generate();
+    let err = validate_key_pair(&other.public, &keypair.secret).unwrap_err();
+    assert!(err.downcast_ref::<KeyMismatch>().is_some());
+}
@@ -12,7 +12,7 @@ impl HashMethods for H {
 
     fn leaf(&self, leaf: &PartialNode, _roots: &[Arc<Self::Node>]) -> Self::Hash {
         match leaf.data() {
-            NodeKind::Leaf(data) => Hash::from_leaf(&data),
+            NodeKind::Leaf(data) => Hash::from_leaf(data),
             NodeKind::Parent => unreachable!(),
         }
     }
@@ -45,10 +45,18 @@ impl Merkle {
         }
     }
 
+    /// Create a new instance that continues from existing roots.
+    pub fn from_roots(roots: Vec<Arc<Node>>) -> Self {
+        Self {
+            nodes: vec![],
+            stream: MerkleTreeStream::new(H, roots),
+        }
+    }
+
     /// Access the next item.
     // TODO: remove extra conversion alloc.
     pub fn next(&mut self, data: &[u8]) {
-        self.stream.next(&data, &mut self.nodes);
+        self.stream.next(data, &mut self.nodes);
     }
 
     /// Get the roots vector.
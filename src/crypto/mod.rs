@@ -1,11 +1,25 @@
 //! Cryptographic functions.
 
+#[cfg(feature = "encryption")]
+mod encrypt;
 mod hash;
+mod key_chain;
+mod key_encoding;
 mod key_pair;
 mod merkle;
+mod scheme;
 
+#[cfg(feature = "encryption")]
+pub(crate) use self::encrypt::{decrypt_secret_key, encrypt_secret_key, ENCRYPTED_KEY_LEN};
 pub use self::hash::Hash;
+pub use self::key_chain::KeyChain;
+pub(crate) use self::key_encoding::to_hex;
+pub use self::key_encoding::{
+    decode_key, decode_public_key, discovery_key, encode_key, KeyFormat, KEY_LENGTH,
+};
 pub use self::key_pair::{
-    generate as generate_keypair, sign, verify, PublicKey, SecretKey, Signature,
+    from_seed as keypair_from_seed, generate as generate_keypair, sign, validate_key_pair, verify,
+    KeyMismatch, PublicKey, SecretKey, Signature,
 };
 pub use self::merkle::Merkle;
+pub use self::scheme::{signature_scheme, Ed25519, SignatureScheme, DEFAULT_SIGNATURE_SCHEME};
@@ -0,0 +1,76 @@
+//! Signature schemes that can sign the roots of a feed.
+
+use super::key_pair::{self, PublicKey, SecretKey, Signature};
+use anyhow::{bail, Result};
+use std::fmt::Debug;
+
+/// Name of the default scheme, used when a feed's header doesn't name one.
+pub const DEFAULT_SIGNATURE_SCHEME: &str = "ed25519";
+
+/// A scheme signing and verifying the roots of a feed.
+///
+/// Keys and signatures are passed as bytes, so schemes with other key
+/// types can be added without changing the storage format, which keeps
+/// keys and signatures as opaque bytes too. The scheme of a feed is named
+/// in its header, see `Header::signature_scheme()`.
+pub trait SignatureScheme: Debug + Send + Sync {
+    /// Name of the scheme, as recorded in headers.
+    fn name(&self) -> &'static str;
+
+    /// Length of a signature in bytes.
+    fn signature_len(&self) -> usize;
+
+    /// Sign `message` with a key pair.
+    fn sign(&self, public_key: &[u8], secret_key: &[u8], message: &[u8]) -> Result<Vec<u8>>;
+
+    /// Verify the `signature` of `message` with a public key.
+    fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<()>;
+}
+
+/// Ed25519, the scheme used by every feed so far.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ed25519;
+
+impl SignatureScheme for Ed25519 {
+    fn name(&self) -> &'static str {
+        DEFAULT_SIGNATURE_SCHEME
+    }
+
+    fn signature_len(&self) -> usize {
+        ed25519_dalek::SIGNATURE_LENGTH
+    }
+
+    fn sign(&self, public_key: &[u8], secret_key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+        let public = PublicKey::from_bytes(public_key)?;
+        let secret = SecretKey::from_bytes(secret_key)?;
+        Ok(key_pair::sign(&public, &secret, message)
+            .to_bytes()
+            .to_vec())
+    }
+
+    fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<()> {
+        let public = PublicKey::from_bytes(public_key)?;
+        let signature = Signature::from_bytes(signature)?;
+        key_pair::verify(&public, message, Some(&signature))
+    }
+}
+
+/// Look up a supported scheme by name.
+pub fn signature_scheme(name: &str) -> Result<&'static dyn SignatureScheme> {
+    match name {
+        DEFAULT_SIGNATURE_SCHEME => Ok(&Ed25519),
+        name => bail!("Unsupported signature scheme {:?}", name),
+    }
+}
+
+#[test]
+fn should_sign_with_ed25519() {
+    let keypair = key_pair::generate();
+    let scheme = signature_scheme("ed25519").unwrap();
+    let (public, secret) = (keypair.public.as_bytes(), keypair.secret.as_bytes());
+    let signature = scheme.sign(public, secret, b"roots").unwrap();
+    assert_eq!(signature.len(), scheme.signature_len());
+    assert!(scheme.verify(public, b"roots", &signature).is_ok());
+    assert!(scheme.verify(public, b"other", &signature).is_err());
+    assert!(signature_scheme("dilithium3").is_err());
+}
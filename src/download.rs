@@ -0,0 +1,215 @@
+//! Cloning a feed into a directory from a source of blocks, and merging
+//! replicas.
+
+use crate::feed::Feed;
+use crate::proof::Proof;
+use crate::replicate::Request;
+use crate::storage::Storage;
+use anyhow::{ensure, Result};
+use ed25519_dalek::PublicKey;
+use random_access_disk::RandomAccessDisk;
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
+use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Something that answers block requests, such as a connection to a peer.
+#[async_trait::async_trait]
+pub trait Source: Send {
+    /// Get the number of blocks the source knows of.
+    async fn remote_length(&mut self) -> Result<u64>;
+
+    /// Answer `request` with an encoded `Data` message.
+    async fn request(&mut self, request: &Request) -> Result<Vec<u8>>;
+}
+
+#[async_trait::async_trait]
+impl<T> Source for Feed<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    async fn remote_length(&mut self) -> Result<u64> {
+        Ok(self.len())
+    }
+
+    async fn request(&mut self, request: &Request) -> Result<Vec<u8>> {
+        let proof = self.proof_for(request).await?;
+        let data = self.get(proof.index).await?;
+        Ok(proof.encode(data.as_deref()))
+    }
+}
+
+/// Statistics of a `download_to_path()` call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadStats {
+    /// Number of blocks downloaded.
+    pub blocks: u64,
+    /// Number of block bytes downloaded.
+    pub bytes: u64,
+    /// Time taken by the download.
+    pub elapsed: Duration,
+}
+
+/// Progress of a download, passed to the callback of
+/// `download_to_path_with_progress()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// Number of blocks downloaded so far.
+    pub blocks: u64,
+    /// Number of blocks that were missing when the download started.
+    pub total: u64,
+    /// Number of block bytes downloaded so far.
+    pub bytes: u64,
+    /// Time since the download started.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Get the average download rate in bytes per second.
+    pub fn bytes_per_sec(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => self.bytes as f64 / secs,
+            _ => 0.0,
+        }
+    }
+
+    /// Estimate the time left from the average time per block so far, or
+    /// `None` before the first block.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.blocks == 0 {
+            return None;
+        }
+        let remaining = self.total.saturating_sub(self.blocks);
+        Some(self.elapsed.mul_f64(remaining as f64 / self.blocks as f64))
+    }
+
+    /// Check whether every missing block was downloaded.
+    pub fn is_done(&self) -> bool {
+        self.blocks >= self.total
+    }
+}
+
+impl<T> Feed<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    /// Copy the blocks `other`, a replica of the same feed, has and this
+    /// feed is missing, with the tree nodes and signatures proving them.
+    /// Each block is verified before it is stored, as with `.put()`, so
+    /// partial copies from untrusted drives can be merged. Returns the
+    /// number of blocks copied.
+    pub async fn absorb<U>(&mut self, other: &mut Feed<U>) -> Result<u64>
+    where
+        U: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+    {
+        ensure!(
+            other.public_key() == self.public_key(),
+            "Can only absorb a replica of the same feed"
+        );
+        let mut copied = 0;
+        for index in 0..other.len() {
+            if self.has(index) || !other.has(index) {
+                continue;
+            }
+            let proof = other.proof(index, false).await?;
+            let data = other.get(index).await?;
+            self.put(index, data.as_deref(), proof).await?;
+            copied += 1;
+        }
+        Ok(copied)
+    }
+}
+
+impl Feed<RandomAccessDisk> {
+    /// Create or reopen the feed with `public_key` in the directory at
+    /// `path`, and download the blocks in `range` it is missing from
+    /// `source`, verifying each one. Without a range, every block the source
+    /// knows of is downloaded.
+    pub async fn download_to_path<P, S>(
+        public_key: PublicKey,
+        path: P,
+        source: &mut S,
+        range: Option<Range<u64>>,
+    ) -> Result<(Self, DownloadStats)>
+    where
+        P: AsRef<Path>,
+        S: Source + ?Sized,
+    {
+        Self::download_to_path_with_progress(public_key, path, source, range, Duration::MAX, |_| {})
+            .await
+    }
+
+    /// Like `download_to_path()`, calling `on_progress` at most once every
+    /// `interval` while blocks are downloaded, and once more when the
+    /// download completes.
+    pub async fn download_to_path_with_progress<P, S, F>(
+        public_key: PublicKey,
+        path: P,
+        source: &mut S,
+        range: Option<Range<u64>>,
+        interval: Duration,
+        mut on_progress: F,
+    ) -> Result<(Self, DownloadStats)>
+    where
+        P: AsRef<Path>,
+        S: Source + ?Sized,
+        F: FnMut(&Progress) + Send,
+    {
+        let start = Instant::now();
+        let mut feed = Self::open_replica(public_key, path.as_ref()).await?;
+
+        let length = source.remote_length().await?;
+        let range = range.unwrap_or(0..length);
+        let range = range.start..range.end.min(length);
+        let mut progress = Progress {
+            blocks: 0,
+            total: range.clone().filter(|index| !feed.has(*index)).count() as u64,
+            bytes: 0,
+            elapsed: Duration::default(),
+        };
+        let mut last_report = start;
+        for index in range {
+            if feed.has(index) {
+                continue;
+            }
+            let request = feed.request(index);
+            let (proof, data) = Proof::decode(&source.request(&request).await?)?;
+            ensure!(
+                proof.index == index,
+                format!("Requested block {}, got block {}", index, proof.index)
+            );
+            progress.bytes += data.as_ref().map_or(0, |data| data.len() as u64);
+            feed.put(index, data.as_deref(), proof).await?;
+            progress.blocks += 1;
+            if last_report.elapsed() >= interval && !progress.is_done() {
+                progress.elapsed = start.elapsed();
+                on_progress(&progress);
+                last_report = Instant::now();
+            }
+        }
+        progress.elapsed = start.elapsed();
+        on_progress(&progress);
+        let stats = DownloadStats {
+            blocks: progress.blocks,
+            bytes: progress.bytes,
+            elapsed: progress.elapsed,
+        };
+        Ok((feed, stats))
+    }
+
+    /// Create or reopen the replica of the feed with `public_key` in `dir`.
+    pub(crate) async fn open_replica(public_key: PublicKey, dir: &Path) -> Result<Self> {
+        {
+            let mut storage = Storage::new_disk(dir).await?;
+            match storage.read_public_key().await {
+                Ok(existing) => ensure!(
+                    existing == public_key,
+                    "Directory holds a feed with another key"
+                ),
+                Err(_) => storage.write_public_key(&public_key).await?,
+            }
+        }
+        Self::open(dir).await
+    }
+}
@@ -0,0 +1,122 @@
+//! Minimal protobuf-style encoding helpers.
+//!
+//! Only the subset needed for hypercore's own messages is supported: varints
+//! and length-delimited fields.
+
+use anyhow::{bail, ensure, Result};
+
+/// Wire type for varint encoded fields.
+pub(crate) const VARINT: u64 = 0;
+/// Wire type for length-delimited fields.
+pub(crate) const BYTES: u64 = 2;
+
+/// Append a varint to the buffer.
+pub(crate) fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value > 127 {
+        buf.push((value as u8) | 128);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Append a field key (field number and wire type) to the buffer.
+pub(crate) fn write_key(buf: &mut Vec<u8>, field: u64, wire_type: u64) {
+    write_varint(buf, field << 3 | wire_type);
+}
+
+/// Append a length-delimited field to the buffer.
+pub(crate) fn write_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    write_key(buf, field, BYTES);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Cursor over an encoded buffer.
+#[derive(Debug)]
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Create a new instance.
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Self { buf, offset: 0 }
+    }
+
+    /// Check if the whole buffer has been consumed.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.offset >= self.buf.len()
+    }
+
+    /// Get the number of bytes read so far.
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Read a varint.
+    pub(crate) fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            ensure!(self.offset < self.buf.len(), "unexpected end of varint");
+            ensure!(shift < 64, "varint overflows u64");
+            let byte = self.buf[self.offset];
+            self.offset += 1;
+            value |= u64::from(byte & 127) << shift;
+            if byte & 128 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Read a field key, returning `(field, wire_type)`.
+    pub(crate) fn key(&mut self) -> Result<(u64, u64)> {
+        let key = self.varint()?;
+        Ok((key >> 3, key & 7))
+    }
+
+    /// Read the contents of a length-delimited field.
+    pub(crate) fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.varint()? as usize;
+        ensure!(
+            len <= self.buf.len() - self.offset,
+            "length-delimited field exceeds buffer"
+        );
+        let bytes = &self.buf[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    /// Skip over a field with the given wire type.
+    pub(crate) fn skip(&mut self, wire_type: u64) -> Result<()> {
+        match wire_type {
+            VARINT => self.varint().map(|_| ()),
+            BYTES => self.bytes().map(|_| ()),
+            wire_type => bail!("unsupported wire type {}", wire_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varint_roundtrip() {
+        for value in &[0, 1, 127, 128, 300, u64::MAX] {
+            let mut buf = vec![];
+            write_varint(&mut buf, *value);
+            let mut reader = Reader::new(&buf);
+            assert_eq!(reader.varint().unwrap(), *value);
+            assert!(reader.is_empty());
+        }
+    }
+
+    #[test]
+    fn truncated_input() {
+        Reader::new(&[128]).varint().unwrap_err();
+        Reader::new(&[5, 1, 2]).bytes().unwrap_err();
+    }
+}
@@ -1,16 +1,39 @@
 //! Hypercore's main abstraction. Exposes an append-only, secure log structure.
 
 use crate::feed_builder::FeedBuilder;
-use crate::replicate::{Message, Peer};
-pub use crate::storage::{Node, NodeTrait, Storage, Store};
-
+use crate::replicate::{Message, Peer, Request};
+pub use crate::storage::{Node, NodeTrait, Storage};
+// Unused in this module, kept for code naming it through `feed`.
+#[doc(hidden)]
+#[allow(unused_imports)]
+pub use crate::storage::Store;
+
+use crate::storage::{crc32c, ChangeCounter, DirLock, QuarantinedBlock};
+
+use crate::access_log::AccessLog;
+use crate::append::AppendOutcome;
+use crate::archive::{BlockReport, BlockStatus};
 use crate::audit::Audit;
 use crate::bitfield::Bitfield;
+use crate::block::Block;
+use crate::compat;
 use crate::crypto::{
-    generate_keypair, sign, verify, Hash, Merkle, PublicKey, SecretKey, Signature,
+    generate_keypair, keypair_from_seed, sign, signature_scheme, validate_key_pair, verify, Hash,
+    Merkle, PublicKey, SecretKey, Signature, SignatureScheme, DEFAULT_SIGNATURE_SCHEME,
 };
-use crate::proof::Proof;
-use anyhow::{bail, ensure, Result};
+use crate::header::Header;
+use crate::pack::{self, Segment};
+use crate::prefetch::Prefetcher;
+use crate::proof::{Proof, ProofSize};
+use crate::proof_cache::{ProofCache, ProofCacheStats};
+use crate::ranges::Ranges;
+use crate::retention::{self, Retention};
+use crate::telemetry::{self, AppendTimings, Counters, FeedStats, Stopwatch};
+use crate::tentative::{PendingSignature, Tentative};
+use crate::uri::FeedUri;
+use crate::watch::{self, Watch};
+use crate::witness::{self, Witness};
+use anyhow::{anyhow, bail, ensure, Result};
 use flat_tree as flat;
 use pretty_hash::fmt as pretty_fmt;
 use random_access_disk::RandomAccessDisk;
@@ -18,12 +41,13 @@ use random_access_memory::RandomAccessMemory;
 use random_access_storage::RandomAccess;
 use tree_index::TreeIndex;
 
-use std::borrow::Borrow;
 use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{self, Debug, Display};
 use std::ops::Range;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Feed is an append-only log structure.
 ///
@@ -46,6 +70,15 @@ use std::sync::Arc;
 /// __If you want to reopen a `Feed` you have previously opened__
 /// Use [with_storage], giving it a [Storage] that contains the previously opened `Feed`
 ///
+/// __Empty feeds__
+/// A feed of length 0 has no tree: it has no roots, and no signature, as
+/// only roots are ever signed. There is nothing to verify or prove, so
+/// `.root_hashes()`, `.signature()`, `.verify()`, `.proof()` and
+/// `.checkpoint()` fail, and a replica has no way to tell an empty feed from
+/// one whose blocks it hasn't heard of yet. Reads of any block return `None`,
+/// `.seek()` and `.byte_offset()` fail as for any offset past the end, and
+/// `.audit()` and `.verify_range(0..0)` find nothing to check.
+///
 /// these references can be changed to the +nightly version, as docs.rs uses +nightly
 ///
 /// [SecretKey]: ed25519_dalek::SecretKey
@@ -72,6 +105,42 @@ where
     pub(crate) bitfield: Bitfield,
     pub(crate) tree: TreeIndex,
     pub(crate) peers: Vec<Peer>,
+    /// Whether `CRC32C` checksums are stored and checked for each block.
+    pub(crate) checksums: bool,
+    /// Whether corrupted blocks are moved to the quarantine store.
+    pub(crate) quarantine: bool,
+    /// Whether every block read is checked against its tree node.
+    pub(crate) verify_reads: bool,
+    /// Whether signatures of received roots are verified later.
+    pub(crate) lazy_signatures: bool,
+    /// Blocks stored before their signature was verified, if any.
+    pub(crate) tentative: Option<Tentative>,
+    /// Single-writer lock on the feed directory, if opened from disk.
+    pub(crate) lock: Option<DirLock>,
+    /// Change counter shared with other processes, if opened from disk.
+    pub(crate) changes: Option<ChangeCounter>,
+    /// Stage timings of appends, if being recorded.
+    pub(crate) append_timings: Option<AppendTimings>,
+    /// Policies deciding which blocks `.gc()` clears.
+    pub(crate) retention: Vec<Retention>,
+    /// Whether blocks record when they were stored.
+    pub(crate) timestamps: bool,
+    /// Blocks that are never cleared.
+    pub(crate) pins: Ranges,
+    /// Recently generated proofs, if caching them is enabled.
+    pub(crate) proof_cache: Option<ProofCache>,
+    /// Channel publishing the length, once someone watches it.
+    pub(crate) watch: Option<watch::Sender>,
+    /// Activity counters, sampled by `.stats()`.
+    pub(crate) counters: Counters,
+    /// Block indexes by leaf hash, if indexing them is enabled.
+    pub(crate) hash_index: Option<HashMap<Vec<u8>, u64>>,
+    /// Free space to keep on the feed's filesystem, if checked.
+    pub(crate) min_free_space: Option<u64>,
+    /// Blocks read ahead of `.get()`, if prefetching is enabled.
+    pub(crate) prefetch: Option<Prefetcher>,
+    /// Reads of recently read blocks, if recorded.
+    pub(crate) access_log: Option<AccessLog>,
 }
 
 impl<T> Feed<T>
@@ -79,17 +148,23 @@ where
     T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
 {
     /// Create a new instance with a custom storage backend.
+    ///
+    /// If the storage already holds a feed, its blocks are loaded after
+    /// verifying the latest signature. A stored secret key that does not
+    /// belong to the stored public key is a [KeyMismatch] error.
+    ///
+    /// [KeyMismatch]: crate::crypto::KeyMismatch
     pub async fn with_storage(mut storage: crate::storage::Storage<T>) -> Result<Self> {
         match storage.read_partial_keypair().await {
             Some(partial_keypair) => {
-                let builder = FeedBuilder::new(partial_keypair.public, storage);
-
-                // return early without secret key
-                if partial_keypair.secret.is_none() {
-                    return Ok(builder.build()?);
+                let mut builder = FeedBuilder::new(partial_keypair.public, storage);
+                if let Some(secret) = partial_keypair.secret {
+                    builder = builder.secret_key(secret);
                 }
-
-                builder.secret_key(partial_keypair.secret.unwrap()).build()
+                let mut feed = builder.build()?;
+                feed.validate_key_pair()?;
+                feed.load_state().await?;
+                Ok(feed)
             }
             None => {
                 // we have no keys, generate a pair and save them to the storage
@@ -104,6 +179,55 @@ where
         }
     }
 
+    /// Check that the secret key, if the feed has one, belongs to its public
+    /// key, failing with a [KeyMismatch] error otherwise. Keys copied
+    /// between directories would otherwise sign blocks nobody can verify.
+    ///
+    /// [KeyMismatch]: crate::crypto::KeyMismatch
+    pub fn validate_key_pair(&self) -> Result<()> {
+        match &self.secret_key {
+            Some(secret_key) => validate_key_pair(&self.public_key, secret_key),
+            None => Ok(()),
+        }
+    }
+
+    /// Encrypt the stored secret key with a key derived from `passphrase`.
+    /// Once reopened, the feed can only be appended to after `.unlock()`.
+    /// The feed stays unlocked until then. The `secret_key` file of a
+    /// directory written by the JavaScript implementation is overwritten
+    /// and removed, so the key isn't left in the clear.
+    #[cfg(feature = "encryption")]
+    pub async fn encrypt_secret_key(&mut self, passphrase: &[u8]) -> Result<()> {
+        let secret_key = match &self.secret_key {
+            Some(secret_key) => secret_key,
+            None => bail!("no secret key, cannot encrypt it."),
+        };
+        self.storage
+            .write_encrypted_secret_key(&self.public_key, secret_key, passphrase)
+            .await?;
+        if let Some(changes) = &self.changes {
+            compat::remove_secret_key(changes.dir())?;
+        }
+        Ok(())
+    }
+
+    /// Decrypt the stored secret key with `passphrase`, so the feed can be
+    /// appended to.
+    #[cfg(feature = "encryption")]
+    pub async fn unlock(&mut self, passphrase: &[u8]) -> Result<()> {
+        ensure!(
+            self.lock.is_some() || self.changes.is_none(),
+            "Feeds opened read-only can't be unlocked"
+        );
+        let secret_key = self
+            .storage
+            .read_encrypted_secret_key(&self.public_key, passphrase)
+            .await?;
+        validate_key_pair(&self.public_key, &secret_key)?;
+        self.secret_key = Some(secret_key);
+        Ok(())
+    }
+
     /// Starts a `FeedBuilder` with the provided `PublicKey` and `Storage`.
     pub fn builder(public_key: PublicKey, storage: Storage<T>) -> FeedBuilder<T> {
         FeedBuilder::new(public_key, storage)
@@ -121,6 +245,118 @@ where
         self.len() == 0
     }
 
+    /// Watch the length of the feed. The watcher yields the latest
+    /// `(length, fork)` whenever it changes, through appends, replication
+    /// or `.refresh()`. The fork is always 0, as feeds can't be truncated.
+    pub fn watch(&mut self) -> Watch {
+        let length = self.length;
+        self.watch
+            .get_or_insert_with(|| watch::Sender::new((length, 0)))
+            .subscribe()
+    }
+
+    /// Take a snapshot of the state and activity of the feed, for
+    /// dashboards. Rates are averaged since the previous snapshot.
+    pub async fn stats(&mut self) -> Result<FeedStats> {
+        let stored_bytes = self.storage.stored_bytes().await?;
+        let (append_rate, read_rate, interval) = self.counters.sample();
+        Ok(FeedStats {
+            length: self.length,
+            byte_length: self.byte_length,
+            stored_bytes,
+            appended_blocks: self.counters.appended_blocks,
+            read_blocks: self.counters.read_blocks,
+            read_bytes: self.counters.read_bytes,
+            verification_failures: self.counters.verification_failures,
+            append_rate,
+            read_rate,
+            interval,
+            proof_cache: self.proof_cache_stats(),
+            peers: self.peers.len(),
+        })
+    }
+
+    /// Mark a stored block that failed to verify as missing, moving its
+    /// bytes to the quarantine store first if enabled.
+    pub(crate) async fn discard_corrupt(
+        &mut self,
+        index: u64,
+        expected_hash: Vec<u8>,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        self.verification_failure();
+        if self.quarantine {
+            let block = QuarantinedBlock {
+                index,
+                expected_hash,
+                data,
+            };
+            self.storage.quarantine(&block).await?;
+        }
+        self.bitfield.set(index, false);
+        self.persist_bitfield(index).await
+    }
+
+    /// Get the blocks moved to the quarantine store, see
+    /// `FeedBuilder::quarantine()`.
+    pub async fn quarantined(&mut self) -> Result<Vec<QuarantinedBlock>> {
+        self.storage.read_quarantine().await
+    }
+
+    /// Get the application flags of the block at `index`. Each block has a
+    /// byte of flags, such as "processed" or "tombstoned", for higher
+    /// layers to use as they see fit. They are kept locally and are not
+    /// replicated.
+    pub async fn flags(&mut self, index: u64) -> Result<u8> {
+        Ok(self.storage.get_flags(index..index + 1).await?[0])
+    }
+
+    /// Set the bits of `flags` on the blocks in `ranges`, keeping their
+    /// other flags. Each block's byte is written in one write.
+    pub async fn set_flags(&mut self, ranges: impl Into<Ranges>, flags: u8) -> Result<()> {
+        self.update_flags(ranges.into(), |byte| byte | flags).await
+    }
+
+    /// Clear the bits of `flags` on the blocks in `ranges`, keeping their
+    /// other flags.
+    pub async fn clear_flags(&mut self, ranges: impl Into<Ranges>, flags: u8) -> Result<()> {
+        self.update_flags(ranges.into(), |byte| byte & !flags).await
+    }
+
+    /// Find the blocks in `range` that have every bit of `flags` set.
+    pub async fn find_flags(&mut self, range: Range<u64>, flags: u8) -> Result<Ranges> {
+        let bytes = self.storage.get_flags(range.clone()).await?;
+        Ok(range
+            .zip(bytes)
+            .filter(|(_, byte)| byte & flags == flags)
+            .map(|(index, _)| index..index + 1)
+            .collect())
+    }
+
+    async fn update_flags(&mut self, ranges: Ranges, update: impl Fn(u8) -> u8) -> Result<()> {
+        for range in ranges.ranges() {
+            let mut bytes = self.storage.get_flags(range.clone()).await?;
+            for byte in &mut bytes {
+                *byte = update(*byte);
+            }
+            self.storage.put_flags(range.start, &bytes).await?;
+        }
+        Ok(())
+    }
+
+    /// Count a signature, checksum or hash that failed to verify.
+    pub(crate) fn verification_failure(&mut self) {
+        self.counters.verification_failures += 1;
+        telemetry::verification_failure();
+    }
+
+    /// Publish the length to the watchers, if any.
+    fn notify_length(&self) {
+        if let Some(watch) = &self.watch {
+            watch.send((self.length, 0));
+        }
+    }
+
     /// Get the total amount of bytes stored in the feed.
     #[inline]
     pub fn byte_len(&self) -> u64 {
@@ -132,38 +368,200 @@ where
     ///
     /// It inserts the inputed data, it's signature, and a new [Merkle] node into [Storage].
     ///
+    /// Appends take `&mut self`, so they never interleave: tasks sharing a
+    /// feed have to hold a lock across the whole call. A failed append
+    /// leaves the feed as it was, so it can be retried. To apply appends from
+    /// concurrent tasks in the order they are made, queue them through a
+    /// [GroupCommit].
+    ///
+    /// [GroupCommit]: crate::group_commit::GroupCommit
     /// [SecretKey]: ed25519_dalek::SecretKey
     /// [Merkle]: crate::crypto::Merkle
     /// [Storage]: crate::storage::Storage
     #[inline]
-    pub async fn append(&mut self, data: &[u8]) -> Result<()> {
+    pub async fn append(&mut self, data: &[u8]) -> Result<AppendOutcome> {
+        self.append_batch(&[data]).await
+    }
+
+    /// Append a block whose data may be cleared once `expiry` has passed,
+    /// by `.expire_now()` or `.gc()`. The expiry is kept locally, next to
+    /// the block, and is not replicated.
+    pub async fn append_with_expiry(
+        &mut self,
+        data: &[u8],
+        expiry: SystemTime,
+    ) -> Result<AppendOutcome> {
+        let expiry = expiry
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0)
+            .max(1);
+        self.append_blocks(&[data], Some(expiry)).await
+    }
+
+    /// Append several blocks, signing only the roots after the last one.
+    ///
+    /// Earlier blocks in the batch are covered by that signature, so this
+    /// trades the ability to verify the feed at every intermediate length
+    /// for far fewer signatures under high write rates. Proofs of blocks
+    /// within the batch are signed for a length at or past its last block,
+    /// and `.verify()` only accepts signatures for signed lengths.
+    ///
+    /// Appending no blocks is an error, as there would be nothing to sign.
+    pub async fn append_batch<B: AsRef<[u8]>>(&mut self, blocks: &[B]) -> Result<AppendOutcome> {
+        self.append_blocks(blocks, None).await
+    }
+
+    /// Append blocks, each with `expiry` if set, in one batch.
+    async fn append_blocks<B: AsRef<[u8]>>(
+        &mut self,
+        blocks: &[B],
+        expiry: Option<u64>,
+    ) -> Result<AppendOutcome> {
+        let roots = self.merkle.roots().clone();
+        let result = self.write_batch(blocks, expiry).await;
+        if result.is_err() {
+            // Drop the hashes of the blocks that were not committed, so the
+            // next append continues from the last signed roots.
+            self.merkle = Merkle::from_roots(roots);
+        }
+        result
+    }
+
+    /// Append small records packed into segments of up to `segment_size`
+    /// bytes, each a single block, see [Segment]. The segments are appended
+    /// as one batch. Records are read back with `.get_packed()`, by the index
+    /// of their segment and their position in it; `AppendOutcome::index` is
+    /// the index of the first segment. With `Storage::set_alignment()` set to
+    /// the segment size, every segment starts on its own aligned chunk of the
+    /// data store.
+    pub async fn append_packed<B: AsRef<[u8]>>(
+        &mut self,
+        records: &[B],
+        segment_size: usize,
+    ) -> Result<AppendOutcome> {
+        self.append_batch(&pack::pack(records, segment_size)).await
+    }
+
+    /// Retrieve record `record` of the segment stored as block `index`, see
+    /// `.append_packed()`. Returns `None` if the block is not stored locally
+    /// or holds fewer records.
+    pub async fn get_packed(&mut self, index: u64, record: usize) -> Result<Option<Vec<u8>>> {
+        let segment = match self.get(index).await? {
+            Some(segment) => segment,
+            None => return Ok(None),
+        };
+        Ok(Segment::decode(&segment)?.get(record).map(<[u8]>::to_vec))
+    }
+
+    /// Write and sign a batch of blocks.
+    async fn write_batch<B: AsRef<[u8]>>(
+        &mut self,
+        blocks: &[B],
+        expiry: Option<u64>,
+    ) -> Result<AppendOutcome> {
         let key = match &self.secret_key {
             Some(key) => key,
             None => bail!("no secret key, cannot append."),
         };
-        self.merkle.next(data);
-
-        self.storage
-            .write_data(self.byte_length as u64, &data)
-            .await?;
+        ensure!(!blocks.is_empty(), "No blocks to append");
+        let bytes = blocks.iter().map(|data| data.as_ref().len() as u64).sum();
+        self.ensure_space(bytes)?;
+        let mut stopwatch = Stopwatch::start();
+        let mut timings = AppendTimings {
+            appends: blocks.len() as u64,
+            ..AppendTimings::default()
+        };
 
+        let start = self.length;
+        let last = start + blocks.len() as u64 - 1;
+        let mut byte_length = self.byte_length;
+        let first_node = self.merkle.nodes().len();
+        for data in blocks {
+            self.merkle.next(data.as_ref());
+        }
         let hash = Hash::from_roots(self.merkle.roots());
-        let index = self.length;
-        let message = hash_with_length_as_bytes(hash, index + 1);
+        let message = hash_with_length_as_bytes(hash, last + 1);
+        timings.hashing = stopwatch.lap();
         let signature = sign(&self.public_key, key, &message);
-        self.storage.put_signature(index, signature).await?;
-
+        timings.signing = stopwatch.lap();
+
+        let records_timestamps = self.records_timestamps();
+        let mut batch = self.storage.begin_batch();
+        for (index, data) in (start..).zip(blocks) {
+            let data = data.as_ref();
+            batch.append_data(index, byte_length, data);
+            byte_length += data.len() as u64;
+            if self.checksums {
+                batch.put_checksum(index, crc32c(data));
+            }
+            if let Some(expiry) = expiry {
+                batch.put_expiry(index, expiry);
+            }
+        }
+        if records_timestamps {
+            batch.put_timestamps(start, blocks.len() as u64, retention::unix_time());
+        }
         for node in self.merkle.nodes() {
-            self.storage.put_node(node).await?;
+            batch.put_node(node);
+        }
+        batch.put_signature(last, signature);
+        for index in start..=last {
+            self.bitfield.set(index, true);
+            // Bitfield bytes hold 8 blocks, so write each one once.
+            if index % 8 == 7 || index == last {
+                batch.put_data_bitfield(index / 8, self.bitfield.data_byte(index));
+            }
+        }
+        if let Err(err) = batch.commit_timed(&mut timings).await {
+            for index in start..=last {
+                self.bitfield.set(index, false);
+            }
+            return Err(err);
+        }
+
+        self.byte_length = byte_length;
+        for index in start..=last {
+            self.tree.set(tree_index(index));
+        }
+        if let Some(hash_index) = &mut self.hash_index {
+            for node in &self.merkle.nodes()[first_node..] {
+                index_leaf(hash_index, node);
+            }
+        }
+        self.length = last + 1;
+        self.notify_length();
+        if let Some(cache) = &mut self.proof_cache {
+            cache.invalidate();
         }
 
-        self.byte_length += data.len() as u64;
+        if let Some(total) = &mut self.append_timings {
+            *total += timings;
+        }
+        self.counters.appended_blocks += blocks.len() as u64;
 
-        self.bitfield.set(index, true);
-        self.tree.set(tree_index(index));
-        self.length += 1;
+        if let Some(changes) = &mut self.changes {
+            changes.bump().await?;
+        }
 
-        Ok(())
+        Ok(AppendOutcome {
+            index: start,
+            length: self.length,
+            byte_length: self.byte_length,
+            signature,
+        })
+    }
+
+    /// Start recording how long each stage of `.append()` takes, discarding
+    /// any timings recorded so far.
+    pub fn record_append_timings(&mut self) {
+        self.append_timings = Some(AppendTimings::default());
+    }
+
+    /// Get the stage timings recorded since `.record_append_timings()` was
+    /// called, if it was.
+    pub fn append_timings(&self) -> Option<&AppendTimings> {
+        self.append_timings.as_ref()
     }
 
     /// Get the block of data at the tip of the feed. This will be the most
@@ -176,6 +574,58 @@ where
         }
     }
 
+    /// Write a [Header] as the first block of the feed. Can only be called on
+    /// an empty feed. Fails if the header names a signature scheme other
+    /// than ed25519, the only one feeds can be signed with so far.
+    ///
+    /// [Header]: crate::header::Header
+    pub async fn set_header(&mut self, header: &Header) -> Result<()> {
+        ensure!(self.is_empty(), "header can only be set on an empty feed");
+        let scheme = signature_scheme(header.signature_scheme())?;
+        ensure!(
+            scheme.name() == DEFAULT_SIGNATURE_SCHEME,
+            "Feeds can only be signed with ed25519 for now"
+        );
+        self.append(&header.encode_tagged()).await?;
+        Ok(())
+    }
+
+    /// Read the [Header] from the first block of the feed. Returns `None` if
+    /// the block is not available locally. Headers written by other
+    /// implementations are read too, though they lack the magic field.
+    ///
+    /// [Header]: crate::header::Header
+    pub async fn get_header(&mut self) -> Result<Option<Header>> {
+        match self.get(0).await? {
+            Some(data) => Ok(Some(Header::decode(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the scheme signing the feed, as named by a header written by
+    /// `.set_header()`. Feeds without one, or whose header doesn't name a
+    /// scheme, use ed25519.
+    /// Fails if the header names a scheme this version doesn't support.
+    pub async fn signature_scheme(&mut self) -> Result<&'static dyn SignatureScheme> {
+        match self.get(0).await? {
+            Some(data) => match Header::decode_tagged(&data) {
+                Ok(header) => signature_scheme(header.signature_scheme()),
+                Err(_) => signature_scheme(DEFAULT_SIGNATURE_SCHEME),
+            },
+            None => signature_scheme(DEFAULT_SIGNATURE_SCHEME),
+        }
+    }
+
+    /// Get the index of the first content block, skipping the header block if
+    /// the feed starts with one written by `.set_header()`. Headers without
+    /// the magic field can't be told apart from content, so aren't skipped.
+    pub async fn content_start(&mut self) -> Result<u64> {
+        match self.get(0).await? {
+            Some(data) if Header::decode_tagged(&data).is_ok() => Ok(1),
+            _ => Ok(0),
+        }
+    }
+
     /// Return `true` if a data block is available locally.
     #[inline]
     pub fn has(&mut self, index: u64) -> bool {
@@ -196,13 +646,318 @@ where
     }
 
     /// Retrieve data from the log.
+    ///
+    /// If checksums are enabled and the stored data does not match its
+    /// checksum, the block is marked as missing and an error is returned.
     #[inline]
     pub async fn get(&mut self, index: u64) -> Result<Option<Vec<u8>>> {
         if !self.bitfield.get(index) {
             // NOTE: Do (network) lookup here once we have network code.
             return Ok(None);
         }
-        Ok(Some(self.storage.get_data(index).await?))
+        let data = self.read_block(index).await?;
+        if self.checksums {
+            if let Some(checksum) = self.storage.get_checksum(index).await? {
+                if checksum != crc32c(&data) {
+                    // NOTE: Trigger a re-download here once we have network code.
+                    let node = self.storage.get_node(tree_index(index)).await?;
+                    self.discard_corrupt(index, node.hash, data).await?;
+                    bail!("Checksum mismatch for block {}", index);
+                }
+            }
+        }
+        if self.verify_reads {
+            let node = self.storage.get_node(tree_index(index)).await?;
+            if node.hash != Hash::from_leaf(&data).as_bytes() {
+                self.discard_corrupt(index, node.hash, data).await?;
+                bail!("Block {} does not match its tree node", index);
+            }
+        }
+        self.counters.read_blocks += 1;
+        self.counters.read_bytes += data.len() as u64;
+        if let Some(log) = &mut self.access_log {
+            log.record(index);
+        }
+        Ok(Some(data))
+    }
+
+    /// Get `len` bytes of the block at `index`, starting at `offset` within
+    /// the block, or `None` if the block is not stored locally. Only the
+    /// requested bytes are read, unless checksums or `verify_reads` are
+    /// enabled: then the whole block is read and checked as with `.get()`.
+    pub async fn get_slice(
+        &mut self,
+        index: u64,
+        offset: u64,
+        len: u64,
+    ) -> Result<Option<Vec<u8>>> {
+        if self.checksums || self.verify_reads {
+            let data = match self.get(index).await? {
+                Some(data) => data,
+                None => return Ok(None),
+            };
+            let end = offset.saturating_add(len);
+            ensure!(
+                end <= data.len() as u64,
+                "Bytes {}..{} are past the end of block {} of {} bytes",
+                offset,
+                end,
+                index,
+                data.len()
+            );
+            return Ok(Some(data[offset as usize..end as usize].to_vec()));
+        }
+        if !self.bitfield.get(index) {
+            return Ok(None);
+        }
+        let data = self.storage.get_data_slice(index, offset, len).await?;
+        self.counters.read_blocks += 1;
+        self.counters.read_bytes += len;
+        if let Some(log) = &mut self.access_log {
+            log.record(index);
+        }
+        Ok(Some(data))
+    }
+
+    /// Clear the blocks in `ranges` from local storage, zeroing their data.
+    /// The tree is kept, so the blocks can still be proven and put back.
+    /// Pinned blocks are skipped.
+    pub async fn clear(&mut self, ranges: impl Into<Ranges>) -> Result<()> {
+        let ranges = ranges.into().difference(&self.pins);
+        for index in ranges.intersect(&Ranges::from(0..self.length)).iter() {
+            if self.bitfield.get(index) {
+                self.clear_block(index).await?;
+            }
+        }
+        if let Some(changes) = &mut self.changes {
+            changes.bump().await?;
+        }
+        Ok(())
+    }
+
+    /// Pin the blocks in `ranges`, so neither `.clear()` nor `.gc()` clears
+    /// them. Pins are persisted, and blocks can be pinned before they are
+    /// downloaded.
+    pub async fn pin(&mut self, ranges: impl Into<Ranges>) -> Result<()> {
+        self.pins = self.pins.union(&ranges.into());
+        self.storage.write_pins(&self.pins).await?;
+        if let Some(changes) = &mut self.changes {
+            changes.bump().await?;
+        }
+        Ok(())
+    }
+
+    /// Unpin the blocks in `ranges`, splitting pinned ranges that extend
+    /// past them.
+    pub async fn unpin(&mut self, ranges: impl Into<Ranges>) -> Result<()> {
+        self.pins = self.pins.difference(&ranges.into());
+        self.storage.write_pins(&self.pins).await?;
+        if let Some(changes) = &mut self.changes {
+            changes.bump().await?;
+        }
+        Ok(())
+    }
+
+    /// Get the pinned blocks.
+    pub fn pins(&self) -> &Ranges {
+        &self.pins
+    }
+
+    /// Get the retention policies applied by `.gc()`.
+    pub fn retention(&self) -> &[Retention] {
+        &self.retention
+    }
+
+    /// Replace the retention policies applied by `.gc()`. Policies are not
+    /// persisted, but once an age policy is set blocks keep recording when
+    /// they were stored, also after the feed is reopened without one.
+    pub fn set_retention(&mut self, policies: Vec<Retention>) {
+        self.timestamps |= retention::has_age(&policies);
+        self.retention = policies;
+    }
+
+    /// Check whether blocks record when they were stored, which only age
+    /// policies need.
+    fn records_timestamps(&self) -> bool {
+        self.timestamps
+    }
+
+    /// Clear the data of the blocks the retention policies don't keep, and
+    /// of expired blocks, except for pinned blocks. Returns the number of
+    /// blocks cleared. Without any policies, only expired blocks are
+    /// cleared.
+    pub async fn gc(&mut self) -> Result<u64> {
+        if self.retention.is_empty() {
+            return self.expire_now().await;
+        }
+        let now = retention::unix_time();
+        let (mut blocks, mut bytes, mut cleared) = (0, 0, 0);
+        for index in (0..self.length).rev() {
+            if !self.bitfield.get(index) {
+                continue;
+            }
+            let len = self.storage.get_node(tree_index(index)).await?.length;
+            let timestamp = self.storage.get_timestamp(index).await?;
+            let expiry = self.storage.get_expiry(index).await?;
+            let keep = !retention::expired(expiry, now)
+                && self
+                    .retention
+                    .iter()
+                    .all(|policy| policy.keeps(blocks, bytes, len, timestamp, now));
+            if !keep && !self.pins.contains(index) {
+                self.clear_block(index).await?;
+                cleared += 1;
+            }
+            blocks += 1;
+            bytes += len;
+        }
+        if cleared > 0 {
+            if let Some(changes) = &mut self.changes {
+                changes.bump().await?;
+            }
+        }
+        Ok(cleared)
+    }
+
+    /// Clear the data of the blocks whose expiry has passed, except for
+    /// pinned blocks. Returns the number of blocks cleared.
+    pub async fn expire_now(&mut self) -> Result<u64> {
+        let now = retention::unix_time();
+        let mut cleared = 0;
+        for index in 0..self.length {
+            if !self.bitfield.get(index) || self.pins.contains(index) {
+                continue;
+            }
+            if retention::expired(self.storage.get_expiry(index).await?, now) {
+                self.clear_block(index).await?;
+                cleared += 1;
+            }
+        }
+        if cleared > 0 {
+            if let Some(changes) = &mut self.changes {
+                changes.bump().await?;
+            }
+        }
+        Ok(cleared)
+    }
+
+    /// Zero the data of a stored block and mark it as missing.
+    async fn clear_block(&mut self, index: u64) -> Result<()> {
+        if let Some(prefetch) = &mut self.prefetch {
+            prefetch.forget(index);
+        }
+        self.storage.del_data(index).await?;
+        self.bitfield.set(index, false);
+        self.persist_bitfield(index).await
+    }
+
+    /// Reclaim the space of cleared blocks by compacting the data store.
+    /// Returns the number of bytes reclaimed. Requires an aligned data store,
+    /// see `Storage::set_alignment()`. Pinned blocks are never cleared, so
+    /// they are always kept.
+    pub async fn compact(&mut self) -> Result<u64> {
+        let bitfield = &mut self.bitfield;
+        let keep: Vec<u64> = (0..self.length).filter(|i| bitfield.get(*i)).collect();
+        let reclaimed = self.storage.compact_data(&keep).await?;
+        if let Some(changes) = &mut self.changes {
+            changes.bump().await?;
+        }
+        Ok(reclaimed)
+    }
+
+    /// Find the block holding the byte at offset `bytes`, returning the block
+    /// index and the offset of the byte within that block.
+    pub async fn seek(&mut self, bytes: u64) -> Result<(u64, u64)> {
+        ensure!(
+            bytes < self.byte_length,
+            format!("Offset {} is past the end of the feed", bytes)
+        );
+
+        let mut roots = vec![];
+        flat::full_roots(tree_index(self.length), &mut roots);
+        let mut offset = bytes;
+        for root in roots {
+            let node = self.storage.get_node(root).await?;
+            if offset >= node.length {
+                offset -= node.length;
+                continue;
+            }
+
+            let mut index = root;
+            while let Some((left, right)) = flat::children(index) {
+                let left_node = self.storage.get_node(left).await?;
+                if offset < left_node.length {
+                    index = left;
+                } else {
+                    offset -= left_node.length;
+                    index = right;
+                }
+            }
+            return Ok((index / 2, offset));
+        }
+        bail!("Offset {} is past the end of the tree", bytes)
+    }
+
+    /// Count the bytes in a range of blocks. Sums the lengths of the tree
+    /// roots on either side of the range, reading O(log n) nodes.
+    pub async fn byte_range_len(&mut self, range: Range<u64>) -> Result<u64> {
+        ensure!(
+            range.start <= range.end && range.end <= self.length,
+            format!("Range {:?} is past the end of the feed", range)
+        );
+        let start = self.bytes_before(range.start).await?;
+        let end = self.bytes_before(range.end).await?;
+        Ok(end - start)
+    }
+
+    /// Get the byte offset of the block at `index` within the feed, and its
+    /// length in bytes. This is the position of the block in the
+    /// concatenated blocks, independent of how the data store lays them out.
+    pub async fn byte_offset(&mut self, index: u64) -> Result<(u64, u64)> {
+        ensure!(
+            index < self.length,
+            format!("Block {} is past the end of the feed", index)
+        );
+        let offset = self.bytes_before(index).await?;
+        let len = self.storage.get_node(tree_index(index)).await?.length;
+        Ok((offset, len))
+    }
+
+    /// Get the metadata of the block at `index`, without reading its data.
+    /// Fails if the node of the block isn't stored locally, which happens
+    /// on sparse replicas.
+    pub async fn block(&mut self, index: u64) -> Result<Block> {
+        ensure!(
+            index < self.length,
+            format!("Block {} is past the end of the feed", index)
+        );
+        ensure!(
+            self.tree.get(tree_index(index)),
+            format!("Block {} is not in the local tree", index)
+        );
+        let byte_offset = self.bytes_before(index).await?;
+        let node = self.storage.get_node(tree_index(index)).await?;
+        Ok(Block {
+            index,
+            byte_offset,
+            len: node.length,
+            hash: node.hash,
+            is_downloaded: self.bitfield.get(index),
+        })
+    }
+
+    /// Get the number of bytes before the block at `index`.
+    async fn bytes_before(&mut self, index: u64) -> Result<u64> {
+        if index == self.length {
+            return Ok(self.byte_length);
+        }
+        let mut roots = vec![];
+        flat::full_roots(tree_index(index), &mut roots);
+        let mut offset = 0;
+        for root in roots {
+            offset += self.storage.get_node(root).await?.length;
+        }
+        Ok(offset)
     }
 
     /// Return the Nodes which prove the correctness for the Node at index.
@@ -219,55 +974,253 @@ where
         digest: u64,
         include_hash: bool,
     ) -> Result<Proof> {
+        if let Some(cache) = &mut self.proof_cache {
+            if let Some(proof) = cache.get(index, digest, include_hash) {
+                return Ok(proof);
+            }
+        }
+
+        let (mut indexes, mut sig_index) = self.tree_proof(index, digest, include_hash)?;
+        let mut signature = match sig_index {
+            Some(sig_index) => self.storage.get_signature(sig_index).await.ok(),
+            None => None,
+        };
+        // Lengths within a batch are not signed, so prove the block against
+        // the whole tree instead, which ends at a signed length.
+        if signature.is_none() && sig_index.is_some() && digest != 0 {
+            (indexes, sig_index) = self.tree_proof(index, 0, include_hash)?;
+            if let Some(sig_index) = sig_index {
+                signature = self.storage.get_signature(sig_index).await.ok();
+            }
+        }
+
+        let nodes = self.storage.get_nodes(&indexes).await?;
+
+        let proof = Proof {
+            nodes,
+            signature,
+            index,
+        };
+        if let Some(cache) = &mut self.proof_cache {
+            cache.insert(digest, include_hash, proof.clone());
+        }
+        Ok(proof)
+    }
+
+    /// Get the indexes of the nodes proving the block at `index` to a peer
+    /// holding the nodes in `digest`, and the index of the signature needed
+    /// to verify them, if any.
+    fn tree_proof(
+        &mut self,
+        index: u64,
+        digest: u64,
+        include_hash: bool,
+    ) -> Result<(Vec<u64>, Option<u64>)> {
         let mut remote_tree = TreeIndex::default();
         let mut nodes = vec![];
-
-        let proof = self.tree.proof_with_digest(
+        let proof = match self.tree.proof_with_digest(
             tree_index(index),
             digest,
             include_hash,
             &mut nodes,
             &mut remote_tree,
-        );
-
-        let proof = match proof {
+        ) {
             Some(proof) => proof,
             None => bail!("No proof available for index {}", index),
         };
+        let sig_index = (proof.verified_by() / 2).checked_sub(1);
+        Ok((proof.nodes().to_vec(), sig_index))
+    }
 
-        let tmp_num = proof.verified_by() / 2;
-        let (sig_index, has_underflow) = tmp_num.overflowing_sub(1);
-        let signature = if has_underflow {
-            None
-        } else {
-            match self.storage.get_signature(sig_index).await {
-                Ok(sig) => Some(sig),
-                Err(_) => None,
-            }
+    /// Cache up to `capacity` generated proofs, so the proofs of popular
+    /// blocks are not rebuilt from the tree for every peer asking for them.
+    /// The cache is emptied whenever the tree changes. A capacity of 0
+    /// disables caching.
+    pub fn set_proof_cache(&mut self, capacity: usize) {
+        self.proof_cache = match capacity {
+            0 => None,
+            capacity => Some(ProofCache::new(capacity)),
         };
+    }
+
+    /// Get the hit rate statistics of the proof cache, if enabled.
+    pub fn proof_cache_stats(&self) -> Option<ProofCacheStats> {
+        self.proof_cache.as_ref().map(|cache| cache.stats())
+    }
+
+    /// Keep an in-memory index of blocks by the hash of their leaf node, for
+    /// `.get_by_hash()`. Enabling it reads the leaf nodes in the local tree;
+    /// blocks appended or downloaded later are indexed as they are stored.
+    pub async fn set_hash_index(&mut self, enabled: bool) -> Result<()> {
+        if !enabled {
+            self.hash_index = None;
+            return Ok(());
+        }
+        let tree = &mut self.tree;
+        let leaves: Vec<u64> = (0..self.length)
+            .map(tree_index)
+            .filter(|index| tree.get(*index))
+            .collect();
+        let mut hash_index = HashMap::with_capacity(leaves.len());
+        for node in self.storage.get_nodes(&leaves).await? {
+            index_leaf(&mut hash_index, &node);
+        }
+        self.hash_index = Some(hash_index);
+        Ok(())
+    }
+
+    /// Look up the index of the first block whose leaf node has `hash`, see
+    /// `.set_hash_index()`. Errors if the index is not enabled.
+    pub fn index_of_hash(&self, hash: &[u8]) -> Result<Option<u64>> {
+        match &self.hash_index {
+            Some(hash_index) => Ok(hash_index.get(hash).copied()),
+            None => bail!("Hash index is not enabled"),
+        }
+    }
+
+    /// Retrieve the block whose leaf node has `hash`, if it is stored
+    /// locally. Identical blocks have the same hash, so this returns the
+    /// data of any of them.
+    pub async fn get_by_hash(&mut self, hash: &[u8]) -> Result<Option<Vec<u8>>> {
+        match self.index_of_hash(hash)? {
+            Some(index) => self.get(index).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieve data from the log as it was when it held `length` blocks.
+    /// Blocks at or past `length` are not part of that version.
+    pub async fn get_at(&mut self, index: u64, length: u64) -> Result<Option<Vec<u8>>> {
+        ensure!(
+            length <= self.length,
+            format!("Length {} is past the end of the feed", length)
+        );
+        if index >= length {
+            return Ok(None);
+        }
+        self.get(index).await
+    }
+
+    /// Return the Nodes which prove the correctness for the Node at index,
+    /// relative to the feed as it was when it held `length` blocks. The
+    /// signature is the one covering that length, if it was stored.
+    pub async fn proof_at(&mut self, index: u64, length: u64, include_hash: bool) -> Result<Proof> {
+        ensure!(
+            index < length && length <= self.length,
+            format!(
+                "No proof available for index {} at length {}",
+                index, length
+            )
+        );
+
+        // Nodes never change once written, so the tree at `length` is the
+        // part of the current tree that only spans blocks before it.
+        let verified_by = tree_index(length);
+        let mut indexes = vec![];
+        let mut next = tree_index(index);
+        if include_hash {
+            indexes.push(next);
+        }
+        while flat::right_span(flat::parent(next)) < verified_by {
+            indexes.push(flat::sibling(next));
+            next = flat::parent(next);
+        }
+        let mut roots = vec![];
+        flat::full_roots(verified_by, &mut roots);
+        indexes.extend(roots.into_iter().filter(|root| *root != next));
 
-        let mut nodes = Vec::with_capacity(proof.nodes().len());
-        for index in proof.nodes() {
-            let node = self.storage.get_node(*index).await?;
-            nodes.push(node);
+        let mut nodes = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            ensure!(
+                self.tree.get(index),
+                format!("Missing tree node {} needed for the proof", index)
+            );
+            nodes.push(self.storage.get_node(index).await?);
         }
 
         Ok(Proof {
             nodes,
-            signature,
+            signature: self.storage.get_signature(length - 1).await.ok(),
             index,
         })
     }
 
+    /// Compute the size of the proof `.proof_with_digest()` would return
+    /// for `index` and `digest`, without generating it.
+    pub fn proof_size(&mut self, index: u64, digest: u64) -> Result<ProofSize> {
+        let mut remote_tree = TreeIndex::default();
+        let mut nodes = vec![];
+        let proof = self.tree.proof_with_digest(
+            tree_index(index),
+            digest,
+            false,
+            &mut nodes,
+            &mut remote_tree,
+        );
+        match proof {
+            Some(proof) => Ok(ProofSize {
+                nodes: proof.nodes().len(),
+                signed: proof.verified_by() >= 2,
+            }),
+            None => bail!("No proof available for index {}", index),
+        }
+    }
+
+    /// Get the locally stored nodes whose flat-tree index is in `range`.
+    pub async fn nodes(&mut self, range: Range<u64>) -> Result<Vec<Node>> {
+        let tree = &mut self.tree;
+        let indexes: Vec<u64> = range.filter(|index| tree.get(*index)).collect();
+        self.storage.get_nodes(&indexes).await
+    }
+
+    /// Count the nodes missing locally on the way up from the flat-tree
+    /// `index` to the first stored node, like `missingNodes` in the
+    /// JavaScript implementation. Nodes past the end of the feed count as 0.
+    pub fn missing_nodes(&mut self, index: u64) -> u64 {
+        let head = tree_index(self.length);
+        if flat::right_span(index) >= head {
+            return 0;
+        }
+        let mut index = index;
+        let mut count = 0;
+        while flat::right_span(index) < head && !self.tree.get(index) {
+            count += 1;
+            index = flat::parent(index);
+        }
+        count
+    }
+
     /// Compute the digest for the index.
     pub fn digest(&mut self, index: u64) -> u64 {
         self.tree.digest(tree_index(index))
     }
 
+    /// Create a request for the block at `index`, carrying the digest of the
+    /// nodes this feed already has.
+    pub fn request(&mut self, index: u64) -> Request {
+        Request {
+            index,
+            nodes: self.digest(index),
+            ..Request::default()
+        }
+    }
+
+    /// Return the proof answering a remote's `request`, leaving out the
+    /// nodes its digest says it already has.
+    pub async fn proof_for(&mut self, request: &Request) -> Result<Proof> {
+        let index = match request.bytes {
+            Some(bytes) => self.seek(bytes).await?.0,
+            None => request.index,
+        };
+        self.proof_with_digest(index, request.nodes, request.hash)
+            .await
+    }
+
     /// Insert data into the tree at `index`. Verifies the `proof` when inserting
     /// to make sure data is correct. Useful when replicating data from a remote
     /// host.
     pub async fn put(&mut self, index: u64, data: Option<&[u8]>, mut proof: Proof) -> Result<()> {
+        self.ensure_space(data.map_or(0, |data| data.len() as u64))?;
         let mut next = tree_index(index);
         let mut trusted: Option<u64> = None;
         let mut missing = vec![];
@@ -314,7 +1267,7 @@ where
         let mut top = match data {
             Some(data) => Node::new(
                 tree_index(index),
-                Hash::from_leaf(&data).as_bytes().to_owned(),
+                Hash::from_leaf(data).as_bytes().to_owned(),
                 data.len() as u64,
             ),
             None => proof.nodes.remove(0),
@@ -338,9 +1291,14 @@ where
                 node = missing_nodes.remove(0);
             } else {
                 // TODO: panics here
-                let nodes = self.verify_roots(&top, &mut proof).await?;
+                let (nodes, length) = self.verify_roots(&top, &mut proof).await?;
                 visited.extend_from_slice(&nodes);
-                self.write(index, data, &visited, proof.signature).await?;
+                // Lazy signatures are only stored once verified.
+                let signature = match self.lazy_signatures {
+                    true => None,
+                    false => proof.signature.map(|sig| (length - 1, sig)),
+                };
+                self.write(index, data, &visited, signature).await?;
                 return Ok(());
             }
 
@@ -371,39 +1329,65 @@ where
     // - ._writeDone()
     //
     // Arguments are: (index, data, node, sig, from, cb)
-    async fn write(
+    //
+    // The signature is stored at the index of the last block it covers.
+    pub(crate) async fn write(
         &mut self,
         index: u64,
         data: Option<&[u8]>,
         nodes: &[Node],
-        sig: Option<Signature>,
+        sig: Option<(u64, Signature)>,
     ) -> Result<()> {
         for node in nodes {
             self.storage.put_node(node).await?;
         }
 
         if let Some(data) = data {
-            self.storage.put_data(index, data, &nodes).await?;
+            if let Some(prefetch) = &mut self.prefetch {
+                prefetch.forget(index);
+            }
+            self.storage.put_data(index, data, nodes).await?;
+            if self.records_timestamps() {
+                self.storage
+                    .put_timestamps(index, 1, retention::unix_time())
+                    .await?;
+            }
+            if self.checksums {
+                self.storage.put_checksum(index, crc32c(data)).await?;
+            }
         }
 
-        if let Some(sig) = sig {
-            let sig = sig.borrow();
-            self.storage.put_signature(index, sig).await?;
+        if let Some((sig_index, sig)) = sig {
+            self.storage.put_signature(sig_index, sig).await?;
         }
 
         for node in nodes {
             self.tree.set(node.index);
+            if let Some(hash_index) = &mut self.hash_index {
+                index_leaf(hash_index, node);
+            }
         }
 
         self.tree.set(tree_index(index));
+        if let Some(cache) = &mut self.proof_cache {
+            cache.invalidate();
+        }
+        if let (Some(tentative), Some(_)) = (&mut self.tentative, data) {
+            tentative.blocks.add(index..index + 1);
+        }
 
         if let Some(_data) = data {
             if self.bitfield.set(index, true).is_changed() {
+                self.persist_bitfield(index).await?;
                 // TODO: emit "download" event
             }
             // TODO: check peers.length, call ._announce if peers exist.
         }
 
+        if let Some(changes) = &mut self.changes {
+            changes.bump().await?;
+        }
+
         // TODO: Discern between "primary" and "replica" streams.
         // if (!this.writable) {
         //   if (!this._synced) this._synced = this.bitfield.iterator(0, this.length)
@@ -429,7 +1413,7 @@ where
     }
 
     /// Verify the entire feed. Checks a signature against the signature of all
-    /// root nodes combined.
+    /// root nodes combined. An empty feed has no roots, so it never verifies.
     pub async fn verify(&mut self, index: u64, signature: &Signature) -> Result<()> {
         let roots = self.root_hashes(index).await?;
         let roots: Vec<_> = roots.into_iter().map(Arc::new).collect();
@@ -437,7 +1421,59 @@ where
         let hash = Hash::from_roots(&roots);
         let message = hash_with_length_as_bytes(hash, index + 1);
 
-        verify_compat(&self.public_key, &message, Some(signature))?;
+        verify_compat(&self.public_key, &message, Some(signature))
+            .inspect_err(|_| self.verification_failure())?;
+        Ok(())
+    }
+
+    /// Write the bitfield byte holding `index` to the storage.
+    async fn persist_bitfield(&mut self, index: u64) -> Result<()> {
+        let byte = self.bitfield.data_byte(index);
+        self.storage.put_data_bitfield(index / 8, byte).await
+    }
+
+    /// Load blocks that another writer appended to the storage, after
+    /// verifying the signature covering them.
+    async fn load_state(&mut self) -> Result<()> {
+        self.pins = self.storage.read_pins().await?;
+        self.timestamps |= self.storage.has_timestamps();
+        let length = self.storage.signature_count().await?;
+        if length <= self.length {
+            return Ok(());
+        }
+
+        let mut indexes = vec![];
+        flat::full_roots(tree_index(length), &mut indexes);
+        let mut roots = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            roots.push(self.storage.get_node(index).await?);
+        }
+        let signature = self.storage.get_signature(length - 1).await?;
+        let message = hash_with_length_as_bytes(Hash::from_roots(&roots), length);
+        verify_compat(&self.public_key, &message, Some(&signature))?;
+
+        let mut byte = 0;
+        for index in self.length..length {
+            if index == self.length || index % 8 == 0 {
+                byte = self.storage.get_data_bitfield(index / 8).await?;
+            }
+            // Only blocks stored locally have their leaf in the tree.
+            if byte & (128 >> (index % 8)) != 0 {
+                self.bitfield.set(index, true);
+                self.tree.set(tree_index(index));
+            }
+        }
+        for root in &roots {
+            self.tree.set(root.index);
+        }
+
+        self.length = length;
+        self.byte_length = roots.iter().map(|root| root.len()).sum();
+        self.merkle = Merkle::from_roots(roots.into_iter().map(Arc::new).collect());
+        if let Some(cache) = &mut self.proof_cache {
+            cache.invalidate();
+        }
+        self.notify_length();
         Ok(())
     }
 
@@ -459,13 +1495,17 @@ where
         }
     }
 
-    /// Get all root hashes from the feed.
+    /// Get the roots of the tree covering blocks `0..=index`, which the
+    /// signature at `index` signs. An empty feed has no roots.
     // In the JavaScript implementation this calls to `._getRootsToVerify()`
     // internally. In Rust it seems better to just inline the code.
     pub async fn root_hashes(&mut self, index: u64) -> Result<Vec<Node>> {
         ensure!(
-            index <= self.length,
-            format!("Root index bounds exceeded {} > {}", index, self.length)
+            index < self.length,
+            format!(
+                "No roots cover block {}, the feed has {} blocks",
+                index, self.length
+            )
         );
         let roots_index = tree_index(index) + 2;
         let mut indexes = vec![];
@@ -480,17 +1520,67 @@ where
         Ok(roots)
     }
 
+    /// Get the message witnesses sign to vouch for the feed at `length`:
+    /// the public key of the feed, the hash of its roots and the length.
+    pub async fn checkpoint(&mut self, length: u64) -> Result<Vec<u8>> {
+        ensure!(
+            length > 0 && length <= self.length,
+            format!("No checkpoint at length {}", length)
+        );
+        let roots = self.root_hashes(length - 1).await?;
+        let hash = Hash::from_roots(&roots);
+        Ok(witness::checkpoint(
+            &self.public_key,
+            hash.as_bytes(),
+            length,
+        ))
+    }
+
+    /// Verify and store a witness's co-signature of a checkpoint.
+    pub async fn add_witness(&mut self, witness: &Witness) -> Result<()> {
+        let checkpoint = self.checkpoint(witness.length).await?;
+        witness.verify(&checkpoint)?;
+        self.storage.append_witness(witness).await
+    }
+
+    /// Get the stored witnesses, in the order they were added.
+    pub async fn witnesses(&mut self) -> Result<Vec<Witness>> {
+        self.storage.read_witnesses().await
+    }
+
+    /// Check whether the witness with `public_key` vouched for the feed at
+    /// `length` or later, which covers the blocks before `length`.
+    pub async fn is_witnessed_by(&mut self, public_key: &PublicKey, length: u64) -> Result<bool> {
+        let witnesses = self.storage.read_witnesses().await?;
+        Ok(witnesses
+            .iter()
+            .any(|witness| witness.public_key == *public_key && witness.length >= length))
+    }
+
     /// Access the public key.
     pub fn public_key(&self) -> &PublicKey {
         &self.public_key
     }
 
+    /// Get a `hyper://` URI for the feed, with the options it was opened
+    /// with. See [FeedUri].
+    pub fn to_uri(&self) -> FeedUri {
+        FeedUri {
+            checksums: self.checksums,
+            quarantine: self.quarantine,
+            verify_reads: self.verify_reads,
+            ..FeedUri::new(self.public_key)
+        }
+    }
+
     /// Access the secret key.
     pub fn secret_key(&self) -> &Option<SecretKey> {
         &self.secret_key
     }
 
-    async fn verify_roots(&mut self, top: &Node, proof: &mut Proof) -> Result<Vec<Node>> {
+    /// Verify the signature of a proof against the roots it leads to.
+    /// Returns the nodes to store and the length the signature covers.
+    async fn verify_roots(&mut self, top: &Node, proof: &mut Proof) -> Result<(Vec<Node>, u64)> {
         let last_node = if !proof.nodes.is_empty() {
             proof.nodes[proof.nodes.len() - 1].index
         } else {
@@ -522,17 +1612,106 @@ where
         let checksum = Hash::from_roots(&roots);
         let length = verified_by / 2;
         let message = hash_with_length_as_bytes(checksum, length);
-        verify_compat(&self.public_key, &message, proof.signature())?;
+        if self.lazy_signatures {
+            let signature = *proof
+                .signature()
+                .ok_or_else(|| anyhow!("Proof of new roots carries no signature"))?;
+            let (length_before, byte_length) = (self.length, self.byte_length);
+            self.tentative
+                .get_or_insert_with(|| Tentative::new(length_before, byte_length))
+                .pending
+                .push(PendingSignature {
+                    length,
+                    message,
+                    signature,
+                });
+        } else {
+            verify_compat(&self.public_key, &message, proof.signature())
+                .inspect_err(|_| self.verification_failure())?;
+        }
 
         // Update the length if we grew the feed.
         let len = verified_by / 2;
         if len > self.len() {
             self.length = len;
-            self.byte_length = roots.iter().fold(0, |acc, root| acc + root.index)
+            self.byte_length = roots.iter().map(|root| root.len()).sum();
+            self.notify_length();
             // TODO: emit('append')
         }
 
-        Ok(extra_nodes)
+        Ok((extra_nodes, len))
+    }
+
+    /// Get the blocks stored with lazy signatures whose signature has not
+    /// been verified yet, see `FeedBuilder::lazy_signatures()`.
+    pub fn tentative(&self) -> Ranges {
+        match &self.tentative {
+            Some(tentative) => tentative.blocks.clone(),
+            None => Ranges::new(),
+        }
+    }
+
+    /// Verify the signatures received since the last confirmation, storing
+    /// them if they are valid and returning the blocks they confirm. Meant
+    /// to be called from a background task when ingesting with lazy
+    /// signatures.
+    ///
+    /// If any signature is invalid, every tentative block is discarded and
+    /// the feed goes back to the length it had before the first of them,
+    /// since blocks stored later may have been checked against nodes only
+    /// that signature vouched for.
+    pub async fn confirm_tentative(&mut self) -> Result<Ranges> {
+        let tentative = match self.tentative.take() {
+            Some(tentative) => tentative,
+            None => return Ok(Ranges::new()),
+        };
+        let public_key = self.public_key;
+        let valid = tentative.pending.iter().all(|pending| {
+            verify_compat(&public_key, &pending.message, Some(&pending.signature)).is_ok()
+        });
+        if valid {
+            for pending in &tentative.pending {
+                let result = self
+                    .storage
+                    .put_signature(pending.length - 1, pending.signature)
+                    .await;
+                if let Err(err) = result {
+                    self.tentative = Some(tentative);
+                    return Err(err);
+                }
+            }
+            return Ok(tentative.blocks);
+        }
+
+        self.verification_failure();
+        for index in tentative.blocks.iter() {
+            self.bitfield.set(index, false);
+            self.persist_bitfield(index).await?;
+        }
+        let (length, byte_length) = tentative.confirmed;
+        self.length = length;
+        self.byte_length = byte_length;
+        // Nodes stored with the tentative blocks can't be trusted, so rebuild
+        // the tree from the stored blocks and the confirmed roots, as when
+        // loading the feed.
+        let mut tree = TreeIndex::default();
+        for index in (0..length).filter(|index| self.bitfield.get(*index)) {
+            tree.set(tree_index(index));
+        }
+        let mut roots = vec![];
+        flat::full_roots(tree_index(length), &mut roots);
+        for root in roots {
+            tree.set(root);
+        }
+        self.tree = tree;
+        if let Some(cache) = &mut self.proof_cache {
+            cache.invalidate();
+        }
+        self.notify_length();
+        bail!(
+            "Invalid signature, discarded {} tentative blocks",
+            tentative.blocks.count()
+        )
     }
 
     /// Audit all data in the feed. Checks that all current data matches
@@ -550,7 +1729,7 @@ where
                     valid_blocks += 1;
                 } else {
                     invalid_blocks += 1;
-                    self.bitfield.set(index, false);
+                    self.discard_corrupt(index, node.hash, data).await?;
                 }
             }
         }
@@ -560,18 +1739,108 @@ where
         })
     }
 
+    /// Re-verify the stored blocks in `range` against the tree, up to the
+    /// roots covered by the first signature at or after the end of the
+    /// range, and check that signature. Unlike `.audit()`, this only reads
+    /// the blocks in the range and the nodes above them, and doesn't mark
+    /// failing blocks as missing.
+    ///
+    /// Errors if no stored signature covers the range or it doesn't verify;
+    /// otherwise returns a report for every block in the range.
+    pub async fn verify_range(&mut self, range: Range<u64>) -> Result<Vec<BlockReport>> {
+        ensure!(
+            range.end <= self.length,
+            format!("Range {:?} is past the end of the feed", range)
+        );
+        if range.start >= range.end {
+            return Ok(vec![]);
+        }
+
+        let mut covering = None;
+        for index in range.end - 1..self.length {
+            if let Ok(signature) = self.storage.get_signature(index).await {
+                covering = Some((index, signature));
+                break;
+            }
+        }
+        let (signed, signature) = match covering {
+            Some(covering) => covering,
+            None => bail!("No signature covers blocks {:?}", range),
+        };
+        self.verify(signed, &signature).await?;
+        let mut roots = vec![];
+        flat::full_roots(tree_index(signed + 1), &mut roots);
+
+        // Nodes whose path up to a root was checked for an earlier block.
+        let mut verified = HashSet::new();
+        let mut reports = Vec::with_capacity((range.end - range.start) as usize);
+        for index in range {
+            let (bytes, status) = if self.bitfield.get(index) {
+                let node = self.storage.get_node(tree_index(index)).await?;
+                let data = self.storage.get_data(index).await?;
+                let status = if node.hash != Hash::from_leaf(&data).as_bytes() {
+                    self.verification_failure();
+                    BlockStatus::Invalid("Data does not match the tree".into())
+                } else {
+                    self.verify_path(node, &roots, &mut verified).await?
+                };
+                (Some(data.len() as u64), status)
+            } else {
+                let status = BlockStatus::Missing("Block is not stored locally".into());
+                (None, status)
+            };
+            reports.push(BlockReport {
+                index,
+                bytes,
+                status,
+            });
+        }
+        Ok(reports)
+    }
+
+    /// Check the hashes of the nodes from `node` up to one of `roots`.
+    async fn verify_path(
+        &mut self,
+        mut node: Node,
+        roots: &[u64],
+        verified: &mut HashSet<u64>,
+    ) -> Result<BlockStatus> {
+        let mut path = vec![];
+        while !roots.contains(&node.index) && !verified.contains(&node.index) {
+            let sibling = flat::sibling(node.index);
+            let parent = flat::parent(node.index);
+            for index in &[sibling, parent] {
+                if !self.tree.get(*index) {
+                    let error = format!("Tree node {} is not stored locally", index);
+                    return Ok(BlockStatus::Missing(error));
+                }
+            }
+            let sibling = self.storage.get_node(sibling).await?;
+            let parent = self.storage.get_node(parent).await?;
+            if parent.hash != Hash::from_hashes(&node, &sibling).as_bytes() {
+                self.verification_failure();
+                let error = format!("Tree node {} does not match its children", parent.index);
+                return Ok(BlockStatus::Invalid(error));
+            }
+            path.push(node.index);
+            node = parent;
+        }
+        verified.extend(path);
+        Ok(BlockStatus::Verified)
+    }
+
     /// Expose the bitfield attribute to use on during download
     pub fn bitfield(&self) -> &Bitfield {
         &self.bitfield
     }
 
-    /// (unimplemented) Provide a range of data to download.
-    pub fn download(&mut self, _range: Range<u64>) -> Result<()> {
+    /// (unimplemented) Provide ranges of data to download.
+    pub fn download(&mut self, _ranges: impl Into<Ranges>) -> Result<()> {
         unimplemented!();
     }
 
-    /// (unimplemented) Provide a range of data to remove from the local storage.
-    pub fn undownload(&mut self, _range: Range<u64>) -> Result<()> {
+    /// (unimplemented) Provide ranges of data to remove from the local storage.
+    pub fn undownload(&mut self, _ranges: impl Into<Ranges>) -> Result<()> {
         unimplemented!();
     }
 
@@ -585,6 +1854,40 @@ where
         unimplemented!();
     }
 
+    /// Load the whole feed into memory, returning a read-only copy that is
+    /// served entirely from RAM. Meant for small, frequently read feeds.
+    pub async fn load_into_memory(&mut self) -> Result<Feed<RandomAccessMemory>> {
+        let storage = self.storage.to_memory().await?;
+        let mut feed = FeedBuilder::new(self.public_key, storage)
+            .checksums(self.checksums)
+            .build()?;
+
+        for index in 0..self.length {
+            if self.bitfield.get(index) {
+                feed.bitfield.set(index, true);
+            }
+        }
+        for index in 0..tree_index(self.length) {
+            if self.tree.get(index) {
+                feed.tree.set(index);
+            }
+        }
+        feed.length = self.length;
+        feed.byte_length = self.byte_length;
+
+        Ok(feed)
+    }
+
+    /// Check if another process has written to the feed directory since this
+    /// instance last wrote to it. Always `false` for feeds that were not
+    /// opened from a directory.
+    pub async fn has_external_changes(&self) -> Result<bool> {
+        match &self.changes {
+            Some(changes) => changes.changed().await,
+            None => Ok(false),
+        }
+    }
+
     /// Update all peers.
     pub fn update_peers(&mut self) {
         for peer in &mut self.peers {
@@ -598,10 +1901,83 @@ impl Feed<RandomAccessDisk> {
     // TODO: Ensure that dir is always a directory.
     // NOTE: Should we `mkdirp` here?
     // NOTE: Should we call these `data.bitfield` / `data.tree`?
+    ///
+    /// Directories written by the JavaScript implementation, which keeps the
+    /// secret key in a separate `secret_key` file, are supported as well.
+    ///
+    /// The feed takes an advisory lock on the directory, as replicas are
+    /// written to by `.put()`, `.clear()` and `.gc()` too, so opening it from
+    /// a second process fails. Use `open_read_only()` to read a directory
+    /// another process writes to.
     pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let dir = path.as_ref().to_owned();
+        // Lock before touching the stores, so a second writer fails without
+        // changing the directory.
+        std::fs::create_dir_all(&dir)?;
+        let lock = DirLock::acquire(&dir)?;
         let storage = Storage::new_disk(&dir).await?;
-        Self::with_storage(storage).await
+        let mut feed = Self::with_storage(storage).await?;
+        // An encrypted key takes precedence over a JS `secret_key` file.
+        if feed.secret_key.is_none() && !feed.storage.is_secret_key_encrypted().await? {
+            feed.secret_key = compat::read_secret_key(&dir, &feed.public_key)?;
+        }
+        feed.lock = Some(lock);
+        feed.changes = Some(ChangeCounter::open(&dir).await?);
+        Ok(feed)
+    }
+
+    /// Open an existing feed directory for reading only, without taking the
+    /// writer lock. Blocks appended later by the writer process can be picked
+    /// up with `.refresh()`.
+    pub async fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let dir = path.as_ref().to_owned();
+        let mut storage = Storage::open_disk(&dir).await?;
+        let public_key = storage.read_public_key().await?;
+        let mut feed = FeedBuilder::new(public_key, storage).build()?;
+        feed.changes = Some(ChangeCounter::open(&dir).await?);
+        feed.refresh().await?;
+        Ok(feed)
+    }
+
+    /// Re-read the state written by the writer process. Returns `true` if the
+    /// feed grew.
+    pub async fn refresh(&mut self) -> Result<bool> {
+        ensure!(
+            self.secret_key.is_none(),
+            "Only read-only feeds can be refreshed"
+        );
+        let changes = match &mut self.changes {
+            Some(changes) => changes,
+            None => bail!("Feed was not opened from a directory"),
+        };
+        changes.sync().await?;
+
+        // Reopen the stores, as file handles don't see growth from other
+        // processes.
+        self.storage = Storage::open_disk(changes.dir()).await?;
+
+        let length = self.length;
+        self.load_state().await?;
+        Ok(self.length > length)
+    }
+}
+
+impl Feed<RandomAccessMemory> {
+    /// Create an in-memory feed with the key pair derived from `seed` by
+    /// `keypair_from_seed()`, holding `blocks` blocks where block `i` is the
+    /// bytes of `"<seed>-<i>"`. The same seed always gives the same keys,
+    /// hashes and signatures, for golden tests and test vectors.
+    pub async fn test_with_seed(seed: &str, blocks: u64) -> Result<Self> {
+        let keypair = keypair_from_seed(seed.as_bytes());
+        let storage = Storage::new_memory().await?;
+        let mut feed = Self::builder(keypair.public, storage)
+            .secret_key(keypair.secret)
+            .build()?;
+        for index in 0..blocks {
+            feed.append(format!("{}-{}", seed, index).as_bytes())
+                .await?;
+        }
+        Ok(feed)
     }
 }
 
@@ -643,8 +2019,18 @@ fn tree_index(index: u64) -> u64 {
     2 * index
 }
 
+/// Add `node` to a hash index if it is a leaf, keeping the lowest index of
+/// identical blocks.
+fn index_leaf(hash_index: &mut HashMap<Vec<u8>, u64>, node: &Node) {
+    if node.index.is_multiple_of(2) {
+        hash_index
+            .entry(node.hash.clone())
+            .or_insert(node.index / 2);
+    }
+}
+
 /// Extend a hash with a big-endian encoded length.
-fn hash_with_length_as_bytes(hash: Hash, length: u64) -> Vec<u8> {
+pub(crate) fn hash_with_length_as_bytes(hash: Hash, length: u64) -> Vec<u8> {
     [hash.as_bytes(), &length.to_be_bytes()].concat().to_vec()
 }
 
@@ -2,7 +2,10 @@ use ed25519_dalek::{PublicKey, SecretKey};
 
 use crate::bitfield::Bitfield;
 use crate::crypto::Merkle;
+use crate::ranges::Ranges;
+use crate::retention::{self, Retention};
 use crate::storage::Storage;
+use crate::telemetry::Counters;
 use random_access_storage::RandomAccess;
 use std::fmt::Debug;
 use tree_index::TreeIndex;
@@ -21,6 +24,11 @@ where
     storage: Storage<T>,
     public_key: PublicKey,
     secret_key: Option<SecretKey>,
+    checksums: bool,
+    quarantine: bool,
+    verify_reads: bool,
+    lazy_signatures: bool,
+    retention: Vec<Retention>,
 }
 
 impl<T> FeedBuilder<T>
@@ -34,6 +42,11 @@ where
             storage,
             public_key,
             secret_key: None,
+            checksums: false,
+            quarantine: false,
+            verify_reads: false,
+            lazy_signatures: false,
+            retention: vec![],
         }
     }
 
@@ -43,6 +56,46 @@ where
         self
     }
 
+    /// Store a `CRC32C` checksum for every block, and check it on `.get()`.
+    /// Catches local disk corruption without reading the merkle tree.
+    pub fn checksums(mut self, checksums: bool) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// Move the bytes of blocks that fail to verify in `.audit()` or
+    /// `.get()` to a quarantine store before marking them as missing, to
+    /// debug storage bugs. See `Feed::quarantined()`.
+    pub fn quarantine(mut self, quarantine: bool) -> Self {
+        self.quarantine = quarantine;
+        self
+    }
+
+    /// Hash the data of every block read by `.get()` and compare it to the
+    /// block's tree node, failing the read on a mismatch. Catches any local
+    /// corruption, at the cost of a hash and a tree read per block. Corrupt
+    /// blocks are handled as in `.audit()`.
+    pub fn verify_reads(mut self, verify_reads: bool) -> Self {
+        self.verify_reads = verify_reads;
+        self
+    }
+
+    /// Store blocks received with `.put()` once they hash up to the roots in
+    /// their proof, leaving the signature of the roots to be verified later
+    /// by `Feed::confirm_tentative()`. Speeds up ingesting from a trusted
+    /// source; until confirmed, the blocks are listed by
+    /// `Feed::tentative()`.
+    pub fn lazy_signatures(mut self, lazy_signatures: bool) -> Self {
+        self.lazy_signatures = lazy_signatures;
+        self
+    }
+
+    /// Add a retention policy applied by `.gc()`, see [Retention].
+    pub fn retention(mut self, policy: Retention) -> Self {
+        self.retention.push(policy);
+        self
+    }
+
     /// Finalize the builder.
     #[inline]
     pub fn build(self) -> Result<Feed<T>> {
@@ -56,6 +109,24 @@ where
             secret_key: self.secret_key,
             storage: self.storage,
             peers: vec![],
+            checksums: self.checksums,
+            quarantine: self.quarantine,
+            verify_reads: self.verify_reads,
+            lazy_signatures: self.lazy_signatures,
+            tentative: None,
+            lock: None,
+            changes: None,
+            append_timings: None,
+            timestamps: retention::has_age(&self.retention),
+            retention: self.retention,
+            pins: Ranges::new(),
+            proof_cache: None,
+            watch: None,
+            counters: Counters::default(),
+            hash_index: None,
+            min_free_space: None,
+            prefetch: None,
+            access_log: None,
         })
     }
 }
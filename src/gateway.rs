@@ -0,0 +1,297 @@
+//! HTTP gateway serving feeds to clients that don't speak the replication
+//! protocol, such as browsers and `curl`.
+//!
+//! Feeds are opened read-only from their directories, so they can keep being
+//! written to by another process. Routes, with `<key>` the hex encoded public
+//! key:
+//! - `GET /feed/<key>/info`: JSON with the length, byte length and key.
+//! - `GET /feed/<key>/block/<index>`: the raw block.
+//! - `GET /feed/<key>/bytes`: the concatenated blocks, honouring a
+//!   `Range: bytes=<start>-<end>` header.
+//! - `GET /feed/<key>/proof/<index>`: JSON with the merkle proof of a block.
+//! - `GET /metrics`: Prometheus metrics, when built with the `prometheus`
+//!   feature and a handle was passed to `.prometheus()`.
+
+use crate::crypto::to_hex as hex;
+use crate::feed::Feed;
+use crate::telemetry;
+use anyhow::Result;
+use async_std::channel;
+use async_std::sync::Mutex;
+use futures::{AsyncBufRead, TryStreamExt};
+#[cfg(feature = "prometheus")]
+use metrics_exporter_prometheus::PrometheusHandle;
+use random_access_disk::RandomAccessDisk;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use tide::convert::json;
+use tide::http::headers::{ACCEPT_RANGES, CONTENT_RANGE};
+use tide::{Body, Request, Response, StatusCode};
+
+type SharedFeed = Arc<Mutex<Feed<RandomAccessDisk>>>;
+
+/// Number of blocks buffered ahead of a client reading a byte range.
+const STREAM_BUFFER: usize = 16;
+
+/// HTTP gateway over a set of feeds.
+#[derive(Debug, Default)]
+pub struct Gateway {
+    feeds: HashMap<String, SharedFeed>,
+    #[cfg(feature = "prometheus")]
+    prometheus: Option<PrometheusHandle>,
+}
+
+/// Request state shared by all routes.
+#[derive(Debug, Clone)]
+pub struct State {
+    feeds: Arc<HashMap<String, SharedFeed>>,
+    #[cfg(feature = "prometheus")]
+    prometheus: Option<PrometheusHandle>,
+}
+
+impl Gateway {
+    /// Create a new instance without any feeds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve the feed stored in `dir`. Returns the hex encoded key it is
+    /// served under.
+    pub async fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<String> {
+        let feed = Feed::open_read_only(dir).await?;
+        let key = hex(&feed.public_key().to_bytes());
+        self.feeds.insert(key.clone(), Arc::new(Mutex::new(feed)));
+        telemetry::gateway_feed_added();
+        Ok(key)
+    }
+
+    /// Serve the metrics rendered by a Prometheus recorder on `/metrics`.
+    #[cfg(feature = "prometheus")]
+    pub fn prometheus(mut self, handle: PrometheusHandle) -> Self {
+        self.prometheus = Some(handle);
+        self
+    }
+
+    /// Build the HTTP server.
+    pub fn into_server(self) -> tide::Server<State> {
+        let mut server = tide::with_state(State {
+            feeds: Arc::new(self.feeds),
+            #[cfg(feature = "prometheus")]
+            prometheus: self.prometheus,
+        });
+        server.at("/feed/:key/info").get(info);
+        server.at("/feed/:key/block/:index").get(block);
+        server.at("/feed/:key/bytes").get(bytes);
+        server.at("/feed/:key/proof/:index").get(proof);
+        #[cfg(feature = "prometheus")]
+        server.at("/metrics").get(metrics);
+        server
+    }
+
+    /// Serve the feeds on `addr` until the listener fails.
+    pub async fn listen<A: tide::listener::ToListener<State>>(self, addr: A) -> Result<()> {
+        self.into_server().listen(addr).await?;
+        Ok(())
+    }
+}
+
+/// Look up the feed named in the request, picking up blocks appended by the
+/// writer since the last request.
+async fn feed(req: &Request<State>) -> tide::Result<SharedFeed> {
+    let feed = match req.state().feeds.get(req.param("key")?) {
+        Some(feed) => feed.clone(),
+        None => return Err(tide::Error::from_str(StatusCode::NotFound, "Unknown feed")),
+    };
+    {
+        let mut feed = feed.lock().await;
+        if feed.has_external_changes().await? {
+            feed.refresh().await?;
+        }
+    }
+    Ok(feed)
+}
+
+fn index(req: &Request<State>) -> tide::Result<u64> {
+    req.param("index")?
+        .parse()
+        .map_err(|_| tide::Error::from_str(StatusCode::BadRequest, "Invalid index"))
+}
+
+async fn info(req: Request<State>) -> tide::Result {
+    let feed = feed(&req).await?;
+    let feed = feed.lock().await;
+    let body = json!({
+        "key": hex(&feed.public_key().to_bytes()),
+        "length": feed.len(),
+        "byteLength": feed.byte_len(),
+    });
+    Ok(Response::builder(StatusCode::Ok)
+        .body(Body::from_json(&body)?)
+        .build())
+}
+
+async fn block(req: Request<State>) -> tide::Result {
+    let index = index(&req)?;
+    let feed = feed(&req).await?;
+    let data = feed.lock().await.get(index).await?;
+    match data {
+        Some(data) => {
+            telemetry::gateway_served(1, data.len() as u64);
+            Ok(Response::builder(StatusCode::Ok)
+                .body(Body::from_bytes(data))
+                .build())
+        }
+        None => Ok(Response::new(StatusCode::NotFound)),
+    }
+}
+
+async fn proof(req: Request<State>) -> tide::Result {
+    let index = index(&req)?;
+    let feed = feed(&req).await?;
+    let proof = {
+        let mut feed = feed.lock().await;
+        if !feed.has(index) {
+            return Ok(Response::new(StatusCode::NotFound));
+        }
+        feed.proof(index, false).await?
+    };
+    let nodes: Vec<_> = proof
+        .nodes
+        .iter()
+        .map(|node| {
+            json!({
+                "index": node.index,
+                "length": node.length,
+                "hash": hex(&node.hash),
+            })
+        })
+        .collect();
+    let body = json!({
+        "index": proof.index,
+        "nodes": nodes,
+        "signature": proof.signature.map(|signature| hex(&signature.to_bytes())),
+    });
+    Ok(Response::builder(StatusCode::Ok)
+        .body(Body::from_json(&body)?)
+        .build())
+}
+
+async fn bytes(req: Request<State>) -> tide::Result {
+    let feed = feed(&req).await?;
+    let byte_len = feed.lock().await.byte_len();
+
+    let range = match req.header("range") {
+        Some(header) => match parse_range(header.as_str(), byte_len) {
+            Some(range) => Some(range),
+            None => {
+                return Ok(Response::builder(StatusCode::RequestedRangeNotSatisfiable)
+                    .header(CONTENT_RANGE, format!("bytes */{}", byte_len))
+                    .build())
+            }
+        },
+        None => None,
+    };
+    let (start, end) = range.unwrap_or((0, byte_len));
+    let len = end - start;
+
+    let mut res = Response::new(match range {
+        Some(_) => StatusCode::PartialContent,
+        None => StatusCode::Ok,
+    });
+    res.insert_header(ACCEPT_RANGES, "bytes");
+    if range.is_some() {
+        res.insert_header(
+            CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end - 1, byte_len),
+        );
+    }
+    if len > 0 {
+        res.set_body(Body::from_reader(
+            stream_bytes(feed, start, end),
+            Some(len as usize),
+        ));
+    }
+    Ok(res)
+}
+
+#[cfg(feature = "prometheus")]
+async fn metrics(req: Request<State>) -> tide::Result {
+    match &req.state().prometheus {
+        Some(handle) => Ok(Response::builder(StatusCode::Ok)
+            .content_type("text/plain; version=0.0.4")
+            .body(handle.render())
+            .build()),
+        None => Ok(Response::new(StatusCode::NotFound)),
+    }
+}
+
+/// Stream the bytes in `start..end` from a feed, reading a block at a time.
+fn stream_bytes(
+    feed: SharedFeed,
+    start: u64,
+    end: u64,
+) -> impl AsyncBufRead + Unpin + Send + Sync + 'static {
+    let (sender, receiver) = channel::bounded(STREAM_BUFFER);
+    async_std::task::spawn(async move {
+        let result: Result<()> = async {
+            let (mut index, mut skip) = feed.lock().await.seek(start).await?;
+            let mut remaining = end - start;
+            while remaining > 0 {
+                let data = match feed.lock().await.get(index).await? {
+                    Some(data) => data,
+                    None => anyhow::bail!("Missing block {}", index),
+                };
+                let take = std::cmp::min(data.len() as u64 - skip, remaining);
+                let chunk = data[skip as usize..(skip + take) as usize].to_vec();
+                telemetry::gateway_served(1, take);
+                if sender.send(Ok(chunk)).await.is_err() {
+                    return Ok(());
+                }
+                remaining -= take;
+                skip = 0;
+                index += 1;
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(err) = result {
+            let err = io::Error::other(err.to_string());
+            sender.send(Err(err)).await.ok();
+        }
+    });
+    receiver.into_async_read()
+}
+
+/// Parse a single `bytes=<start>-<end>` range into a half-open range.
+fn parse_range(header: &str, byte_len: u64) -> Option<(u64, u64)> {
+    let range = header.trim().strip_prefix("bytes=")?;
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", "") => return None,
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (byte_len.saturating_sub(suffix), byte_len)
+        }
+        (start, "") => (start.parse().ok()?, byte_len),
+        (start, end) => {
+            let end: u64 = end.parse().ok()?;
+            (start.parse().ok()?, end.saturating_add(1).min(byte_len))
+        }
+    };
+    if start >= end {
+        return None;
+    }
+    Some((start, end))
+}
+
+#[test]
+fn should_parse_ranges() {
+    assert_eq!(parse_range("bytes=0-4", 10), Some((0, 5)));
+    assert_eq!(parse_range("bytes=5-", 10), Some((5, 10)));
+    assert_eq!(parse_range("bytes=-3", 10), Some((7, 10)));
+    assert_eq!(parse_range("bytes=8-100", 10), Some((8, 10)));
+    assert_eq!(parse_range("bytes=10-", 10), None);
+    assert_eq!(parse_range("items=0-4", 10), None);
+}
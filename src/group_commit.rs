@@ -0,0 +1,123 @@
+//! Coalesce appends from concurrent callers into batches that share one
+//! signature.
+
+use crate::feed::Feed;
+use anyhow::{anyhow, Result};
+use async_std::channel::{self, Receiver, Sender};
+use async_std::future;
+use async_std::sync::Mutex;
+use async_std::task;
+use futures::channel::oneshot;
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A block waiting to be committed.
+#[derive(Debug)]
+struct Pending {
+    data: Vec<u8>,
+    done: oneshot::Sender<Result<u64>>,
+}
+
+/// Handle for appending to a feed through group commit.
+///
+/// Blocks are queued and appended by a background task with
+/// `.append_batch()`, once `max_blocks` are waiting or `interval` has passed
+/// since the first of them was queued. This trades up to `interval` of
+/// latency for one signature per batch. Blocks are appended in the order
+/// they were queued, across every handle. The task stops once every handle
+/// is dropped, or `.shutdown()` is called, and flushes the storage as it
+/// stops.
+#[derive(Debug, Clone)]
+pub struct GroupCommit {
+    sender: Sender<Pending>,
+    /// Closed when the task stops.
+    stopped: Receiver<()>,
+    /// Why flushing the storage failed when the task stopped, if it did.
+    flush_error: Arc<Mutex<Option<String>>>,
+}
+
+impl GroupCommit {
+    /// Start committing batches of at most `max_blocks` blocks to `feed`.
+    pub fn new<T>(feed: Arc<Mutex<Feed<T>>>, max_blocks: usize, interval: Duration) -> Self
+    where
+        T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send + 'static,
+    {
+        let (sender, receiver) = channel::unbounded();
+        let (running, stopped) = channel::bounded(1);
+        let flush_error = Arc::new(Mutex::new(None));
+        let error = flush_error.clone();
+        task::spawn(async move {
+            commit(&feed, receiver, max_blocks.max(1), interval).await;
+            if let Err(err) = feed.lock().await.storage.sync_all().await {
+                *error.lock().await = Some(err.to_string());
+            }
+            drop(running);
+        });
+        Self {
+            sender,
+            stopped,
+            flush_error,
+        }
+    }
+
+    /// Queue a block, returning its index once the batch holding it has
+    /// been committed.
+    pub async fn append(&self, data: Vec<u8>) -> Result<u64> {
+        let (done, receiver) = oneshot::channel();
+        self.sender
+            .send(Pending { data, done })
+            .await
+            .map_err(|_| anyhow!("Group commit task has stopped"))?;
+        receiver
+            .await
+            .map_err(|_| anyhow!("Group commit task has stopped"))?
+    }
+
+    /// Stop accepting blocks on every handle, and wait until the blocks
+    /// already queued have been committed, the storage has been flushed and
+    /// the task has stopped. Later appends fail. Fails if flushing the
+    /// storage did.
+    pub async fn shutdown(&self) -> Result<()> {
+        self.sender.close();
+        self.stopped.recv().await.ok();
+        match &*self.flush_error.lock().await {
+            Some(err) => Err(anyhow!("Flushing the storage failed: {}", err)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Append queued blocks in batches until every handle is dropped.
+async fn commit<T>(
+    feed: &Mutex<Feed<T>>,
+    receiver: Receiver<Pending>,
+    max_blocks: usize,
+    interval: Duration,
+) where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    while let Ok(first) = receiver.recv().await {
+        let mut batch = vec![first];
+        let deadline = Instant::now() + interval;
+        while batch.len() < max_blocks {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match future::timeout(remaining, receiver.recv()).await {
+                Ok(Ok(pending)) => batch.push(pending),
+                _ => break,
+            }
+        }
+
+        let blocks: Vec<&[u8]> = batch.iter().map(|pending| &pending.data[..]).collect();
+        let result = feed.lock().await.append_batch(&blocks).await;
+
+        for (offset, pending) in (0..).zip(batch) {
+            let result = match &result {
+                Ok(outcome) => Ok(outcome.index + offset),
+                Err(err) => Err(anyhow!("{}", err)),
+            };
+            pending.done.send(result).ok();
+        }
+    }
+}
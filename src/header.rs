@@ -0,0 +1,170 @@
+//! Feed header, stored as the first block of a feed.
+//!
+//! Compatible with the `hypercore-header` message used by hyperdrive and
+//! friends to declare what kind of content a feed holds. Headers written by
+//! `Feed::set_header()` also carry a magic field, so block 0 is only skipped
+//! as a header if it was written as one. Other decoders skip the magic as an
+//! unknown field, and headers without it decode all the same.
+
+use crate::crypto::DEFAULT_SIGNATURE_SCHEME;
+use crate::encoding::{self, Reader, BYTES};
+use anyhow::{bail, ensure, Result};
+
+/// Field number of the magic marking a block as a header.
+const MAGIC_FIELD: u64 = 15;
+/// Value of the magic field.
+const MAGIC: &[u8] = b"hypercore-header";
+
+/// A header declaring the content type of a feed, created by `.set_header()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    /// The type of content stored in the feed, e.g. `"hyperdrive"`.
+    pub content_type: String,
+    /// Application-defined metadata.
+    pub metadata: Option<Vec<u8>>,
+    /// Name of the scheme signing the feed, if not the default ed25519.
+    pub signature_scheme: Option<String>,
+}
+
+impl Header {
+    /// Create a new instance.
+    pub fn new(content_type: impl Into<String>) -> Self {
+        Self {
+            content_type: content_type.into(),
+            metadata: None,
+            signature_scheme: None,
+        }
+    }
+
+    /// Set the metadata.
+    pub fn metadata(mut self, metadata: Vec<u8>) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Set the name of the scheme signing the feed.
+    pub fn with_signature_scheme(mut self, name: impl Into<String>) -> Self {
+        self.signature_scheme = Some(name.into());
+        self
+    }
+
+    /// Get the name of the scheme signing the feed.
+    pub fn signature_scheme(&self) -> &str {
+        self.signature_scheme
+            .as_deref()
+            .unwrap_or(DEFAULT_SIGNATURE_SCHEME)
+    }
+
+    /// Access the `content_type` field from the header.
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    /// Encode the header into a block, as the JavaScript implementation
+    /// does.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.encode_fields(&mut buf);
+        buf
+    }
+
+    /// Encode the header into a block, marked with the magic field.
+    pub fn encode_tagged(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        encoding::write_bytes(&mut buf, MAGIC_FIELD, MAGIC);
+        self.encode_fields(&mut buf);
+        buf
+    }
+
+    fn encode_fields(&self, buf: &mut Vec<u8>) {
+        encoding::write_bytes(buf, 1, self.content_type.as_bytes());
+        if let Some(metadata) = &self.metadata {
+            encoding::write_bytes(buf, 2, metadata);
+        }
+        if let Some(scheme) = &self.signature_scheme {
+            encoding::write_bytes(buf, 3, scheme.as_bytes());
+        }
+    }
+
+    /// Decode a header from a block, with or without the magic field.
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        Self::decode_inner(buf).map(|(header, _)| header)
+    }
+
+    /// Decode a header from a block, failing if it lacks the magic field.
+    pub fn decode_tagged(buf: &[u8]) -> Result<Self> {
+        let (header, tagged) = Self::decode_inner(buf)?;
+        ensure!(tagged, "block is not marked as a header");
+        Ok(header)
+    }
+
+    fn decode_inner(buf: &[u8]) -> Result<(Self, bool)> {
+        let mut reader = Reader::new(buf);
+        let mut magic = None;
+        let mut content_type = None;
+        let mut metadata = None;
+        let mut signature_scheme = None;
+
+        while !reader.is_empty() {
+            match reader.key()? {
+                (MAGIC_FIELD, BYTES) => magic = Some(reader.bytes()? == MAGIC),
+                (1, BYTES) => content_type = Some(String::from_utf8(reader.bytes()?.to_vec())?),
+                (2, BYTES) => metadata = Some(reader.bytes()?.to_vec()),
+                (3, BYTES) => signature_scheme = Some(String::from_utf8(reader.bytes()?.to_vec())?),
+                (_, wire_type) => reader.skip(wire_type)?,
+            }
+        }
+
+        ensure!(magic != Some(false), "block is not a header");
+        match content_type {
+            Some(content_type) => Ok((
+                Self {
+                    content_type,
+                    metadata,
+                    signature_scheme,
+                },
+                magic.is_some(),
+            )),
+            None => bail!("header is missing its content type"),
+        }
+    }
+}
+
+#[test]
+fn should_encode_hypercore_header_schema() {
+    // `Header { required string type = 1; optional bytes metadata = 2; }`,
+    // encoded by hand from the hypercore-header schema.
+    let header = Header::new("hyperdrive").metadata(vec![1, 2, 3]);
+    let mut expected = vec![10, 10];
+    expected.extend_from_slice(b"hyperdrive");
+    expected.extend_from_slice(&[18, 3, 1, 2, 3]);
+    assert_eq!(header.encode(), expected);
+    assert_eq!(Header::decode(&expected).unwrap(), header);
+    assert!(Header::decode_tagged(&expected).is_err());
+    assert_eq!(header.signature_scheme(), "ed25519");
+}
+
+#[test]
+fn should_tag_with_magic() {
+    let header = Header::new("hyperdrive").metadata(vec![1, 2, 3]);
+    let mut expected = vec![122, 16];
+    expected.extend_from_slice(b"hypercore-header");
+    expected.extend_from_slice(&header.encode());
+    assert_eq!(header.encode_tagged(), expected);
+    assert_eq!(Header::decode(&expected).unwrap(), header);
+    assert_eq!(Header::decode_tagged(&expected).unwrap(), header);
+
+    let mut wrong = vec![122, 5];
+    wrong.extend_from_slice(b"hello");
+    wrong.extend_from_slice(&header.encode());
+    assert!(Header::decode(&wrong).is_err());
+    assert!(Header::decode(b"").is_err());
+    assert!(Header::decode_tagged(b"\n\x05hello").is_err());
+}
+
+#[test]
+fn should_record_signature_scheme() {
+    let header = Header::new("hyperdrive").with_signature_scheme("ed25519");
+    let decoded = Header::decode(&header.encode()).unwrap();
+    assert_eq!(decoded.signature_scheme.as_deref(), Some("ed25519"));
+}
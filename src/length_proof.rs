@@ -0,0 +1,127 @@
+//! Proofs of the length of a feed, for verifiers that don't replicate it.
+
+use crate::crypto::{Hash, PublicKey, Signature};
+use crate::feed::{hash_with_length_as_bytes, verify_compat, Feed};
+use crate::storage::Node;
+use anyhow::{bail, ensure, Result};
+use ed25519_dalek::SIGNATURE_LENGTH;
+use flat_tree as flat;
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
+
+/// Length of an encoded root: its byte length and hash.
+const ROOT_LEN: usize = 8 + 32;
+
+/// Proof that a feed had at least some length, with the roots of its tree
+/// at that length, signed by the feed's writer.
+///
+/// Checking it takes only the feed's public key, so audit logs and
+/// transparency logs can show that an entry was in a feed without the
+/// verifier replicating it. As feeds are append-only, the proof covers every
+/// shorter length too. Proofs are exchanged by the application, using
+/// `.encode()` and `.decode()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LengthProof {
+    /// Length of the feed the roots and signature are for.
+    pub length: u64,
+    /// Roots of the tree at `length`, in flat-tree order.
+    pub roots: Vec<Node>,
+    /// Signature of the roots and length.
+    pub signature: Signature,
+}
+
+impl LengthProof {
+    /// Get the hash of the roots, which identifies the feed's contents up to
+    /// `length`.
+    pub fn root_hash(&self) -> Vec<u8> {
+        Hash::from_roots(&self.roots).as_bytes().to_vec()
+    }
+
+    /// Check that the roots are the ones of a feed of `length` blocks, and
+    /// that the feed with `public_key` signed them.
+    pub fn verify(&self, public_key: &PublicKey) -> Result<()> {
+        let mut indexes = vec![];
+        flat::full_roots(2 * self.length, &mut indexes);
+        ensure!(
+            self.length > 0
+                && self
+                    .roots
+                    .iter()
+                    .map(|root| root.index)
+                    .eq(indexes.iter().copied()),
+            "Roots don't match a feed of length {}",
+            self.length
+        );
+        let message = hash_with_length_as_bytes(Hash::from_roots(&self.roots), self.length);
+        verify_compat(public_key, &message, Some(&self.signature))
+    }
+
+    /// Encode the proof: the length, the byte length and hash of each root,
+    /// and the signature.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(8 + ROOT_LEN * self.roots.len() + SIGNATURE_LENGTH);
+        buf.extend_from_slice(&self.length.to_be_bytes());
+        for root in &self.roots {
+            buf.extend_from_slice(&root.length.to_be_bytes());
+            buf.extend_from_slice(&root.hash);
+        }
+        buf.extend_from_slice(&self.signature.to_bytes());
+        buf
+    }
+
+    /// Decode a proof encoded by `.encode()`. The root indexes follow from
+    /// the length.
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        ensure!(buf.len() >= 8, "Length proof is truncated");
+        let mut length = [0; 8];
+        length.copy_from_slice(&buf[..8]);
+        let length = u64::from_be_bytes(length);
+        let mut indexes = vec![];
+        flat::full_roots(2 * length, &mut indexes);
+        let expected = 8 + ROOT_LEN * indexes.len() + SIGNATURE_LENGTH;
+        if buf.len() != expected {
+            bail!("Length proof must be {} bytes", expected);
+        }
+        let roots = indexes
+            .into_iter()
+            .zip(buf[8..].chunks_exact(ROOT_LEN))
+            .map(|(index, root)| {
+                let mut len = [0; 8];
+                len.copy_from_slice(&root[..8]);
+                Node::new(index, root[8..].to_vec(), u64::from_be_bytes(len))
+            })
+            .collect();
+        Ok(Self {
+            length,
+            roots,
+            signature: Signature::from_bytes(&buf[expected - SIGNATURE_LENGTH..])?,
+        })
+    }
+}
+
+impl<T> Feed<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    /// Prove that the feed has at least `min_length` blocks, with the first
+    /// stored signature at or after that length. Blocks appended in a batch
+    /// share one signature, so the proven length may be longer.
+    pub async fn length_proof(&mut self, min_length: u64) -> Result<LengthProof> {
+        ensure!(
+            min_length > 0 && min_length <= self.len(),
+            "Cannot prove a length of {} for a feed of {} blocks",
+            min_length,
+            self.len()
+        );
+        for index in min_length - 1..self.len() {
+            if let Ok(signature) = self.storage.get_signature(index).await {
+                return Ok(LengthProof {
+                    length: index + 1,
+                    roots: self.root_hashes(index).await?,
+                    signature,
+                });
+            }
+        }
+        bail!("No signature covers length {}", min_length)
+    }
+}
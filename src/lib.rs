@@ -33,22 +33,84 @@
 pub mod bitfield;
 pub mod prelude;
 
+mod access_log;
+mod append;
+mod archive;
 mod audit;
+mod backup;
+mod block;
+mod compat;
 mod crypto;
+mod download;
+mod encoding;
 mod event;
 mod feed;
 mod feed_builder;
+#[cfg(feature = "gateway")]
+mod gateway;
+mod group_commit;
+mod header;
+mod length_proof;
+mod pack;
+mod prefetch;
 mod proof;
+mod proof_cache;
+mod ranges;
 mod replicate;
+mod retention;
+mod scrub;
+mod space;
 mod storage;
+mod streamed;
+mod tail;
+pub mod telemetry;
+mod tentative;
+pub mod tree;
+mod tree_dump;
+mod uri;
+mod v10;
+mod watch;
+mod witness;
 
-pub use crate::crypto::{generate_keypair, sign, verify, Signature};
+pub use crate::access_log::{BlockAccess, Tier};
+pub use crate::append::AppendOutcome;
+pub use crate::archive::{ArchiveReport, BlockReport, BlockStatus};
+pub use crate::backup::BackupGuard;
+pub use crate::block::Block;
+pub use crate::compat::{CompatReport, Deviation};
+pub use crate::crypto::{
+    decode_key, decode_public_key, discovery_key, encode_key, generate_keypair, keypair_from_seed,
+    sign, signature_scheme, verify, Ed25519, KeyChain, KeyFormat, KeyMismatch, Signature,
+    SignatureScheme, DEFAULT_SIGNATURE_SCHEME, KEY_LENGTH,
+};
+pub use crate::download::{DownloadStats, Progress, Source};
 pub use crate::event::Event;
 pub use crate::feed::Feed;
 pub use crate::feed_builder::FeedBuilder;
-pub use crate::proof::Proof;
-pub use crate::replicate::Peer;
-pub use crate::storage::{Node, NodeTrait, Storage, Store};
+#[cfg(feature = "gateway")]
+pub use crate::gateway::{Gateway, State as GatewayState};
+pub use crate::group_commit::GroupCommit;
+pub use crate::header::Header;
+pub use crate::length_proof::LengthProof;
+pub use crate::pack::Segment;
+pub use crate::prefetch::PrefetchPolicy;
+pub use crate::proof::{Proof, ProofSize};
+pub use crate::proof_cache::ProofCacheStats;
+pub use crate::ranges::Ranges;
+pub use crate::replicate::{Peer, Request};
+pub use crate::retention::Retention;
+pub use crate::scrub::ScrubFinding;
+pub use crate::space::OutOfSpace;
+#[cfg(target_os = "linux")]
+pub use crate::storage::DirectDisk;
+pub use crate::storage::{
+    atomic_write, is_retryable, Batch, Node, NodeTrait, QuarantinedBlock, RetryPolicy,
+    RetryingStorage, Storage, Store, FORMAT_VERSION,
+};
+pub use crate::uri::FeedUri;
+pub use crate::v10::{export_v10, import_v10};
+pub use crate::watch::Watch;
+pub use crate::witness::Witness;
 pub use ed25519_dalek::{PublicKey, SecretKey};
 
 use std::path::Path;
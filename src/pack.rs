@@ -0,0 +1,138 @@
+//! Small records packed into segments, each appended as one block.
+
+use crate::encoding::{self, Reader};
+use anyhow::{ensure, Result};
+use std::ops::Range;
+
+/// A block holding many small records, appended with
+/// `Feed::append_packed()`.
+///
+/// Each record appended on its own costs a tree node, and its data lands
+/// wherever the next block goes. Packed records share one leaf, and are
+/// read back with one read of the data store. A segment starts with an
+/// index of its records, as varints: the record count, then the length of
+/// each record. The records follow, in order.
+///
+/// ```rust
+/// use hypercore::Segment;
+///
+/// let buf = Segment::encode(&[&b"hi"[..], b"ola"]);
+/// let segment = Segment::decode(&buf).unwrap();
+/// assert_eq!(segment.len(), 2);
+/// assert_eq!(segment.get(1), Some(&b"ola"[..]));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment<'a> {
+    data: &'a [u8],
+    records: Vec<Range<usize>>,
+}
+
+impl<'a> Segment<'a> {
+    /// Encode records as a segment.
+    pub fn encode<B: AsRef<[u8]>>(records: &[B]) -> Vec<u8> {
+        let mut buf = vec![];
+        encoding::write_varint(&mut buf, records.len() as u64);
+        for record in records {
+            encoding::write_varint(&mut buf, record.as_ref().len() as u64);
+        }
+        for record in records {
+            buf.extend_from_slice(record.as_ref());
+        }
+        buf
+    }
+
+    /// Decode a segment, checking its index against its length.
+    pub fn decode(buf: &'a [u8]) -> Result<Self> {
+        let mut reader = Reader::new(buf);
+        let count = reader.varint()?;
+        let mut lengths = vec![];
+        for _ in 0..count {
+            lengths.push(reader.varint()? as usize);
+        }
+        let mut start = reader.offset();
+        let mut records = Vec::with_capacity(lengths.len());
+        for len in lengths {
+            ensure!(
+                len <= buf.len() - start,
+                "Segment record overflows the segment"
+            );
+            records.push(start..start + len);
+            start += len;
+        }
+        ensure!(start == buf.len(), "Trailing bytes after segment records");
+        Ok(Self { data: buf, records })
+    }
+
+    /// Get the number of records.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Check whether the segment holds no records.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Get the record at `index` within the segment.
+    pub fn get(&self, index: usize) -> Option<&'a [u8]> {
+        let data = self.data;
+        self.records.get(index).map(|range| &data[range.clone()])
+    }
+
+    /// Iterate over the records, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        let data = self.data;
+        self.records.iter().map(move |range| &data[range.clone()])
+    }
+}
+
+/// Split records into the segments `Feed::append_packed()` appends: as many
+/// records as fit in `segment_size` bytes, index included, go in each
+/// segment. A record too big to share a segment gets one of its own.
+pub(crate) fn pack<B: AsRef<[u8]>>(records: &[B], segment_size: usize) -> Vec<Vec<u8>> {
+    let mut segments = vec![];
+    let mut start = 0;
+    // Size of the records in the current segment, with their lengths.
+    let mut size = 0;
+    for (i, record) in records.iter().enumerate() {
+        let len = record.as_ref().len();
+        let record_size = varint_len(len as u64) + len;
+        let count = (i - start + 1) as u64;
+        if i > start && varint_len(count) + size + record_size > segment_size {
+            segments.push(Segment::encode(&records[start..i]));
+            start = i;
+            size = 0;
+        }
+        size += record_size;
+    }
+    if start < records.len() {
+        segments.push(Segment::encode(&records[start..]));
+    }
+    segments
+}
+
+fn varint_len(value: u64) -> usize {
+    let bits = 64 - value.leading_zeros() as usize;
+    bits.max(1).div_ceil(7)
+}
+
+#[test]
+fn should_pack_records() {
+    let records = vec![vec![1; 10], vec![2; 10], vec![3; 30], vec![4; 5]];
+    let segments = pack(&records, 24);
+    assert_eq!(segments.len(), 3);
+    assert!(segments[0].len() <= 24 && segments[2].len() <= 24);
+    let unpacked: Vec<Vec<u8>> = segments
+        .iter()
+        .flat_map(|buf| {
+            let segment = Segment::decode(buf).unwrap();
+            segment.iter().map(<[u8]>::to_vec).collect::<Vec<_>>()
+        })
+        .collect();
+    assert_eq!(unpacked, records);
+
+    assert!(pack(&Vec::<Vec<u8>>::new(), 24).is_empty());
+    assert!(Segment::decode(&segments[0][..20]).is_err());
+    assert_eq!(varint_len(127), 1);
+    assert_eq!(varint_len(128), 2);
+}
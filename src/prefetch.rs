@@ -0,0 +1,149 @@
+//! Reading blocks ahead of `Feed::get()`, predicted from the blocks read
+//! before.
+
+use crate::feed::Feed;
+use anyhow::Result;
+use random_access_storage::RandomAccess;
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+/// How `Feed::get()` predicts the blocks read next, see
+/// `Feed::set_prefetch()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefetchPolicy {
+    /// Don't read ahead.
+    None,
+    /// Once two consecutive blocks are read, read the next `n` blocks
+    /// stored locally, with one read of the data store if blocks are packed
+    /// back to back.
+    Sequential(u64),
+    /// Once three blocks the same distance apart are read, read the next
+    /// `n` blocks at that distance.
+    Stride(u64),
+}
+
+impl Default for PrefetchPolicy {
+    fn default() -> Self {
+        PrefetchPolicy::Sequential(16)
+    }
+}
+
+/// Blocks read ahead, and the reads they were predicted from.
+#[derive(Debug)]
+pub(crate) struct Prefetcher {
+    policy: PrefetchPolicy,
+    last: Option<u64>,
+    stride: Option<u64>,
+    cache: HashMap<u64, Vec<u8>>,
+}
+
+impl Prefetcher {
+    pub(crate) fn new(policy: PrefetchPolicy) -> Self {
+        Self {
+            policy,
+            last: None,
+            stride: None,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Record a read of `index`, and get the distance and number of blocks
+    /// to read ahead, if a pattern was found and the blocks it predicts are
+    /// not cached yet. The cache is emptied for the new blocks.
+    fn predict(&mut self, index: u64) -> Option<(u64, u64)> {
+        let stride = self
+            .last
+            .replace(index)
+            .and_then(|last| index.checked_sub(last))
+            .filter(|stride| *stride > 0);
+        let (stride, count) = match self.policy {
+            PrefetchPolicy::None => return None,
+            PrefetchPolicy::Sequential(count) => (stride.filter(|stride| *stride == 1), count),
+            PrefetchPolicy::Stride(count) => {
+                let previous = std::mem::replace(&mut self.stride, stride);
+                (stride.filter(|stride| Some(*stride) == previous), count)
+            }
+        };
+        let stride = stride?;
+        if count == 0 || self.cache.contains_key(&index.saturating_add(stride)) {
+            return None;
+        }
+        self.cache.clear();
+        Some((stride, count))
+    }
+
+    /// Drop the cached data of the block at `index`, which was rewritten.
+    pub(crate) fn forget(&mut self, index: u64) {
+        self.cache.remove(&index);
+    }
+}
+
+impl<T> Feed<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    /// Read blocks ahead of `.get()` as predicted by `policy`, keeping them
+    /// in memory until they are read. Prefetching is off by default; the
+    /// default policy reads the next 16 blocks of sequential reads.
+    pub fn set_prefetch(&mut self, policy: PrefetchPolicy) {
+        self.prefetch = match policy {
+            PrefetchPolicy::None => None,
+            policy => Some(Prefetcher::new(policy)),
+        };
+    }
+
+    /// Read the stored data of the block at `index`, from the blocks read
+    /// ahead if it was, and read ahead of it if the reads so far predict it.
+    pub(crate) async fn read_block(&mut self, index: u64) -> Result<Vec<u8>> {
+        let prefetch = match &mut self.prefetch {
+            Some(prefetch) => prefetch,
+            None => return self.storage.get_data(index).await,
+        };
+        let cached = prefetch.cache.remove(&index);
+        let predicted = prefetch.predict(index);
+        let data = match cached {
+            Some(data) => data,
+            None => self.storage.get_data(index).await?,
+        };
+        if let Some((stride, count)) = predicted {
+            let bitfield = &mut self.bitfield;
+            let length = self.length;
+            let indexes: Vec<u64> = (1..=count)
+                .map(|i| index.saturating_add(i.saturating_mul(stride)))
+                .take_while(|next| *next < length && bitfield.get(*next))
+                .collect();
+            let blocks = match (stride, indexes.first(), indexes.last()) {
+                (1, Some(first), Some(last)) => {
+                    self.storage.get_data_range(*first..*last + 1).await?
+                }
+                _ => {
+                    let mut blocks = Vec::with_capacity(indexes.len());
+                    for next in &indexes {
+                        blocks.push(self.storage.get_data(*next).await?);
+                    }
+                    blocks
+                }
+            };
+            if let Some(prefetch) = &mut self.prefetch {
+                prefetch.cache.extend(indexes.into_iter().zip(blocks));
+            }
+        }
+        Ok(data)
+    }
+}
+
+#[test]
+fn should_predict_reads() {
+    let mut prefetcher = Prefetcher::new(PrefetchPolicy::Sequential(4));
+    assert_eq!(prefetcher.predict(3), None);
+    assert_eq!(prefetcher.predict(4), Some((1, 4)));
+    prefetcher.cache.insert(6, vec![]);
+    assert_eq!(prefetcher.predict(5), None);
+    assert_eq!(prefetcher.predict(9), None);
+
+    let mut prefetcher = Prefetcher::new(PrefetchPolicy::Stride(2));
+    assert_eq!(prefetcher.predict(0), None);
+    assert_eq!(prefetcher.predict(3), None);
+    assert_eq!(prefetcher.predict(6), Some((3, 2)));
+    assert_eq!(prefetcher.predict(7), None);
+}
@@ -1,5 +1,10 @@
+//! Merkle proofs, encodable as the `Data` message of the replication
+//! protocol used by the JavaScript implementation.
+
+use crate::encoding::{self, Reader, BYTES, VARINT};
 use crate::Node;
 use crate::Signature;
+use anyhow::{bail, Result};
 
 /// A merkle proof for an index, created by the `.proof()` method.
 #[derive(Debug, PartialEq, Clone)]
@@ -27,4 +32,133 @@ impl Proof {
     pub fn signature(&self) -> Option<&Signature> {
         self.signature.as_ref()
     }
+
+    /// Encode the proof, along with the block it proves, as a `Data`
+    /// message.
+    pub fn encode(&self, value: Option<&[u8]>) -> Vec<u8> {
+        let mut buf = vec![];
+        encoding::write_key(&mut buf, 1, VARINT);
+        encoding::write_varint(&mut buf, self.index);
+        if let Some(value) = value {
+            encoding::write_bytes(&mut buf, 2, value);
+        }
+        for node in &self.nodes {
+            let mut node_buf = vec![];
+            encoding::write_key(&mut node_buf, 1, VARINT);
+            encoding::write_varint(&mut node_buf, node.index);
+            encoding::write_bytes(&mut node_buf, 2, &node.hash);
+            encoding::write_key(&mut node_buf, 3, VARINT);
+            encoding::write_varint(&mut node_buf, node.length);
+            encoding::write_bytes(&mut buf, 3, &node_buf);
+        }
+        if let Some(signature) = &self.signature {
+            encoding::write_bytes(&mut buf, 4, &signature.to_bytes());
+        }
+        buf
+    }
+
+    /// Decode a `Data` message into the proof and the block it proves.
+    pub fn decode(buf: &[u8]) -> Result<(Self, Option<Vec<u8>>)> {
+        let mut reader = Reader::new(buf);
+        let mut index = None;
+        let mut value = None;
+        let mut nodes = vec![];
+        let mut signature = None;
+
+        while !reader.is_empty() {
+            match reader.key()? {
+                (1, VARINT) => index = Some(reader.varint()?),
+                (2, BYTES) => value = Some(reader.bytes()?.to_vec()),
+                (3, BYTES) => nodes.push(decode_node(reader.bytes()?)?),
+                (4, BYTES) => signature = Some(Signature::from_bytes(reader.bytes()?)?),
+                (_, wire_type) => reader.skip(wire_type)?,
+            }
+        }
+
+        let index = match index {
+            Some(index) => index,
+            None => bail!("data message is missing its index"),
+        };
+        let proof = Self {
+            index,
+            nodes,
+            signature,
+        };
+        Ok((proof, value))
+    }
+}
+
+/// The size of a proof, computed by `.proof_size()` without reading any
+/// nodes from storage.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ProofSize {
+    /// The number of nodes in the proof.
+    pub nodes: usize,
+    /// Whether the proof carries a signature.
+    pub signed: bool,
+}
+
+impl ProofSize {
+    /// Upper bound of the bytes the proof adds to an encoded `Data`
+    /// message, assuming 32 byte hashes.
+    pub fn bytes(&self) -> u64 {
+        // Nested message header, index, hash and size, with varints taking
+        // up to 10 bytes each.
+        let node = 2 + 11 + 34 + 11;
+        let signature = if self.signed { 66 } else { 0 };
+        self.nodes as u64 * node + signature
+    }
+}
+
+/// Decode a node nested in a `Data` message.
+fn decode_node(buf: &[u8]) -> Result<Node> {
+    let mut reader = Reader::new(buf);
+    let mut index = None;
+    let mut hash = None;
+    let mut length = None;
+
+    while !reader.is_empty() {
+        match reader.key()? {
+            (1, VARINT) => index = Some(reader.varint()?),
+            (2, BYTES) => hash = Some(reader.bytes()?.to_vec()),
+            (3, VARINT) => length = Some(reader.varint()?),
+            (_, wire_type) => reader.skip(wire_type)?,
+        }
+    }
+
+    match (index, hash, length) {
+        (Some(index), Some(hash), Some(length)) => Ok(Node::new(index, hash, length)),
+        _ => bail!("data message node is missing a required field"),
+    }
+}
+
+#[test]
+fn should_encode_data_message_schema() {
+    let proof = Proof {
+        index: 1,
+        nodes: vec![Node::new(0, vec![0xaa; 32], 2)],
+        signature: Some(Signature::from_bytes(&[0x05; 64]).unwrap()),
+    };
+
+    // Encoded by hand from the `Data` message schema of the replication
+    // protocol, not captured from a JavaScript peer.
+    let mut expected = vec![8, 1, 18, 3];
+    expected.extend_from_slice(b"ola");
+    expected.extend_from_slice(&[26, 38, 8, 0, 18, 32]);
+    expected.extend_from_slice(&[0xaa; 32]);
+    expected.extend_from_slice(&[24, 2, 34, 64]);
+    expected.extend_from_slice(&[0x05; 64]);
+
+    assert_eq!(proof.encode(Some(b"ola")), expected);
+    let (decoded, value) = Proof::decode(&expected).unwrap();
+    assert_eq!(decoded, proof);
+    assert_eq!(value, Some(b"ola".to_vec()));
+
+    let proof = Proof {
+        index: 300,
+        nodes: vec![],
+        signature: None,
+    };
+    assert_eq!(proof.encode(None), vec![8, 172, 2]);
+    assert_eq!(Proof::decode(&[8, 172, 2]).unwrap(), (proof, None));
 }
@@ -0,0 +1,100 @@
+//! Bounded cache of the proofs served for popular blocks.
+
+use crate::proof::Proof;
+use std::collections::{HashMap, VecDeque};
+
+/// Proofs are cached per block, digest of the remote tree, and whether the
+/// hash of the block itself was included.
+type Key = (u64, u64, bool);
+
+/// Cache of generated proofs, evicting the oldest entry once full.
+///
+/// A proof depends on the roots and signature of the feed, so the cache is
+/// emptied whenever the tree changes.
+#[derive(Debug)]
+pub(crate) struct ProofCache {
+    capacity: usize,
+    proofs: HashMap<Key, Proof>,
+    order: VecDeque<Key>,
+    hits: u64,
+    misses: u64,
+}
+
+/// Hit rate statistics of a proof cache, returned by
+/// `Feed::proof_cache_stats()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProofCacheStats {
+    /// Number of proofs served from the cache.
+    pub hits: u64,
+    /// Number of proofs generated because they were not cached.
+    pub misses: u64,
+    /// Number of proofs currently cached.
+    pub entries: usize,
+}
+
+impl ProofCacheStats {
+    /// Get the share of proofs served from the cache, between 0 and 1.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+impl ProofCache {
+    /// Create a cache holding at most `capacity` proofs.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            proofs: HashMap::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Look up a proof, counting the hit or miss.
+    pub(crate) fn get(&mut self, index: u64, digest: u64, include_hash: bool) -> Option<Proof> {
+        match self.proofs.get(&(index, digest, include_hash)) {
+            Some(proof) => {
+                self.hits += 1;
+                Some(proof.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Cache a proof, evicting the oldest one if the cache is full.
+    pub(crate) fn insert(&mut self, digest: u64, include_hash: bool, proof: Proof) {
+        let key = (proof.index, digest, include_hash);
+        if self.capacity == 0 || self.proofs.contains_key(&key) {
+            return;
+        }
+        if self.proofs.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.proofs.remove(&oldest);
+            }
+        }
+        self.order.push_back(key);
+        self.proofs.insert(key, proof);
+    }
+
+    /// Drop every cached proof, keeping the statistics.
+    pub(crate) fn invalidate(&mut self) {
+        self.proofs.clear();
+        self.order.clear();
+    }
+
+    /// Get the hit rate statistics.
+    pub(crate) fn stats(&self) -> ProofCacheStats {
+        ProofCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.proofs.len(),
+        }
+    }
+}
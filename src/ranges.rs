@@ -0,0 +1,289 @@
+//! Sets of block indexes, kept as sorted, disjoint ranges.
+
+use crate::encoding::{self, Reader};
+use anyhow::{anyhow, bail, ensure, Result};
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// A set of blocks, as a union of ranges.
+///
+/// The ranges are kept sorted, with overlapping and touching ranges merged,
+/// so two sets holding the same blocks are equal. A single `Range<u64>`
+/// converts into a set, so `feed.clear(0..10)` works as well as
+/// `feed.clear(ranges)`.
+///
+/// Sets parse from and display as comma separated ranges, with exclusive
+/// ends:
+///
+/// ```rust
+/// use hypercore::Ranges;
+///
+/// let mut ranges: Ranges = "0..10,20..30".parse().unwrap();
+/// ranges.subtract(5..25);
+/// assert_eq!(ranges.to_string(), "0..5,25..30");
+/// assert_eq!(Ranges::from(0..40).difference(&ranges).to_string(), "5..25,30..40");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Ranges {
+    ranges: Vec<Range<u64>>,
+}
+
+impl Ranges {
+    /// Create an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the blocks in `range`, merging it with the ranges it overlaps or
+    /// touches.
+    pub fn add(&mut self, range: Range<u64>) {
+        if range.start >= range.end {
+            return;
+        }
+        let first = self.ranges.partition_point(|r| r.end < range.start);
+        let last = self.ranges.partition_point(|r| r.start <= range.end);
+        let mut merged = range;
+        if first < last {
+            merged.start = merged.start.min(self.ranges[first].start);
+            merged.end = merged.end.max(self.ranges[last - 1].end);
+        }
+        self.ranges.splice(first..last, std::iter::once(merged));
+    }
+
+    /// Remove the blocks in `range`, splitting the ranges it covers part of.
+    pub fn subtract(&mut self, range: Range<u64>) {
+        if range.start >= range.end {
+            return;
+        }
+        let mut kept = Vec::with_capacity(self.ranges.len() + 1);
+        for r in self.ranges.drain(..) {
+            if r.start < range.start {
+                kept.push(r.start..r.end.min(range.start));
+            }
+            if r.end > range.end {
+                kept.push(r.start.max(range.end)..r.end);
+            }
+        }
+        self.ranges = kept;
+    }
+
+    /// Get the blocks in both sets.
+    pub fn intersect(&self, other: &Ranges) -> Ranges {
+        let mut ranges = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            let start = a.start.max(b.start);
+            let end = a.end.min(b.end);
+            if start < end {
+                ranges.push(start..end);
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        Ranges { ranges }
+    }
+
+    /// Get the blocks in either set.
+    pub fn union(&self, other: &Ranges) -> Ranges {
+        let mut union = self.clone();
+        for range in &other.ranges {
+            union.add(range.clone());
+        }
+        union
+    }
+
+    /// Get the blocks in this set but not in `other`.
+    pub fn difference(&self, other: &Ranges) -> Ranges {
+        let mut difference = self.clone();
+        for range in &other.ranges {
+            difference.subtract(range.clone());
+        }
+        difference
+    }
+
+    /// Check whether the set contains `index`.
+    pub fn contains(&self, index: u64) -> bool {
+        let i = self.ranges.partition_point(|r| r.end <= index);
+        i < self.ranges.len() && self.ranges[i].start <= index
+    }
+
+    /// Check whether the set holds no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Count the blocks in the set.
+    pub fn count(&self) -> u64 {
+        self.ranges.iter().map(|r| r.end - r.start).sum()
+    }
+
+    /// Get the ranges, sorted and merged.
+    pub fn as_slice(&self) -> &[Range<u64>] {
+        &self.ranges
+    }
+
+    /// Iterate over the ranges, sorted and merged.
+    pub fn ranges(&self) -> impl Iterator<Item = Range<u64>> + '_ {
+        self.ranges.iter().cloned()
+    }
+
+    /// Iterate over every block in the set, in order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.ranges.iter().flat_map(|r| r.clone())
+    }
+
+    /// Encode the set as varints: the number of ranges, then the gap before
+    /// and length of each range.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        encoding::write_varint(&mut buf, self.ranges.len() as u64);
+        let mut end = 0;
+        for range in &self.ranges {
+            encoding::write_varint(&mut buf, range.start - end);
+            encoding::write_varint(&mut buf, range.end - range.start);
+            end = range.end;
+        }
+        buf
+    }
+
+    /// Decode a set encoded by `.encode()`.
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buf);
+        let count = reader.varint()?;
+        let mut ranges = Ranges::new();
+        let mut end: u64 = 0;
+        for _ in 0..count {
+            let start = end
+                .checked_add(reader.varint()?)
+                .ok_or_else(|| anyhow!("Range start overflows"))?;
+            end = start
+                .checked_add(reader.varint()?)
+                .ok_or_else(|| anyhow!("Range end overflows"))?;
+            ranges.add(start..end);
+        }
+        ensure!(reader.is_empty(), "Trailing bytes after ranges");
+        Ok(ranges)
+    }
+}
+
+impl From<Range<u64>> for Ranges {
+    fn from(range: Range<u64>) -> Self {
+        let mut ranges = Ranges::new();
+        ranges.add(range);
+        ranges
+    }
+}
+
+impl From<Vec<Range<u64>>> for Ranges {
+    fn from(list: Vec<Range<u64>>) -> Self {
+        list.into_iter().collect()
+    }
+}
+
+impl FromIterator<Range<u64>> for Ranges {
+    fn from_iter<I: IntoIterator<Item = Range<u64>>>(iter: I) -> Self {
+        let mut ranges = Ranges::new();
+        for range in iter {
+            ranges.add(range);
+        }
+        ranges
+    }
+}
+
+impl fmt::Display for Ranges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, range) in self.ranges.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}..{}", range.start, range.end)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Ranges {
+    type Err = anyhow::Error;
+
+    /// Parse comma separated ranges like `0..10,20..30`. A single index
+    /// like `5` is the range `5..6`.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut ranges = Ranges::new();
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let range = match part.split_once("..") {
+                Some((start, end)) => start.trim().parse()?..end.trim().parse()?,
+                None => {
+                    let index: u64 = part.parse()?;
+                    match index.checked_add(1) {
+                        Some(end) => index..end,
+                        None => bail!("Index {} is too large", part),
+                    }
+                }
+            };
+            ensure!(range.start <= range.end, "Invalid range {}", part);
+            ranges.add(range);
+        }
+        Ok(ranges)
+    }
+}
+
+#[test]
+fn should_add_and_subtract_ranges() {
+    let mut ranges = Ranges::new();
+    ranges.add(10..20);
+    ranges.add(0..5);
+    ranges.add(30..40);
+    assert_eq!(ranges.as_slice(), &[0..5, 10..20, 30..40][..]);
+    ranges.add(5..12);
+    assert_eq!(ranges.as_slice(), &[0..20, 30..40][..]);
+    ranges.add(15..35);
+    assert_eq!(ranges, Ranges::from(0..40));
+
+    ranges.subtract(10..20);
+    assert_eq!(ranges.as_slice(), &[0..10, 20..40][..]);
+    ranges.subtract(5..25);
+    assert_eq!(ranges.as_slice(), &[0..5, 25..40][..]);
+    assert!(ranges.contains(4));
+    assert!(!ranges.contains(5));
+    assert!(ranges.contains(25));
+    assert!(!ranges.contains(40));
+    assert_eq!(ranges.count(), 20);
+    assert_eq!(
+        ranges.iter().take(6).collect::<Vec<_>>(),
+        vec![0, 1, 2, 3, 4, 25]
+    );
+}
+
+#[test]
+fn should_combine_sets() {
+    let a = Ranges::from(vec![0..10, 20..30]);
+    let b = Ranges::from(vec![5..25, 28..40]);
+    assert_eq!(a.intersect(&b), Ranges::from(vec![5..10, 20..25, 28..30]));
+    assert_eq!(a.union(&b), Ranges::from(0..40));
+    assert_eq!(a.difference(&b), Ranges::from(vec![0..5, 25..28]));
+    assert!(a.intersect(&Ranges::new()).is_empty());
+}
+
+#[test]
+fn should_encode_and_parse_sets() {
+    let ranges = Ranges::from(vec![3..10, 200..300, 1000..1001]);
+    assert_eq!(Ranges::decode(&ranges.encode()).unwrap(), ranges);
+    assert!(Ranges::decode(&ranges.encode()[..3]).is_err());
+
+    assert_eq!(ranges.to_string(), "3..10,200..300,1000..1001");
+    assert_eq!("3..10, 200..300, 1000".parse::<Ranges>().unwrap(), ranges);
+    assert_eq!("".parse::<Ranges>().unwrap(), Ranges::new());
+    assert!("5..3".parse::<Ranges>().is_err());
+    assert!("a..b".parse::<Ranges>().is_err());
+    assert!("18446744073709551615".parse::<Ranges>().is_err());
+    assert_eq!(
+        "18446744073709551614".parse::<Ranges>().unwrap(),
+        Ranges::from(u64::MAX - 1..u64::MAX)
+    );
+}
@@ -1,5 +1,7 @@
 mod message;
 mod peer;
+mod request;
 
 pub use self::message::Message;
 pub use self::peer::Peer;
+pub use self::request::Request;
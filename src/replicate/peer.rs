@@ -11,15 +11,15 @@ use super::Message;
 #[derive(Debug, Clone, PartialEq)]
 pub struct Peer {
     // remote_id: u64,
-// remote_length: u64,
-// remote_bitfield: Bitfield,
-// remote_is_want: bool,
-// remote_is_downloading: bool,
-// is_live: bool,
-// is_sparse: bool,
-// is_downloading: bool,
-// is_uploading: bool,
-// max_requests: u16,
+    // remote_length: u64,
+    // remote_bitfield: Bitfield,
+    // remote_is_want: bool,
+    // remote_is_downloading: bool,
+    // is_live: bool,
+    // is_sparse: bool,
+    // is_downloading: bool,
+    // is_uploading: bool,
+    // max_requests: u16,
 }
 
 impl Peer {
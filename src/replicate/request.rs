@@ -0,0 +1,78 @@
+use crate::encoding::{self, Reader, VARINT};
+use anyhow::{bail, Result};
+
+/// A request for a block, encodable as the `Request` message of the
+/// replication protocol used by the JavaScript implementation.
+///
+/// `nodes` is the digest from `.digest()`, telling the remote which
+/// ancestor hashes the requester already has, so they can be left out of
+/// the proof.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Request {
+    /// The index of the requested block.
+    pub index: u64,
+    /// A byte offset to request the block holding instead, if set.
+    pub bytes: Option<u64>,
+    /// Whether only the hash of the block is wanted.
+    pub hash: bool,
+    /// The digest of the nodes the requester already has.
+    pub nodes: u64,
+}
+
+impl Request {
+    /// Encode the request as a `Request` message.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        encoding::write_key(&mut buf, 1, VARINT);
+        encoding::write_varint(&mut buf, self.index);
+        if let Some(bytes) = self.bytes {
+            encoding::write_key(&mut buf, 2, VARINT);
+            encoding::write_varint(&mut buf, bytes);
+        }
+        if self.hash {
+            encoding::write_key(&mut buf, 3, VARINT);
+            encoding::write_varint(&mut buf, 1);
+        }
+        if self.nodes != 0 {
+            encoding::write_key(&mut buf, 4, VARINT);
+            encoding::write_varint(&mut buf, self.nodes);
+        }
+        buf
+    }
+
+    /// Decode a `Request` message.
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(buf);
+        let mut index = None;
+        let mut request = Self::default();
+
+        while !reader.is_empty() {
+            match reader.key()? {
+                (1, VARINT) => index = Some(reader.varint()?),
+                (2, VARINT) => request.bytes = Some(reader.varint()?),
+                (3, VARINT) => request.hash = reader.varint()? != 0,
+                (4, VARINT) => request.nodes = reader.varint()?,
+                (_, wire_type) => reader.skip(wire_type)?,
+            }
+        }
+
+        match index {
+            Some(index) => Ok(Self { index, ..request }),
+            None => bail!("request message is missing its index"),
+        }
+    }
+}
+
+#[test]
+fn should_encode_like_js() {
+    let request = Request {
+        index: 300,
+        bytes: None,
+        hash: true,
+        nodes: 5,
+    };
+    let expected = vec![8, 172, 2, 24, 1, 32, 5];
+    assert_eq!(request.encode(), expected);
+    assert_eq!(Request::decode(&expected).unwrap(), request);
+    assert_eq!(Request::decode(&[8, 0]).unwrap(), Request::default());
+}
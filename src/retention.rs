@@ -0,0 +1,76 @@
+//! Retention policies deciding which blocks `Feed::gc()` clears.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A policy selecting which locally stored blocks to keep.
+///
+/// Each policy is applied on its own, from the newest block stored locally
+/// to the oldest, and a block is kept only if every policy keeps it. Cleared
+/// blocks keep their tree nodes and signatures, so the feed can still be
+/// verified and the blocks downloaded again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retention {
+    /// Keep the newest `n` blocks.
+    Blocks(u64),
+    /// Keep the newest blocks, up to a total of `n` bytes.
+    Bytes(u64),
+    /// Keep blocks stored less than this long ago. When blocks are stored is
+    /// only recorded once an age policy was set on the feed, and blocks
+    /// stored before, or by versions that did not record it, are kept.
+    Age(Duration),
+}
+
+impl Retention {
+    /// Check whether the policy keeps a block of `len` bytes, with `blocks`
+    /// blocks and `bytes` bytes stored after it.
+    pub(crate) fn keeps(
+        &self,
+        blocks: u64,
+        bytes: u64,
+        len: u64,
+        timestamp: Option<u64>,
+        now: u64,
+    ) -> bool {
+        match *self {
+            Retention::Blocks(max) => blocks < max,
+            Retention::Bytes(max) => bytes + len <= max,
+            Retention::Age(age) => match timestamp {
+                Some(timestamp) => now.saturating_sub(timestamp) < age.as_secs(),
+                None => true,
+            },
+        }
+    }
+}
+
+/// Check whether any of `policies` needs to know when blocks were stored.
+pub(crate) fn has_age(policies: &[Retention]) -> bool {
+    policies
+        .iter()
+        .any(|policy| matches!(policy, Retention::Age(_)))
+}
+
+/// Get the current time in seconds since the Unix epoch.
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
+
+/// Check whether a block with `expiry` has expired at `now`.
+pub(crate) fn expired(expiry: Option<u64>, now: u64) -> bool {
+    matches!(expiry, Some(expiry) if expiry <= now)
+}
+
+#[test]
+fn should_apply_policies() {
+    assert!(Retention::Blocks(2).keeps(1, 0, 10, None, 0));
+    assert!(!Retention::Blocks(2).keeps(2, 0, 10, None, 0));
+    assert!(Retention::Bytes(20).keeps(1, 10, 10, None, 0));
+    assert!(!Retention::Bytes(20).keeps(1, 15, 10, None, 0));
+
+    let age = Retention::Age(Duration::from_secs(60));
+    assert!(age.keeps(0, 0, 10, Some(1000), 1059));
+    assert!(!age.keeps(0, 0, 10, Some(1000), 1060));
+    assert!(age.keeps(0, 0, 10, None, 1060));
+}
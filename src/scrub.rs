@@ -0,0 +1,130 @@
+//! Re-verifying stored blocks slowly, in the background.
+
+use crate::crypto::Hash;
+use crate::download::Source;
+use crate::feed::Feed;
+use crate::proof::Proof;
+use anyhow::{ensure, Result};
+use async_std::sync::Mutex;
+use async_std::task;
+use futures::stream::{self, Stream};
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+/// A corrupt block found by `Feed::scrub()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScrubFinding {
+    /// Index of the block.
+    pub index: u64,
+    /// Whether the block was downloaded again and verified.
+    pub repaired: bool,
+}
+
+/// Position of a scrub in its feed.
+struct Scrub<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug,
+{
+    feed: Weak<Mutex<Feed<T>>>,
+    next: u64,
+    end: u64,
+    bytes_per_sec: u64,
+    source: Option<Box<dyn Source>>,
+}
+
+impl<T> Feed<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send + 'static,
+{
+    /// Check every block stored locally against its tree node, reading at
+    /// most about `bytes_per_sec` bytes per second, or as fast as possible
+    /// with 0. Yields a finding for each corrupt block.
+    ///
+    /// Corrupt blocks are handled as in `.audit()`. With a `source`, they
+    /// are then requested from it again and stored as with `.put()`. The
+    /// feed is only locked while a block is checked or stored, so spawning a
+    /// task that drives the stream scrubs the feed in the background while
+    /// it is in use. The stream ends after the last block of the feed when
+    /// it was created, or once every other reference to the feed is
+    /// dropped.
+    pub async fn scrub(
+        feed: &Arc<Mutex<Self>>,
+        bytes_per_sec: u64,
+        source: Option<Box<dyn Source>>,
+    ) -> impl Stream<Item = Result<ScrubFinding>> {
+        let scrub = Scrub {
+            feed: Arc::downgrade(feed),
+            next: 0,
+            end: feed.lock().await.len(),
+            bytes_per_sec,
+            source,
+        };
+        stream::unfold(scrub, next_finding)
+    }
+
+    /// Check the block at `index` if it is stored, discarding it if corrupt.
+    /// Returns the bytes read and whether the block was corrupt.
+    async fn scrub_block(&mut self, index: u64) -> Result<(u64, bool)> {
+        if !self.bitfield.get(index) {
+            return Ok((0, false));
+        }
+        let node = self.storage.get_node(2 * index).await?;
+        let data = self.storage.get_data(index).await?;
+        let bytes = data.len() as u64;
+        if node.hash == Hash::from_leaf(&data).as_bytes() {
+            return Ok((bytes, false));
+        }
+        self.discard_corrupt(index, node.hash, data).await?;
+        Ok((bytes, true))
+    }
+}
+
+/// Check blocks until the next corrupt one, pacing the reads.
+async fn next_finding<T>(mut scrub: Scrub<T>) -> Option<(Result<ScrubFinding>, Scrub<T>)>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send + 'static,
+{
+    while scrub.next < scrub.end {
+        let index = scrub.next;
+        scrub.next += 1;
+        let feed = scrub.feed.upgrade()?;
+        let result = feed.lock().await.scrub_block(index).await;
+        let (bytes, corrupt) = match result {
+            Ok(checked) => checked,
+            Err(err) => return Some((Err(err), scrub)),
+        };
+        if scrub.bytes_per_sec > 0 && bytes > 0 {
+            let secs = bytes as f64 / scrub.bytes_per_sec as f64;
+            task::sleep(Duration::from_secs_f64(secs)).await;
+        }
+        if !corrupt {
+            continue;
+        }
+        let repaired = match scrub.source.as_deref_mut() {
+            Some(source) => match repair(&feed, source, index).await {
+                Ok(()) => true,
+                Err(err) => return Some((Err(err), scrub)),
+            },
+            None => false,
+        };
+        return Some((Ok(ScrubFinding { index, repaired }), scrub));
+    }
+    None
+}
+
+/// Download the block at `index` from `source` and store it, without
+/// holding the feed locked during the request.
+async fn repair<T>(feed: &Mutex<Feed<T>>, source: &mut dyn Source, index: u64) -> Result<()>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    let request = feed.lock().await.request(index);
+    let (proof, data) = Proof::decode(&source.request(&request).await?)?;
+    ensure!(
+        proof.index == index,
+        format!("Requested block {}, got block {}", index, proof.index)
+    );
+    feed.lock().await.put(index, data.as_deref(), proof).await
+}
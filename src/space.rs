@@ -0,0 +1,73 @@
+//! Checking for free disk space before writing.
+
+use crate::feed::Feed;
+use anyhow::Result;
+use random_access_storage::RandomAccess;
+use std::fmt::{self, Debug, Display};
+use std::path::Path;
+
+/// Error returned when a write would leave less free space on the feed's
+/// filesystem than set by `Feed::set_min_free_space()`. Nothing is written.
+/// Detect it with `err.downcast_ref::<OutOfSpace>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutOfSpace {
+    /// Bytes to write, plus the free space to keep.
+    pub needed: u64,
+    /// Bytes available on the filesystem.
+    pub available: u64,
+}
+
+impl Display for OutOfSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Not enough disk space: {} bytes needed, {} available",
+            self.needed, self.available
+        )
+    }
+}
+
+impl std::error::Error for OutOfSpace {}
+
+impl<T> Feed<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    /// Keep at least `bytes` free on the filesystem of the feed's directory:
+    /// appends and `.put()` check the available space first, and fail with
+    /// [OutOfSpace] rather than stopping halfway through a batch. `None`
+    /// turns the check off, which is the default. Only feeds opened from a
+    /// directory, on Unix, are checked.
+    pub fn set_min_free_space(&mut self, bytes: Option<u64>) {
+        self.min_free_space = bytes;
+    }
+
+    /// Fail with [OutOfSpace] unless `bytes` can be written while keeping
+    /// the free space set by `.set_min_free_space()`.
+    pub(crate) fn ensure_space(&self, bytes: u64) -> Result<()> {
+        let (min_free_space, changes) = match (self.min_free_space, &self.changes) {
+            (Some(min_free_space), Some(changes)) => (min_free_space, changes),
+            _ => return Ok(()),
+        };
+        if let Some(available) = available_space(changes.dir())? {
+            let needed = bytes.saturating_add(min_free_space);
+            if available < needed {
+                return Err(OutOfSpace { needed, available }.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get the bytes available to unprivileged users on the filesystem holding
+/// `dir`, if the platform can tell.
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Result<Option<u64>> {
+    let stat = rustix::fs::statvfs(dir)?;
+    Ok(Some(stat.f_bavail.saturating_mul(stat.f_frsize)))
+}
+
+#[cfg(not(unix))]
+fn available_space(_dir: &Path) -> Result<Option<u64>> {
+    Ok(None)
+}
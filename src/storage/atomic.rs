@@ -0,0 +1,85 @@
+//! Replacing whole files so readers see either the old or the new contents.
+
+use anyhow::{anyhow, Result};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Write `data` to the file at `path`, replacing it atomically: the data
+/// goes to a temporary file next to it, which is flushed and renamed over
+/// `path`, and the directory is flushed so the rename survives a power loss.
+/// A crash at any point leaves either the old file or the new one, never a
+/// half-written one. Meant for small files rewritten as a whole, such as
+/// headers, not for stores written in place.
+pub fn atomic_write(path: &Path, data: &[u8]) -> Result<()> {
+    replace(path, data, true)
+}
+
+/// Like `atomic_write()`, without flushing anything: readers never see a
+/// half-written file, but a power loss may leave the old one. For files
+/// rewritten too often to flush each time.
+pub(crate) fn atomic_replace(path: &Path, data: &[u8]) -> Result<()> {
+    replace(path, data, false)
+}
+
+fn replace(path: &Path, data: &[u8], durable: bool) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{:?} is not a file path", path))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut temp_name = name.to_owned();
+    temp_name.push(".tmp");
+    let temp = dir.join(temp_name);
+
+    let result = write_and_rename(&temp, path, dir, data, durable);
+    if result.is_err() {
+        fs::remove_file(&temp).ok();
+    }
+    Ok(result?)
+}
+
+fn write_and_rename(
+    temp: &Path,
+    path: &Path,
+    dir: &Path,
+    data: &[u8],
+    durable: bool,
+) -> io::Result<()> {
+    let mut file = File::create(temp)?;
+    file.write_all(data)?;
+    if durable {
+        file.sync_all()?;
+    }
+    fs::rename(temp, path)?;
+    if durable {
+        sync_dir(dir)?;
+    }
+    Ok(())
+}
+
+/// Flush a directory's entries, which only Unix supports.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[test]
+fn should_replace_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("header");
+    atomic_write(&path, b"old").unwrap();
+    atomic_write(&path, b"new").unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"new");
+    atomic_replace(&path, b"newer").unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"newer");
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    assert!(atomic_write(&dir.path().join("missing").join("header"), b"").is_err());
+}
@@ -0,0 +1,191 @@
+//! Writes staged across stores and flushed together, in a safe order.
+
+use super::{Node, Storage};
+use crate::telemetry::{AppendTimings, Stopwatch};
+use anyhow::Result;
+use ed25519_dalek::Signature;
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
+
+/// A staged write.
+#[derive(Debug)]
+enum Op<'a> {
+    Data {
+        index: u64,
+        byte_offset: u64,
+        data: &'a [u8],
+    },
+    Checksum {
+        index: u64,
+        checksum: u32,
+    },
+    Timestamps {
+        index: u64,
+        count: u64,
+        timestamp: u64,
+    },
+    Expiry {
+        index: u64,
+        expiry: u64,
+    },
+    Node(&'a Node),
+    Signature {
+        index: u64,
+        signature: Signature,
+    },
+    DataBitfield {
+        byte_index: u64,
+        byte: u8,
+    },
+}
+
+impl Op<'_> {
+    /// Position of the op in the flush. Blocks and their metadata are
+    /// written before the tree, the tree before the signatures, since
+    /// readers in other processes take a signature to mean that everything
+    /// it covers is complete, and the bitfield last.
+    fn phase(&self) -> u8 {
+        match self {
+            Op::Data { .. } | Op::Checksum { .. } | Op::Timestamps { .. } | Op::Expiry { .. } => 0,
+            Op::Node(_) => 1,
+            Op::Signature { .. } => 2,
+            Op::DataBitfield { .. } => 3,
+        }
+    }
+}
+
+/// Writes staged with `Storage::begin_batch()`.
+///
+/// Nothing is written until `.commit()`, which flushes the writes in a fixed
+/// order whatever order they were staged in: block data, checksums,
+/// timestamps and expiry, then tree nodes, then signatures, then the bitfield. If a
+/// write fails, the writes after it are not attempted, so a signature is
+/// never stored for a block whose data or nodes failed to store.
+#[derive(Debug)]
+pub struct Batch<'a, T>
+where
+    T: RandomAccess + Debug,
+{
+    storage: &'a mut Storage<T>,
+    ops: Vec<Op<'a>>,
+}
+
+impl<'a, T> Batch<'a, T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    pub(crate) fn new(storage: &'a mut Storage<T>) -> Self {
+        Self {
+            storage,
+            ops: vec![],
+        }
+    }
+
+    /// Stage the data of the block at `index`, starting at `byte_offset`
+    /// within the feed. See `Storage::append_data()`.
+    pub fn append_data(&mut self, index: u64, byte_offset: u64, data: &'a [u8]) {
+        self.ops.push(Op::Data {
+            index,
+            byte_offset,
+            data,
+        });
+    }
+
+    /// Stage the checksum of the block at `index`.
+    pub fn put_checksum(&mut self, index: u64, checksum: u32) {
+        self.ops.push(Op::Checksum { index, checksum });
+    }
+
+    /// Stage the time `count` blocks starting at `index` were stored.
+    pub fn put_timestamps(&mut self, index: u64, count: u64, timestamp: u64) {
+        self.ops.push(Op::Timestamps {
+            index,
+            count,
+            timestamp,
+        });
+    }
+
+    /// Stage the time after which the block at `index` may be cleared.
+    pub fn put_expiry(&mut self, index: u64, expiry: u64) {
+        self.ops.push(Op::Expiry { index, expiry });
+    }
+
+    /// Stage a tree node.
+    pub fn put_node(&mut self, node: &'a Node) {
+        self.ops.push(Op::Node(node));
+    }
+
+    /// Stage the signature at `index`.
+    pub fn put_signature(&mut self, index: u64, signature: Signature) {
+        self.ops.push(Op::Signature { index, signature });
+    }
+
+    /// Stage a byte of the data bitfield.
+    pub fn put_data_bitfield(&mut self, byte_index: u64, byte: u8) {
+        self.ops.push(Op::DataBitfield { byte_index, byte });
+    }
+
+    /// Get the number of staged writes.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Check whether no writes are staged.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Flush the staged writes, in order.
+    pub async fn commit(self) -> Result<()> {
+        self.commit_timed(&mut AppendTimings::default()).await
+    }
+
+    /// Flush the staged writes, adding the time spent on each store to
+    /// `timings`.
+    pub(crate) async fn commit_timed(mut self, timings: &mut AppendTimings) -> Result<()> {
+        self.ops.sort_by_key(Op::phase);
+        let storage = self.storage;
+        let mut stopwatch = Stopwatch::start();
+        for op in self.ops {
+            match op {
+                Op::Data {
+                    index,
+                    byte_offset,
+                    data,
+                } => {
+                    storage.append_data(index, byte_offset, data).await?;
+                    timings.data += stopwatch.lap();
+                }
+                Op::Checksum { index, checksum } => {
+                    storage.put_checksum(index, checksum).await?;
+                    timings.checksums += stopwatch.lap();
+                }
+                Op::Timestamps {
+                    index,
+                    count,
+                    timestamp,
+                } => {
+                    storage.put_timestamps(index, count, timestamp).await?;
+                    timings.data += stopwatch.lap();
+                }
+                Op::Expiry { index, expiry } => {
+                    storage.put_expiry(index, expiry).await?;
+                    timings.data += stopwatch.lap();
+                }
+                Op::Node(node) => {
+                    storage.put_node(node).await?;
+                    timings.tree += stopwatch.lap();
+                }
+                Op::Signature { index, signature } => {
+                    storage.put_signature(index, signature).await?;
+                    timings.signatures += stopwatch.lap();
+                }
+                Op::DataBitfield { byte_index, byte } => {
+                    storage.put_data_bitfield(byte_index, byte).await?;
+                    timings.bitfield += stopwatch.lap();
+                }
+            }
+        }
+        Ok(())
+    }
+}
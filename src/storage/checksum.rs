@@ -0,0 +1,50 @@
+//! `CRC32C` (Castagnoli) checksums for cheap local integrity checks, and the
+//! plain `CRC32` used to frame hypercore v10 oplog entries.
+
+const CASTAGNOLI: [u32; 256] = table(0x82f6_3b78);
+const IEEE: [u32; 256] = table(0xedb8_8320);
+
+const fn table(polynomial: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ polynomial
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Compute the `CRC32C` checksum of a byte slice.
+pub fn crc32c(data: &[u8]) -> u32 {
+    checksum(&CASTAGNOLI, data)
+}
+
+/// Compute the `CRC32` (IEEE) checksum of a byte slice.
+pub fn crc32(data: &[u8]) -> u32 {
+    checksum(&IEEE, data)
+}
+
+fn checksum(table: &[u32; 256], data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc = table[((crc ^ u32::from(*byte)) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[test]
+fn should_match_check_value() {
+    assert_eq!(crc32c(b""), 0);
+    assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+}
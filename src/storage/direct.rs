@@ -0,0 +1,237 @@
+//! Disk storage that can bypass the page cache with `O_DIRECT`.
+
+use anyhow::{anyhow, Result};
+use async_std::task;
+use random_access_storage::RandomAccess;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// A file on disk, optionally opened with `O_DIRECT`.
+///
+/// Reads and writes are positioned, so each is one `pread` or `pwrite` on
+/// the blocking thread pool, without seeking first. Direct IO reads and
+/// writes whole blocks of `alignment` bytes, reading back the surrounding
+/// bytes when only part of a block changes. Embedders that cache data
+/// themselves can use it for the data store to avoid caching blocks twice.
+#[derive(Debug)]
+pub struct DirectDisk {
+    file: Arc<File>,
+    length: u64,
+    /// Block size of direct IO, or `None` for buffered IO.
+    alignment: Option<u64>,
+}
+
+impl DirectDisk {
+    /// Open a file with buffered IO.
+    pub async fn open(filename: PathBuf) -> Result<Self> {
+        Self::open_with(filename, None).await
+    }
+
+    /// Open a file with `O_DIRECT`. `alignment` must be a multiple of the
+    /// logical block size of the device, usually 4096.
+    pub async fn open_direct(filename: PathBuf, alignment: u64) -> Result<Self> {
+        anyhow::ensure!(
+            alignment.is_power_of_two() && alignment >= 512,
+            format!("Invalid direct IO alignment {}", alignment)
+        );
+        Self::open_with(filename, Some(alignment)).await
+    }
+
+    async fn open_with(filename: PathBuf, alignment: Option<u64>) -> Result<Self> {
+        task::spawn_blocking(move || {
+            if let Some(dirname) = filename.parent() {
+                fs::create_dir_all(dirname)?;
+            }
+            let mut options = OpenOptions::new();
+            options.create(true).read(true).write(true);
+            if alignment.is_some() {
+                options.custom_flags(libc::O_DIRECT);
+            }
+            let file = options.open(&filename)?;
+            let length = file.metadata()?.len();
+            Ok(Self {
+                file: Arc::new(file),
+                length,
+                alignment,
+            })
+        })
+        .await
+    }
+
+    /// Run a blocking operation on the file.
+    async fn with_file<F, R>(&self, f: F) -> Result<R, BoxError>
+    where
+        F: FnOnce(&File) -> io::Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let file = self.file.clone();
+        task::spawn_blocking(move || f(&file))
+            .await
+            .map_err(|e| e.into())
+    }
+}
+
+#[async_trait::async_trait]
+impl RandomAccess for DirectDisk {
+    type Error = BoxError;
+
+    async fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), Self::Error> {
+        let end = offset + data.len() as u64;
+        match self.alignment {
+            None => {
+                let data = data.to_vec();
+                self.with_file(move |file| file.write_all_at(&data, offset))
+                    .await?;
+            }
+            Some(alignment) => {
+                let start = offset / alignment * alignment;
+                let aligned_end = round_up(end, alignment);
+                let data = data.to_vec();
+                let length = self.length;
+                self.with_file(move |file| {
+                    let mut buf = AlignedBuf::new((aligned_end - start) as usize, alignment);
+                    // Keep the bytes around the write in partially covered blocks.
+                    if offset > start && start < length {
+                        read_blocks(file, start, &mut buf.as_mut()[..alignment as usize])?;
+                    }
+                    if end < aligned_end && aligned_end - alignment < length {
+                        let last = buf.as_mut().len() - alignment as usize;
+                        read_blocks(file, aligned_end - alignment, &mut buf.as_mut()[last..])?;
+                    }
+                    let skip = (offset - start) as usize;
+                    buf.as_mut()[skip..skip + data.len()].copy_from_slice(&data);
+                    file.write_all_at(buf.as_mut(), start)?;
+                    // Drop the padding written past the end of the file.
+                    if aligned_end > length {
+                        file.set_len(std::cmp::max(end, length))?;
+                    }
+                    Ok(())
+                })
+                .await?;
+            }
+        }
+        if end > self.length {
+            self.length = end;
+        }
+        Ok(())
+    }
+
+    async fn read(&mut self, offset: u64, length: u64) -> Result<Vec<u8>, Self::Error> {
+        if offset + length > self.length {
+            return Err(anyhow!(
+                "Read bounds exceeded. {} < {}..{}",
+                self.length,
+                offset,
+                offset + length
+            )
+            .into());
+        }
+        match self.alignment {
+            None => {
+                self.with_file(move |file| {
+                    let mut buf = vec![0; length as usize];
+                    file.read_exact_at(&mut buf, offset)?;
+                    Ok(buf)
+                })
+                .await
+            }
+            Some(alignment) => {
+                self.with_file(move |file| {
+                    let start = offset / alignment * alignment;
+                    let end = round_up(offset + length, alignment);
+                    let mut buf = AlignedBuf::new((end - start) as usize, alignment);
+                    read_blocks(file, start, buf.as_mut())?;
+                    let skip = (offset - start) as usize;
+                    Ok(buf.as_mut()[skip..skip + length as usize].to_vec())
+                })
+                .await
+            }
+        }
+    }
+
+    // Like `RandomAccessDisk`, writing to an unpinned writer is not supported.
+    async fn read_to_writer(
+        &mut self,
+        _offset: u64,
+        _length: u64,
+        _buf: &mut (impl futures::io::AsyncWrite + Send),
+    ) -> Result<(), Self::Error> {
+        Err(anyhow!("read_to_writer is not supported").into())
+    }
+
+    async fn del(&mut self, offset: u64, length: u64) -> Result<(), Self::Error> {
+        let end = std::cmp::min(offset + length, self.length);
+        if offset < end {
+            self.write(offset, &vec![0; (end - offset) as usize])
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn truncate(&mut self, length: u64) -> Result<(), Self::Error> {
+        self.with_file(move |file| file.set_len(length)).await?;
+        self.length = length;
+        Ok(())
+    }
+
+    async fn len(&self) -> Result<u64, Self::Error> {
+        Ok(self.length)
+    }
+
+    async fn is_empty(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.length == 0)
+    }
+
+    async fn sync_all(&mut self) -> Result<(), Self::Error> {
+        self.with_file(|file| file.sync_all()).await
+    }
+}
+
+/// Read whole blocks starting at `offset`, leaving the part of `buf` past
+/// the end of the file untouched.
+fn read_blocks(file: &File, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read_at(&mut buf[read..], offset + read as u64) {
+            Ok(0) => break,
+            // Direct reads only come up short at the end of the file, where
+            // reading on from an unaligned offset would fail.
+            Ok(n) if n % 512 != 0 => break,
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+fn round_up(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+/// A zeroed buffer whose start is aligned in memory, as direct IO requires.
+struct AlignedBuf {
+    buf: Vec<u8>,
+    start: usize,
+    len: usize,
+}
+
+impl AlignedBuf {
+    fn new(len: usize, alignment: u64) -> Self {
+        let alignment = alignment as usize;
+        let buf = vec![0; len + alignment];
+        let start = (alignment - buf.as_ptr() as usize % alignment) % alignment;
+        Self { buf, start, len }
+    }
+
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[self.start..self.start + self.len]
+    }
+}
@@ -0,0 +1,104 @@
+//! Coordination between processes sharing a feed directory.
+
+use super::atomic_replace;
+use anyhow::{anyhow, bail, Result};
+use async_std::task;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+
+/// Name of the lock file inside a feed directory.
+const LOCK: &str = "lock";
+/// Name of the change counter file inside a feed directory.
+const CHANGES: &str = "changes";
+
+/// Exclusive advisory lock on a feed directory. Only one process may write to
+/// a feed at a time. The lock is released when dropped.
+#[derive(Debug)]
+pub struct DirLock {
+    _file: File,
+}
+
+impl DirLock {
+    /// Acquire the lock, failing if another writer holds it.
+    pub fn acquire(dir: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(LOCK))?;
+        match file.try_lock() {
+            Ok(()) => Ok(Self { _file: file }),
+            Err(TryLockError::WouldBlock) => {
+                bail!("Feed at {:?} is locked by another writer", dir)
+            }
+            Err(TryLockError::Error(e)) => Err(anyhow!(e)),
+        }
+    }
+}
+
+/// Counter bumped on every write to a feed directory, so other processes can
+/// detect changes without scanning the stores.
+#[derive(Debug)]
+pub struct ChangeCounter {
+    dir: PathBuf,
+    path: PathBuf,
+    value: u64,
+}
+
+impl ChangeCounter {
+    /// Open the counter for a feed directory.
+    pub async fn open(dir: &Path) -> Result<Self> {
+        let path = dir.join(CHANGES);
+        let value = read_counter(&path).await?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            path,
+            value,
+        })
+    }
+
+    /// The feed directory this counter belongs to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Read the current value from disk, which may have been bumped by
+    /// another process.
+    pub async fn read(&self) -> Result<u64> {
+        read_counter(&self.path).await
+    }
+
+    /// Check if another process changed the feed since the last `sync()` or
+    /// `bump()`.
+    pub async fn changed(&self) -> Result<bool> {
+        Ok(self.read().await? != self.value)
+    }
+
+    /// Mark the current value on disk as seen.
+    pub async fn sync(&mut self) -> Result<()> {
+        self.value = self.read().await?;
+        Ok(())
+    }
+
+    /// Record a change. The file is replaced rather than rewritten in place,
+    /// so readers never see it truncated, on the blocking thread pool.
+    pub async fn bump(&mut self) -> Result<()> {
+        self.value += 1;
+        let path = self.path.clone();
+        let value = self.value;
+        task::spawn_blocking(move || atomic_replace(&path, &value.to_be_bytes())).await
+    }
+}
+
+async fn read_counter(path: &Path) -> Result<u64> {
+    match async_std::fs::read(path).await {
+        Ok(bytes) if bytes.len() == 8 => {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(&bytes);
+            Ok(u64::from_be_bytes(buf))
+        }
+        Ok(_) => Ok(0),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(anyhow!(e)),
+    }
+}
@@ -0,0 +1,95 @@
+//! Versioning of the on-disk layout, and the migrations run on open to bring
+//! older directories up to date.
+//!
+//! The SLEEP headers of the tree, bitfield and signatures stores are shared
+//! with the JavaScript implementation, so the version is kept in a store of
+//! its own. To change the layout, bump [`FORMAT_VERSION`] and append a
+//! migration from the previous version to [`migrations()`].
+
+use super::{Storage, Store, CHECKSUM_LEN};
+use anyhow::Result;
+use futures::future::{BoxFuture, FutureExt};
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
+
+/// Version of the on-disk layout written by this crate. Directories that
+/// predate versioning, including those written by the JavaScript
+/// implementation, are version 0.
+pub const FORMAT_VERSION: u64 = 2;
+
+/// Upgrade the stores in place.
+type Run<T> = for<'a> fn(&'a mut Storage<T>) -> BoxFuture<'a, Result<()>>;
+
+/// A step upgrading the stores from version `from` to the next.
+#[derive(Debug)]
+pub(crate) struct Migration<T>
+where
+    T: RandomAccess + Debug,
+{
+    /// Version the migration upgrades from.
+    pub(crate) from: u64,
+    /// What the migration changes, for error messages.
+    pub(crate) description: &'static str,
+    pub(crate) run: Run<T>,
+}
+
+/// Get every migration, ordered by the version they upgrade from.
+pub(crate) fn migrations<T>() -> Vec<Migration<T>>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    vec![
+        Migration {
+            from: 0,
+            description: "record the format version",
+            run: record_version,
+        },
+        Migration {
+            from: 1,
+            description: "mark stored checksums",
+            run: mark_checksums,
+        },
+    ]
+}
+
+/// Unversioned directories share the layout of version 1, which only adds
+/// the version store itself.
+fn record_version<T>(_storage: &mut Storage<T>) -> BoxFuture<'_, Result<()>>
+where
+    T: RandomAccess + Debug + Send,
+{
+    async { Ok(()) }.boxed()
+}
+
+/// Version 2 precedes each checksum with a byte marking it as present, so a
+/// checksum of 0 is no longer taken for a missing one. Zeroed records were
+/// missing checksums, or can't be told apart from them. The store is
+/// rewritten with a single write.
+fn mark_checksums<T>(storage: &mut Storage<T>) -> BoxFuture<'_, Result<()>>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    async move {
+        let len = storage.side.len(Store::Checksums).await?;
+        let len = len - len % 4;
+        if len == 0 {
+            return Ok(());
+        }
+        let old = storage.side.read(Store::Checksums, 0, len).await?;
+        let mut buf = Vec::with_capacity((len / 4 * CHECKSUM_LEN) as usize);
+        for checksum in old.chunks(4) {
+            buf.push(u8::from(checksum != [0; 4]));
+            buf.extend_from_slice(checksum);
+        }
+        storage.side.write(Store::Checksums, 0, &buf).await
+    }
+    .boxed()
+}
+
+#[test]
+fn should_register_every_version() {
+    use random_access_memory::RandomAccessMemory;
+    let migrations = migrations::<RandomAccessMemory>();
+    let versions: Vec<u64> = migrations.iter().map(|migration| migration.from).collect();
+    assert_eq!(versions, (0..FORMAT_VERSION).collect::<Vec<_>>());
+}
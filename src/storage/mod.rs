@@ -1,12 +1,36 @@
 //! Save data to a desired storage backend.
 
+mod atomic;
+mod batch;
+mod checksum;
+#[cfg(target_os = "linux")]
+mod direct;
+mod lock;
+mod migrate;
 mod node;
 mod persist;
+mod quarantine;
+mod retry;
+mod side;
 
+pub(crate) use self::atomic::atomic_replace;
+pub use self::atomic::atomic_write;
+pub use self::batch::Batch;
+pub(crate) use self::checksum::{crc32, crc32c};
+#[cfg(target_os = "linux")]
+pub use self::direct::DirectDisk;
+pub(crate) use self::lock::{ChangeCounter, DirLock};
+pub use self::migrate::FORMAT_VERSION;
 pub use self::node::Node;
 pub use self::persist::Persist;
+pub use self::quarantine::QuarantinedBlock;
+pub use self::retry::{is_retryable, RetryPolicy, RetryingStorage};
 pub use merkle_tree_stream::Node as NodeTrait;
 
+use self::side::{Create, Exists, SideStores};
+
+use crate::ranges::Ranges;
+use crate::witness::{Witness, WITNESS_LEN};
 use anyhow::{anyhow, ensure, Result};
 use ed25519_dalek::{PublicKey, SecretKey, Signature, PUBLIC_KEY_LENGTH, SECRET_KEY_LENGTH};
 use flat_tree as flat;
@@ -16,11 +40,25 @@ use random_access_memory::RandomAccessMemory;
 use random_access_storage::RandomAccess;
 use sleep_parser::*;
 use std::borrow::Borrow;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::ops::Range;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const HEADER_OFFSET: u64 = 32;
+/// Size of a page in the bitfield store: data, tree and index bitfields.
+const BITFIELD_PAGE_LEN: u64 = 3328;
+/// Size of the data bitfield within a bitfield page.
+const DATA_BITFIELD_PAGE_LEN: u64 = 1024;
+/// Size of the offsets store header, which holds the alignment.
+const OFFSETS_HEADER_LEN: u64 = 8;
+/// Size of a record in the checksums store: a byte set if the block has a
+/// checksum, followed by the checksum.
+const CHECKSUM_LEN: u64 = 5;
+/// Largest number of unrequested nodes read through to join two node reads.
+const COALESCE_GAP: u64 = 4;
+/// Marks a secret key encrypted with a passphrase in the keypair store.
+pub(crate) const ENCRYPTED_KEY_MAGIC: &[u8; 4] = b"HSK1";
 
 #[derive(Debug)]
 pub struct PartialKeypair {
@@ -28,8 +66,10 @@ pub struct PartialKeypair {
     pub secret: Option<SecretKey>,
 }
 
-/// The types of stores that can be created.
-#[derive(Debug)]
+/// The types of stores that can be created. Stores after `Version` are only
+/// created once a feature using them writes to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum Store {
     /// Tree
     Tree,
@@ -41,6 +81,24 @@ pub enum Store {
     Signatures,
     /// Keypair
     Keypair,
+    /// Checksums
+    Checksums,
+    /// Block offsets, for feeds with aligned data
+    Offsets,
+    /// Times at which blocks were stored
+    Timestamps,
+    /// Times after which blocks may be cleared
+    Expiry,
+    /// Ranges of blocks pinned against clearing
+    Pins,
+    /// Version of the on-disk layout
+    Version,
+    /// Co-signatures of checkpoints by witnesses
+    Witnesses,
+    /// Corrupted blocks moved aside
+    Quarantine,
+    /// Application flags of blocks
+    Flags,
 }
 
 /// Save data to a desired storage backend.
@@ -54,6 +112,14 @@ where
     bitfield: T,
     signatures: T,
     keypair: T,
+    version: T,
+    /// Stores opened on first use.
+    side: SideStores<T>,
+    /// Boundary each block in the data store starts at, or 0 if blocks are
+    /// packed back to back.
+    alignment: u64,
+    /// Version of the on-disk layout, see [FORMAT_VERSION].
+    format: u64,
 }
 
 impl<T> Storage<T>
@@ -61,20 +127,36 @@ where
     T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
 {
     /// Create a new instance. Takes a keypair and a callback to create new
-    /// storage instances.
+    /// storage instances. The callback is kept to create side stores when
+    /// they are first used, see [Store], so it must own what it uses.
     // Named `.open()` in the JS version. Replaces the `.openKey()` method too by
     // requiring a key pair to be initialized before creating a new instance.
     pub async fn new<Cb>(create: Cb) -> Result<Self>
     where
-        Cb: Fn(Store) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<T>> + Send>>,
+        Cb: Fn(Store) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<T>> + Send>>
+            + Send
+            + Sync
+            + 'static,
     {
-        let mut instance = Self {
-            tree: create(Store::Tree).await?,
-            data: create(Store::Data).await?,
-            bitfield: create(Store::Bitfield).await?,
-            signatures: create(Store::Signatures).await?,
-            keypair: create(Store::Keypair).await?,
-        };
+        Self::create_with(Box::new(create), None).await
+    }
+
+    /// Open existing stores without writing headers to them. Older formats
+    /// are read as they are, since migrating would write to the stores.
+    pub async fn open<Cb>(create: Cb) -> Result<Self>
+    where
+        Cb: Fn(Store) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<T>> + Send>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self::open_with(Box::new(create), None).await
+    }
+
+    /// Like `.new()`, reading side stores only if `exists` says they were
+    /// created before.
+    async fn create_with(create: Create<T>, exists: Option<Exists>) -> Result<Self> {
+        let mut instance = Self::open_with(create, exists).await?;
 
         let header = create_bitfield();
         instance
@@ -97,13 +179,125 @@ where
             .await
             .map_err(|e| anyhow!(e))?;
 
+        instance.migrate().await?;
         Ok(instance)
     }
 
+    /// Like `.open()`, reading side stores only if `exists` says they were
+    /// created before.
+    async fn open_with(create: Create<T>, exists: Option<Exists>) -> Result<Self> {
+        let mut instance = Self {
+            tree: create(Store::Tree).await?,
+            data: create(Store::Data).await?,
+            bitfield: create(Store::Bitfield).await?,
+            signatures: create(Store::Signatures).await?,
+            keypair: create(Store::Keypair).await?,
+            version: create(Store::Version).await?,
+            side: SideStores::new(create, exists),
+            alignment: 0,
+            format: 0,
+        };
+        if instance.side.len(Store::Offsets).await? >= OFFSETS_HEADER_LEN {
+            let buf = instance
+                .side
+                .read(Store::Offsets, 0, OFFSETS_HEADER_LEN)
+                .await?;
+            instance.alignment = read_u64(&buf);
+        }
+        let version = instance.format_version().await?;
+        ensure!(
+            version <= FORMAT_VERSION,
+            format!("Feed was written in the newer format version {}", version)
+        );
+        instance.format = version;
+        Ok(instance)
+    }
+
+    /// Get the version of the on-disk layout, see [FORMAT_VERSION].
+    pub async fn format_version(&mut self) -> Result<u64> {
+        if self.version.len().await.map_err(|e| anyhow!(e))? < 8 {
+            return Ok(0);
+        }
+        let buf = self.version.read(0, 8).await.map_err(|e| anyhow!(e))?;
+        Ok(read_u64(&buf))
+    }
+
+    /// Run the migrations from the recorded format version up to
+    /// [FORMAT_VERSION], recording the version after each one so an
+    /// interrupted upgrade resumes where it stopped.
+    async fn migrate(&mut self) -> Result<()> {
+        let mut version = self.format_version().await?;
+        for migration in migrate::migrations::<T>() {
+            if migration.from < version {
+                continue;
+            }
+            (migration.run)(self).await.map_err(|e| {
+                anyhow!(
+                    "Migration from format version {} to {} ({}) failed: {}",
+                    migration.from,
+                    migration.from + 1,
+                    migration.description,
+                    e
+                )
+            })?;
+            version = migration.from + 1;
+            self.version
+                .write(0, &version.to_be_bytes())
+                .await
+                .map_err(|e| anyhow!(e))?;
+            self.format = version;
+        }
+        Ok(())
+    }
+
+    /// Get the boundary blocks in the data store are aligned to, or 0 if
+    /// they are packed back to back.
+    pub fn alignment(&self) -> u64 {
+        self.alignment
+    }
+
+    /// Align every block written from now on to a multiple of `alignment`
+    /// bytes in the data store. The gaps are left as padding, and block
+    /// offsets are recorded in the offsets store. Only empty stores can be
+    /// aligned.
+    ///
+    /// An alignment of 1 keeps blocks packed, but still records their
+    /// offsets so the data store can be compacted.
+    pub async fn set_alignment(&mut self, alignment: u64) -> Result<()> {
+        ensure!(
+            alignment.is_power_of_two(),
+            format!("Alignment {} is not a power of two", alignment)
+        );
+        if alignment == self.alignment {
+            return Ok(());
+        }
+        ensure!(
+            self.data.len().await.map_err(|e| anyhow!(e))? == 0,
+            "Only empty feeds can change their alignment"
+        );
+        self.side
+            .write(Store::Offsets, 0, &alignment.to_be_bytes())
+            .await?;
+        self.alignment = alignment;
+        Ok(())
+    }
+
     /// Write data to the feed.
     #[inline]
     pub async fn write_data(&mut self, offset: u64, data: &[u8]) -> Result<()> {
-        self.data.write(offset, &data).await.map_err(|e| anyhow!(e))
+        self.data.write(offset, data).await.map_err(|e| anyhow!(e))
+    }
+
+    /// Write the data of the block at `index`, which starts at `byte_offset`
+    /// when blocks are packed. Aligned blocks are written to the next
+    /// boundary after the end of the data store instead.
+    pub async fn append_data(&mut self, index: u64, byte_offset: u64, data: &[u8]) -> Result<()> {
+        if self.alignment == 0 {
+            return self.write_data(byte_offset, data).await;
+        }
+        let offset = self.next_aligned_offset().await?;
+        self.write_data(offset, data).await?;
+        self.put_offset(index, offset).await
     }
 
     /// Write a byte vector to a data storage (random-access instance) at the
@@ -119,6 +313,10 @@ where
             return Ok(());
         }
 
+        if self.alignment != 0 {
+            return self.append_data(index, 0, data).await;
+        }
+
         let range = self.data_offset(index, nodes).await?;
 
         ensure!(
@@ -132,6 +330,58 @@ where
             .map_err(|e| anyhow!(e))
     }
 
+    /// Zero the data of the block at `index`.
+    // Written out rather than using `del`, which neither the disk nor the
+    // memory backend implements.
+    pub async fn del_data(&mut self, index: u64) -> Result<()> {
+        let range = self.data_offset(index, &[]).await?;
+        let zeros = vec![0; (range.end - range.start) as usize];
+        self.write_data(range.start, &zeros).await
+    }
+
+    /// Move the blocks in `keep` towards the start of the data store, in the
+    /// order they are stored, and truncate it after the last one. Returns the
+    /// number of bytes reclaimed. Only aligned stores, whose block offsets
+    /// are recorded, can be compacted, and the data store has to support
+    /// `truncate`, which `RandomAccessMemory` does not.
+    pub async fn compact_data(&mut self, keep: &[u64]) -> Result<u64> {
+        ensure!(
+            self.alignment != 0,
+            "Only aligned data stores can be compacted"
+        );
+        let mut blocks = Vec::with_capacity(keep.len());
+        for index in keep {
+            blocks.push((self.get_offset(*index).await?, *index));
+        }
+        blocks.sort_unstable();
+        let data_len = self.data.len().await.map_err(|e| anyhow!(e))?;
+
+        // Blocks only ever move towards the start, and are only written to
+        // space no offset points to, so a failure at any point leaves every
+        // block readable. Blocks overlapping their new place are first
+        // moved past the end of the store, which is truncated once done.
+        let mut end = 0u64;
+        for (offset, index) in blocks {
+            let len = self.get_node(tree_index(index)).await?.len();
+            let target = end.div_ceil(self.alignment) * self.alignment;
+            if target != offset {
+                let data = self.data.read(offset, len).await.map_err(|e| anyhow!(e))?;
+                if target + len > offset {
+                    let staging = self.next_aligned_offset().await?;
+                    self.move_block(index, staging, &data).await?;
+                }
+                self.move_block(index, target, &data).await?;
+            }
+            end = target + len;
+        }
+
+        let staged_len = self.data.len().await.map_err(|e| anyhow!(e))?;
+        if end < staged_len {
+            self.data.truncate(end).await.map_err(|e| anyhow!(e))?;
+        }
+        Ok(data_len.saturating_sub(end))
+    }
+
     /// Get data from disk that the user has written to it. This is stored
     /// unencrypted, so there's no decryption needed.
     // FIXME: data_offset always reads out index 0, length 0
@@ -145,6 +395,52 @@ where
             .map_err(|e| anyhow!(e))
     }
 
+    /// Get the data of the consecutive blocks in `range`, which must all be
+    /// stored, with one read of the data store if blocks are packed back to
+    /// back.
+    pub async fn get_data_range(&mut self, range: Range<u64>) -> Result<Vec<Vec<u8>>> {
+        if self.alignment != 0 || range.start >= range.end {
+            let mut blocks = vec![];
+            for index in range {
+                blocks.push(self.get_data(index).await?);
+            }
+            return Ok(blocks);
+        }
+        let start = self.data_offset(range.start, &[]).await?.start;
+        let indexes: Vec<u64> = range.map(tree_index).collect();
+        let nodes = self.get_nodes(&indexes).await?;
+        let len = nodes.iter().map(|node| node.len()).sum();
+        let buf = self.data.read(start, len).await.map_err(|e| anyhow!(e))?;
+        let mut offset = 0;
+        Ok(nodes
+            .iter()
+            .map(|node| {
+                let block = buf[offset..offset + node.len() as usize].to_vec();
+                offset += node.len() as usize;
+                block
+            })
+            .collect())
+    }
+
+    /// Get `len` bytes of the data of the block at `index`, starting at
+    /// `offset` within the block, reading only those bytes.
+    pub async fn get_data_slice(&mut self, index: u64, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let range = self.data_offset(index, &[]).await?;
+        let block_len = range.end - range.start;
+        ensure!(
+            offset.checked_add(len).is_some_and(|end| end <= block_len),
+            "Bytes {}..{} are past the end of block {} of {} bytes",
+            offset,
+            offset.saturating_add(len),
+            index,
+            block_len
+        );
+        self.data
+            .read(range.start + offset, len)
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
     /// Search the signature stores for a `Signature`, starting at `index`.
     pub fn next_signature<'a>(
         &'a mut self,
@@ -195,12 +491,264 @@ where
             .map_err(|e| anyhow!(e))
     }
 
+    /// Get the `CRC32C` checksum of the data at `index`, if one was stored.
+    pub async fn get_checksum(&mut self, index: u64) -> Result<Option<u32>> {
+        if self.format < 2 {
+            return self.get_unmarked_checksum(index).await;
+        }
+        let len = self.side.len(Store::Checksums).await?;
+        if len < CHECKSUM_LEN * (index + 1) {
+            return Ok(None);
+        }
+        let buf = self
+            .side
+            .read(Store::Checksums, CHECKSUM_LEN * index, CHECKSUM_LEN)
+            .await?;
+        match buf[0] {
+            0 => Ok(None),
+            _ => Ok(Some(u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]))),
+        }
+    }
+
+    /// Get a checksum from the layout before format version 2, which had no
+    /// presence byte, so a checksum of 0 reads as none.
+    async fn get_unmarked_checksum(&mut self, index: u64) -> Result<Option<u32>> {
+        let len = self.side.len(Store::Checksums).await?;
+        if len < 4 * (index + 1) {
+            return Ok(None);
+        }
+        let bytes = self.side.read(Store::Checksums, 4 * index, 4).await?;
+        if not_zeroes(&bytes) {
+            Ok(Some(u32::from_be_bytes([
+                bytes[0], bytes[1], bytes[2], bytes[3],
+            ])))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Write the `CRC32C` checksum of the data at `index`.
+    pub async fn put_checksum(&mut self, index: u64, checksum: u32) -> Result<()> {
+        if self.format < 2 {
+            return self
+                .side
+                .write(Store::Checksums, 4 * index, &checksum.to_be_bytes())
+                .await;
+        }
+        let mut buf = vec![1];
+        buf.extend_from_slice(&checksum.to_be_bytes());
+        self.side
+            .write(Store::Checksums, CHECKSUM_LEN * index, &buf)
+            .await
+    }
+
+    /// Get the time the block at `index` was stored, in seconds since the
+    /// Unix epoch, if it was recorded.
+    pub async fn get_timestamp(&mut self, index: u64) -> Result<Option<u64>> {
+        let len = self.side.len(Store::Timestamps).await?;
+        if len < 8 * (index + 1) {
+            return Ok(None);
+        }
+        let buf = self.side.read(Store::Timestamps, 8 * index, 8).await?;
+        match read_u64(&buf) {
+            0 => Ok(None),
+            timestamp => Ok(Some(timestamp)),
+        }
+    }
+
+    /// Check whether the times blocks were stored were ever recorded.
+    pub fn has_timestamps(&self) -> bool {
+        self.side.exists(Store::Timestamps)
+    }
+
+    /// Record the time `count` blocks starting at `index` were stored, in
+    /// seconds since the Unix epoch.
+    pub async fn put_timestamps(&mut self, index: u64, count: u64, timestamp: u64) -> Result<()> {
+        let buf = timestamp.to_be_bytes().repeat(count as usize);
+        self.side.write(Store::Timestamps, 8 * index, &buf).await
+    }
+
+    /// Get the time after which the block at `index` may be cleared, in
+    /// seconds since the Unix epoch, if its writer set one.
+    pub async fn get_expiry(&mut self, index: u64) -> Result<Option<u64>> {
+        let len = self.side.len(Store::Expiry).await?;
+        if len < 8 * (index + 1) {
+            return Ok(None);
+        }
+        let buf = self.side.read(Store::Expiry, 8 * index, 8).await?;
+        match read_u64(&buf) {
+            0 => Ok(None),
+            expiry => Ok(Some(expiry)),
+        }
+    }
+
+    /// Record the time after which the block at `index` may be cleared, in
+    /// seconds since the Unix epoch.
+    pub async fn put_expiry(&mut self, index: u64, expiry: u64) -> Result<()> {
+        self.side
+            .write(Store::Expiry, 8 * index, &expiry.to_be_bytes())
+            .await
+    }
+
+    /// Start staging writes to several stores, to be flushed together by
+    /// `Batch::commit()`.
+    pub fn begin_batch(&mut self) -> Batch<'_, T> {
+        Batch::new(self)
+    }
+
+    /// Count the bytes in every store.
+    pub async fn stored_bytes(&mut self) -> Result<u64> {
+        let stores = [
+            &self.tree,
+            &self.data,
+            &self.bitfield,
+            &self.signatures,
+            &self.keypair,
+            &self.version,
+        ];
+        let mut bytes = 0;
+        for store in stores.iter() {
+            bytes += store.len().await.map_err(|e| anyhow!(e))?;
+        }
+        for (_, store) in self.side.iter_mut() {
+            bytes += store.len().await.map_err(|e| anyhow!(e))?;
+        }
+        Ok(bytes)
+    }
+
+    /// Flush every store to its backing medium.
+    pub async fn sync_all(&mut self) -> Result<()> {
+        let stores = [
+            &mut self.tree,
+            &mut self.data,
+            &mut self.bitfield,
+            &mut self.signatures,
+            &mut self.keypair,
+            &mut self.version,
+        ];
+        for store in stores {
+            store.sync_all().await.map_err(|e| anyhow!(e))?;
+        }
+        for (_, store) in self.side.iter_mut() {
+            store.sync_all().await.map_err(|e| anyhow!(e))?;
+        }
+        Ok(())
+    }
+
+    /// Read the ranges of pinned blocks.
+    pub async fn read_pins(&mut self) -> Result<Ranges> {
+        let len = self.side.len(Store::Pins).await?;
+        if len < 8 {
+            return Ok(Ranges::new());
+        }
+        let count = read_u64(&self.side.read(Store::Pins, 0, 8).await?);
+        ensure!(len >= 8 + 16 * count, "Truncated pins store");
+        let buf = self.side.read(Store::Pins, 8, 16 * count).await?;
+        Ok(buf
+            .chunks(16)
+            .map(|pair| read_u64(&pair[..8])..read_u64(&pair[8..]))
+            .collect())
+    }
+
+    /// Replace the ranges of pinned blocks. They are preceded by their
+    /// count, so ranges left over from a longer list are ignored.
+    pub async fn write_pins(&mut self, pins: &Ranges) -> Result<()> {
+        let pins = pins.as_slice();
+        let mut buf = Vec::with_capacity(8 + 16 * pins.len());
+        buf.extend_from_slice(&(pins.len() as u64).to_be_bytes());
+        for range in pins {
+            buf.extend_from_slice(&range.start.to_be_bytes());
+            buf.extend_from_slice(&range.end.to_be_bytes());
+        }
+        self.side.write(Store::Pins, 0, &buf).await
+    }
+
+    /// Read every stored witness, in the order they were added. A record
+    /// cut short by a crash is ignored.
+    pub async fn read_witnesses(&mut self) -> Result<Vec<Witness>> {
+        let len = self.side.len(Store::Witnesses).await?;
+        let count = len / WITNESS_LEN as u64;
+        if count == 0 {
+            return Ok(vec![]);
+        }
+        let buf = self
+            .side
+            .read(Store::Witnesses, 0, count * WITNESS_LEN as u64)
+            .await?;
+        buf.chunks(WITNESS_LEN).map(Witness::decode).collect()
+    }
+
+    /// Store a witness after the ones already stored.
+    pub async fn append_witness(&mut self, witness: &Witness) -> Result<()> {
+        let len = self.side.len(Store::Witnesses).await?;
+        let offset = len - len % WITNESS_LEN as u64;
+        self.side
+            .write(Store::Witnesses, offset, &witness.encode())
+            .await
+    }
+
+    /// Move a corrupted block aside, after the ones already quarantined.
+    /// Quarantine is rare, so the store is read to find the end of the last
+    /// complete record, overwriting one cut short by a crash.
+    pub async fn quarantine(&mut self, block: &QuarantinedBlock) -> Result<()> {
+        let offset = self
+            .read_quarantine()
+            .await?
+            .iter()
+            .map(|block| block.encoded_len())
+            .sum();
+        self.side
+            .write(Store::Quarantine, offset, &block.encode())
+            .await
+    }
+
+    /// Read every quarantined block, in the order they were quarantined.
+    pub async fn read_quarantine(&mut self) -> Result<Vec<QuarantinedBlock>> {
+        let len = self.side.len(Store::Quarantine).await?;
+        if len == 0 {
+            return Ok(vec![]);
+        }
+        let buf = self.side.read(Store::Quarantine, 0, len).await?;
+        Ok(QuarantinedBlock::decode_all(&buf))
+    }
+
+    /// Get the application flags of the blocks in `range`, one byte per
+    /// block. Blocks that were never flagged read as 0.
+    pub async fn get_flags(&mut self, range: Range<u64>) -> Result<Vec<u8>> {
+        let len = self.side.len(Store::Flags).await?;
+        let mut flags = vec![0; (range.end - range.start) as usize];
+        let end = range.end.min(len);
+        if range.start < end {
+            let buf = self
+                .side
+                .read(Store::Flags, range.start, end - range.start)
+                .await?;
+            flags[..buf.len()].copy_from_slice(&buf);
+        }
+        Ok(flags)
+    }
+
+    /// Write the application flags of the blocks starting at `index`, one
+    /// byte per block.
+    pub async fn put_flags(&mut self, index: u64, flags: &[u8]) -> Result<()> {
+        self.side.write(Store::Flags, index, flags).await
+    }
+
     /// TODO(yw) docs
     /// Get the offset for the data, return `(offset, size)`.
     ///
     /// ## Panics
     /// A panic can occur if no maximum value is found.
     pub async fn data_offset(&mut self, index: u64, cached_nodes: &[Node]) -> Result<Range<u64>> {
+        if self.alignment != 0 {
+            let offset = self.get_offset(index).await?;
+            let len = match find_node(cached_nodes, tree_index(index)) {
+                Some(node) => node.len(),
+                None => (self.get_node(tree_index(index)).await?).len(),
+            };
+            return Ok(offset..offset + len);
+        }
+
         let mut roots = Vec::new(); // TODO: reuse alloc
         flat::full_roots(tree_index(index), &mut roots);
 
@@ -209,7 +757,7 @@ where
         let block_index = tree_index(index);
 
         if pending == 0 {
-            let len = match find_node(&cached_nodes, block_index) {
+            let len = match find_node(cached_nodes, block_index) {
                 Some(node) => node.len(),
                 None => (self.get_node(block_index).await?).len(),
             };
@@ -234,7 +782,7 @@ where
                 continue;
             }
 
-            let len = match find_node(&cached_nodes, block_index) {
+            let len = match find_node(cached_nodes, block_index) {
                 Some(node) => node.len(),
                 None => (self.get_node(block_index).await?).len(),
             };
@@ -245,6 +793,41 @@ where
         unreachable!();
     }
 
+    /// Get the offset of an aligned block in the data store.
+    async fn get_offset(&mut self, index: u64) -> Result<u64> {
+        let buf = self
+            .side
+            .read(Store::Offsets, OFFSETS_HEADER_LEN + 8 * index, 8)
+            .await?;
+        Ok(read_u64(&buf))
+    }
+
+    /// Record the offset of an aligned block in the data store.
+    async fn put_offset(&mut self, index: u64, offset: u64) -> Result<()> {
+        self.side
+            .write(
+                Store::Offsets,
+                OFFSETS_HEADER_LEN + 8 * index,
+                &offset.to_be_bytes(),
+            )
+            .await
+    }
+
+    /// Write the data of an aligned block to `offset`, and point the block
+    /// there once the data is synced.
+    async fn move_block(&mut self, index: u64, offset: u64, data: &[u8]) -> Result<()> {
+        self.write_data(offset, data).await?;
+        self.data.sync_all().await.map_err(|e| anyhow!(e))?;
+        self.put_offset(index, offset).await?;
+        self.side.sync(Store::Offsets).await
+    }
+
+    /// Get the first aligned offset past the end of the data store.
+    async fn next_aligned_offset(&mut self) -> Result<u64> {
+        let len = self.data.len().await.map_err(|e| anyhow!(e))?;
+        Ok(len.div_ceil(self.alignment) * self.alignment)
+    }
+
     /// Get a `Node` from the `tree` storage.
     #[inline]
     pub async fn get_node(&mut self, index: u64) -> Result<Node> {
@@ -257,6 +840,26 @@ where
         Ok(node)
     }
 
+    /// Get several nodes from the tree, in the order of `indexes`. Nodes
+    /// close to each other in the tree store are read together, so proofs
+    /// take a few larger reads instead of one 40 byte read per node.
+    pub async fn get_nodes(&mut self, indexes: &[u64]) -> Result<Vec<Node>> {
+        let mut nodes = HashMap::with_capacity(indexes.len());
+        for run in coalesce(indexes) {
+            let buf = self
+                .tree
+                .read(HEADER_OFFSET + 40 * run.start, 40 * (run.end - run.start))
+                .await
+                .map_err(|e| anyhow!(e))?;
+            for index in indexes.iter().filter(|index| run.contains(index)) {
+                let offset = (40 * (index - run.start)) as usize;
+                let node = Node::from_bytes(*index, &buf[offset..offset + 40])?;
+                nodes.insert(*index, node);
+            }
+        }
+        Ok(indexes.iter().map(|index| nodes[index].clone()).collect())
+    }
+
     /// Write a `Node` to the `tree` storage.
     /// TODO: prevent extra allocs here. Implement a method on node that can reuse
     /// a buffer.
@@ -281,6 +884,36 @@ where
             .map_err(|e| anyhow!(e))
     }
 
+    /// Write a byte of the data bitfield, using the same page layout as the
+    /// JavaScript implementation.
+    pub async fn put_data_bitfield(&mut self, byte_index: u64, byte: u8) -> Result<()> {
+        let offset = data_bitfield_offset(byte_index);
+        self.put_bitfield(offset, &[byte]).await
+    }
+
+    /// Read a byte of the data bitfield. Bytes that were never written are
+    /// zero.
+    pub async fn get_data_bitfield(&mut self, byte_index: u64) -> Result<u8> {
+        let offset = HEADER_OFFSET + data_bitfield_offset(byte_index);
+        let len = self.bitfield.len().await.map_err(|e| anyhow!(e))?;
+        if offset >= len {
+            return Ok(0);
+        }
+        let buf = self
+            .bitfield
+            .read(offset, 1)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(buf[0])
+    }
+
+    /// Get the number of signature slots in the signature store. For a feed
+    /// written by its owner this equals the length of the feed.
+    pub async fn signature_count(&mut self) -> Result<u64> {
+        let len = self.signatures.len().await.map_err(|e| anyhow!(e))?;
+        Ok(len.saturating_sub(HEADER_OFFSET) / 64)
+    }
+
     /// Read a public key from storage
     pub async fn read_public_key(&mut self) -> Result<PublicKey> {
         let buf = self
@@ -292,8 +925,12 @@ where
         Ok(public_key)
     }
 
-    /// Read a secret key from storage
+    /// Read a secret key from storage. Fails if the key is encrypted.
     pub async fn read_secret_key(&mut self) -> Result<SecretKey> {
+        ensure!(
+            !self.is_secret_key_encrypted().await?,
+            "Secret key is encrypted with a passphrase"
+        );
         let buf = self
             .keypair
             .read(PUBLIC_KEY_LENGTH as u64, SECRET_KEY_LENGTH as u64)
@@ -318,6 +955,79 @@ where
             .map_err(|e| anyhow!(e))
     }
 
+    /// Check whether the stored secret key is encrypted with a passphrase.
+    pub async fn is_secret_key_encrypted(&mut self) -> Result<bool> {
+        let offset = PUBLIC_KEY_LENGTH as u64;
+        let len = self.keypair.len().await.map_err(|e| anyhow!(e))?;
+        if len < offset + ENCRYPTED_KEY_MAGIC.len() as u64 + SECRET_KEY_LENGTH as u64 {
+            return Ok(false);
+        }
+        let buf = self
+            .keypair
+            .read(offset, ENCRYPTED_KEY_MAGIC.len() as u64)
+            .await
+            .map_err(|e| anyhow!(e))?;
+        Ok(buf == ENCRYPTED_KEY_MAGIC)
+    }
+
+    /// Write the secret key encrypted with a key derived from `passphrase`,
+    /// replacing the key stored in the clear.
+    #[cfg(feature = "encryption")]
+    pub async fn write_encrypted_secret_key(
+        &mut self,
+        public_key: &PublicKey,
+        secret_key: &SecretKey,
+        passphrase: &[u8],
+    ) -> Result<()> {
+        let buf = crate::crypto::encrypt_secret_key(public_key, secret_key, passphrase)?;
+        self.keypair
+            .write(PUBLIC_KEY_LENGTH as u64, &buf)
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    /// Read and decrypt a secret key written by
+    /// `.write_encrypted_secret_key()`.
+    #[cfg(feature = "encryption")]
+    pub async fn read_encrypted_secret_key(
+        &mut self,
+        public_key: &PublicKey,
+        passphrase: &[u8],
+    ) -> Result<SecretKey> {
+        ensure!(
+            self.is_secret_key_encrypted().await?,
+            "Secret key is not encrypted"
+        );
+        let buf = self
+            .keypair
+            .read(
+                PUBLIC_KEY_LENGTH as u64,
+                crate::crypto::ENCRYPTED_KEY_LEN as u64,
+            )
+            .await
+            .map_err(|e| anyhow!(e))?;
+        crate::crypto::decrypt_secret_key(public_key, &buf, passphrase)
+    }
+
+    /// Copy the contents of every store into a new in-memory `Storage`.
+    pub async fn to_memory(&mut self) -> Result<Storage<RandomAccessMemory>> {
+        let mut side = SideStores::new(Box::new(memory_store), None);
+        for (store, instance) in self.side.iter_mut() {
+            side.insert(*store, copy_to_memory(instance).await?);
+        }
+        Ok(Storage {
+            tree: copy_to_memory(&mut self.tree).await?,
+            data: copy_to_memory(&mut self.data).await?,
+            bitfield: copy_to_memory(&mut self.bitfield).await?,
+            signatures: copy_to_memory(&mut self.signatures).await?,
+            keypair: copy_to_memory(&mut self.keypair).await?,
+            version: copy_to_memory(&mut self.version).await?,
+            side,
+            alignment: self.alignment,
+            format: self.format,
+        })
+    }
+
     /// Tries to read a partial keypair (ie: with an optional secret_key) from the storage
     pub async fn read_partial_keypair(&mut self) -> Option<PartialKeypair> {
         match self.read_public_key().await {
@@ -339,37 +1049,124 @@ where
 impl Storage<RandomAccessMemory> {
     /// Create a new instance backed by a `RandomAccessMemory` instance.
     pub async fn new_memory() -> Result<Self> {
-        let create = |_| async { Ok(RandomAccessMemory::default()) }.boxed();
-        Ok(Self::new(create).await?)
+        Self::new(memory_store).await
     }
 }
 
+/// Create an empty in-memory store.
+fn memory_store(_store: Store) -> futures::future::BoxFuture<'static, Result<RandomAccessMemory>> {
+    async { Ok(RandomAccessMemory::default()) }.boxed()
+}
+
 impl Storage<RandomAccessDisk> {
     /// Create a new instance backed by a `RandomAccessDisk` instance.
-    pub async fn new_disk(dir: &PathBuf) -> Result<Self> {
-        let storage = |storage: Store| {
-            let name = match storage {
-                Store::Tree => "tree",
-                Store::Data => "data",
-                Store::Bitfield => "bitfield",
-                Store::Signatures => "signatures",
-                Store::Keypair => "key",
-            };
-            RandomAccessDisk::open(dir.as_path().join(name)).boxed()
-        };
-        Ok(Self::new(storage).await?)
+    pub async fn new_disk(dir: &Path) -> Result<Self> {
+        let create = disk_store(dir, |path| RandomAccessDisk::open(path).boxed());
+        Self::create_with(create, Some(store_exists(dir))).await
+    }
+
+    /// Open an existing feed directory without writing to it.
+    pub async fn open_disk(dir: &Path) -> Result<Self> {
+        let key = dir.join(store_name(Store::Keypair));
+        ensure!(key.exists(), format!("No feed found at {:?}", dir));
+        let create = disk_store(dir, |path| RandomAccessDisk::open(path).boxed());
+        Self::open_with(create, Some(store_exists(dir))).await
     }
 }
 
+#[cfg(target_os = "linux")]
+impl Storage<DirectDisk> {
+    /// Create a new instance on disk that reads and writes every store with
+    /// positioned IO (`pread`/`pwrite`), saving a seek per operation.
+    ///
+    /// This only saves seeks. Each store still has a single file handle,
+    /// and a feed runs its reads and appends one at a time, since both
+    /// borrow it mutably, so serving proofs still waits for appends.
+    /// `new_disk()` stays the default as it works on every platform.
+    pub async fn new_disk_positioned(dir: &Path) -> Result<Self> {
+        let create = disk_store(dir, |path| DirectDisk::open(path).boxed());
+        Self::create_with(create, Some(store_exists(dir))).await
+    }
+
+    /// Create a new instance on disk, with every block in the data store
+    /// aligned to `alignment` bytes and read and written with `O_DIRECT`.
+    /// The other stores use buffered IO.
+    pub async fn new_disk_direct(dir: &Path, alignment: u64) -> Result<Self> {
+        let data = dir.join(store_name(Store::Data));
+        let create = disk_store(dir, move |path| {
+            if path == data {
+                DirectDisk::open_direct(path, alignment).boxed()
+            } else {
+                DirectDisk::open(path).boxed()
+            }
+        });
+        let mut storage = Self::create_with(create, Some(store_exists(dir))).await?;
+        storage.set_alignment(alignment).await?;
+        Ok(storage)
+    }
+}
+
+/// Create each store of a feed directory by passing its path to `open`.
+fn disk_store<T, F>(dir: &Path, open: F) -> Create<T>
+where
+    F: Fn(PathBuf) -> futures::future::BoxFuture<'static, Result<T>> + Send + Sync + 'static,
+{
+    let dir = dir.to_owned();
+    Box::new(move |store| open(dir.join(store_name(store))))
+}
+
+/// Check whether a store of a feed directory was created.
+fn store_exists(dir: &Path) -> Exists {
+    let dir = dir.to_owned();
+    Box::new(move |store| dir.join(store_name(store)).exists())
+}
+
+/// Get the file name of a store in a feed directory.
+fn store_name(store: Store) -> &'static str {
+    match store {
+        Store::Tree => "tree",
+        Store::Data => "data",
+        Store::Bitfield => "bitfield",
+        Store::Signatures => "signatures",
+        Store::Keypair => "key",
+        Store::Checksums => "checksums",
+        Store::Offsets => "offsets",
+        Store::Timestamps => "timestamps",
+        Store::Expiry => "expiry",
+        Store::Pins => "pins",
+        Store::Version => "version",
+        Store::Witnesses => "witnesses",
+        Store::Quarantine => "quarantine",
+        Store::Flags => "flags",
+    }
+}
+
+/// Get the offset of a data bitfield byte, relative to the header. Pages
+/// hold the data bitfield first, followed by the tree and index bitfields.
+#[inline]
+fn data_bitfield_offset(byte_index: u64) -> u64 {
+    let page = byte_index / DATA_BITFIELD_PAGE_LEN;
+    page * BITFIELD_PAGE_LEN + byte_index % DATA_BITFIELD_PAGE_LEN
+}
+
+/// Read the full contents of a store into a `RandomAccessMemory` instance.
+async fn copy_to_memory<T>(store: &mut T) -> Result<RandomAccessMemory>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Send,
+{
+    let mut memory = RandomAccessMemory::default();
+    let len = store.len().await.map_err(|e| anyhow!(e))?;
+    if len > 0 {
+        let buf = store.read(0, len).await.map_err(|e| anyhow!(e))?;
+        memory.write(0, &buf).await.map_err(|e| anyhow!(e))?;
+    }
+    Ok(memory)
+}
+
 /// Get a node from a vector of nodes.
 #[inline]
 fn find_node(nodes: &[Node], index: u64) -> Option<&Node> {
-    for node in nodes {
-        if node.index() == index {
-            return Some(node);
-        }
-    }
-    None
+    nodes.iter().find(|node| node.index() == index)
 }
 
 /// Check if a byte slice is not completely zero-filled.
@@ -383,12 +1180,43 @@ fn not_zeroes(bytes: &[u8]) -> bool {
     false
 }
 
+/// Read a big-endian `u64` from the start of a buffer.
+#[inline]
+fn read_u64(buf: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&buf[..8]);
+    u64::from_be_bytes(bytes)
+}
+
 /// Convert the index to the index in the tree.
 #[inline]
 fn tree_index(index: u64) -> u64 {
     2 * index
 }
 
+/// Group node indexes into runs to read at once, joining runs separated by
+/// at most `COALESCE_GAP` nodes.
+fn coalesce(indexes: &[u64]) -> Vec<Range<u64>> {
+    let mut sorted = indexes.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let mut runs: Vec<Range<u64>> = vec![];
+    for index in sorted {
+        match runs.last_mut() {
+            Some(run) if index - run.end <= COALESCE_GAP => run.end = index + 1,
+            _ => runs.push(index..index + 1),
+        }
+    }
+    runs
+}
+
+#[test]
+fn should_coalesce_node_reads() {
+    assert_eq!(coalesce(&[]), vec![]);
+    assert_eq!(coalesce(&[10, 3, 4, 3]), vec![3..5, 10..11]);
+    assert_eq!(coalesce(&[0, 5, 6, 20]), vec![0..7, 20..21]);
+}
+
 #[test]
 fn should_detect_zeroes() {
     let nums = vec![0; 10];
@@ -31,7 +31,7 @@ impl Node {
         Self {
             index,
             hash,
-            length: length as u64,
+            length,
             parent: flat_tree::parent(index),
             data: Some(Vec::with_capacity(0)),
         }
@@ -67,7 +67,7 @@ impl Node {
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
         let mut writer = Vec::with_capacity(40);
         writer.extend_from_slice(&self.hash);
-        writer.write_u64::<BigEndian>(self.length as u64)?;
+        writer.write_u64::<BigEndian>(self.length)?;
         Ok(writer)
     }
 }
@@ -85,7 +85,7 @@ impl NodeTrait for Node {
 
     #[inline]
     fn len(&self) -> u64 {
-        self.length as u64
+        self.length
     }
 
     #[inline]
@@ -120,7 +120,7 @@ impl Display for Node {
 
 impl PartialOrd for Node {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.index.cmp(&other.index))
+        Some(self.cmp(other))
     }
 }
 
@@ -141,7 +141,7 @@ impl From<NodeParts<Hash>> for Node {
         Node {
             index: partial.index(),
             parent: partial.parent,
-            length: partial.len() as u64,
+            length: partial.len(),
             hash: parts.hash().as_bytes().into(),
             data,
         }
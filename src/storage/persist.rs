@@ -4,6 +4,7 @@ use random_access_storage::RandomAccess;
 use std::fmt::Debug;
 
 /// Persist data to a `Storage` instance.
+#[allow(dead_code)]
 pub trait Persist<T>
 where
     T: RandomAccess + Debug,
@@ -0,0 +1,72 @@
+//! Records of corrupted blocks, kept for debugging storage bugs.
+
+/// Length of a record before the data: index, expected hash and data length.
+pub(crate) const RECORD_HEADER_LEN: usize = 8 + 32 + 8;
+
+/// The bytes of a block that failed to verify, moved aside by `Feed::audit()`
+/// or `Feed::get()` when quarantine is enabled, see
+/// `FeedBuilder::quarantine()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuarantinedBlock {
+    /// Index of the block.
+    pub index: u64,
+    /// Hash of the block, as stored in the tree.
+    pub expected_hash: Vec<u8>,
+    /// The corrupted data, as read from the data store.
+    pub data: Vec<u8>,
+}
+
+impl QuarantinedBlock {
+    /// Get the length of the encoded record.
+    pub(crate) fn encoded_len(&self) -> u64 {
+        (RECORD_HEADER_LEN + self.data.len()) as u64
+    }
+
+    /// Encode the block as a record of the quarantine store.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(RECORD_HEADER_LEN + self.data.len());
+        buf.extend_from_slice(&self.index.to_be_bytes());
+        buf.extend_from_slice(&self.expected_hash);
+        buf.extend_from_slice(&(self.data.len() as u64).to_be_bytes());
+        buf.extend_from_slice(&self.data);
+        buf
+    }
+
+    /// Decode the records of the quarantine store. A record cut short by a
+    /// crash is ignored.
+    pub(crate) fn decode_all(mut buf: &[u8]) -> Vec<Self> {
+        let mut blocks = vec![];
+        while buf.len() >= RECORD_HEADER_LEN {
+            let len = super::read_u64(&buf[40..48]) as usize;
+            if buf.len() - RECORD_HEADER_LEN < len {
+                break;
+            }
+            blocks.push(Self {
+                index: super::read_u64(&buf[..8]),
+                expected_hash: buf[8..40].to_vec(),
+                data: buf[RECORD_HEADER_LEN..RECORD_HEADER_LEN + len].to_vec(),
+            });
+            buf = &buf[RECORD_HEADER_LEN + len..];
+        }
+        blocks
+    }
+}
+
+#[test]
+fn should_encode_records() {
+    let a = QuarantinedBlock {
+        index: 3,
+        expected_hash: vec![1; 32],
+        data: b"yello".to_vec(),
+    };
+    let b = QuarantinedBlock {
+        index: 9,
+        expected_hash: vec![2; 32],
+        data: vec![],
+    };
+    let mut buf = a.encode();
+    buf.extend(b.encode());
+    assert_eq!(QuarantinedBlock::decode_all(&buf), vec![a.clone(), b]);
+    let cut = a.encode().len() + 20;
+    assert_eq!(QuarantinedBlock::decode_all(&buf[..cut]), vec![a]);
+}
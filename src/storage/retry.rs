@@ -0,0 +1,188 @@
+//! Retry transient storage errors with exponential backoff.
+
+use async_std::task;
+use rand::Rng;
+use random_access_storage::RandomAccess;
+use std::error::Error;
+use std::fmt::Debug;
+use std::io;
+use std::time::Duration;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Configuration for `RetryingStorage`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of retries for a single operation.
+    pub max_retries: u32,
+    /// Maximum number of retries over the lifetime of the store. `None` means
+    /// unlimited.
+    pub budget: Option<u64>,
+    /// Delay before the first retry. Doubled after every attempt.
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between retries.
+    pub max_backoff: Duration,
+    /// Randomize delays to avoid retrying in lockstep with other clients.
+    pub jitter: bool,
+    /// Decide whether an error is transient and the operation can be retried.
+    pub is_retryable: fn(&(dyn Error + 'static)) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            budget: None,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(5),
+            jitter: true,
+            is_retryable,
+        }
+    }
+}
+
+/// Default error classification: IO errors that are typically caused by a
+/// flaky connection are retryable, everything else is fatal.
+pub fn is_retryable(err: &(dyn Error + 'static)) -> bool {
+    match err.downcast_ref::<io::Error>() {
+        Some(err) => matches!(
+            err.kind(),
+            io::ErrorKind::Interrupted
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::WouldBlock
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::BrokenPipe
+        ),
+        None => false,
+    }
+}
+
+/// Wrap a storage backend, retrying transient errors according to a
+/// `RetryPolicy`.
+///
+/// A different policy can be used per store by wrapping each instance
+/// returned from the `Storage::new()` callback separately.
+#[derive(Debug)]
+pub struct RetryingStorage<T> {
+    inner: T,
+    policy: RetryPolicy,
+    retries: u64,
+}
+
+impl<T> RetryingStorage<T> {
+    /// Create a new instance.
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            retries: 0,
+        }
+    }
+
+    /// Total number of retries performed so far.
+    pub fn retries(&self) -> u64 {
+        self.retries
+    }
+
+    /// Access the wrapped store.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Unwrap the wrapped store.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Check if the error can be retried, and wait before the retry if so.
+    async fn backoff(&mut self, attempt: u32, err: &BoxError) -> bool {
+        if attempt >= self.policy.max_retries || !(self.policy.is_retryable)(err.as_ref()) {
+            return false;
+        }
+        if let Some(budget) = self.policy.budget {
+            if self.retries >= budget {
+                return false;
+            }
+        }
+        self.retries += 1;
+
+        let delay = self
+            .policy
+            .initial_backoff
+            .checked_mul(1 << attempt.min(31))
+            .unwrap_or(self.policy.max_backoff)
+            .min(self.policy.max_backoff);
+        let delay = if self.policy.jitter {
+            delay.mul_f64(rand::thread_rng().gen_range(0.5, 1.0))
+        } else {
+            delay
+        };
+        task::sleep(delay).await;
+        true
+    }
+}
+
+macro_rules! retry {
+    ($self:ident, $op:expr) => {{
+        let mut attempt = 0;
+        loop {
+            match $op.await {
+                Ok(value) => break Ok(value),
+                Err(err) => {
+                    if !$self.backoff(attempt, &err).await {
+                        break Err(err);
+                    }
+                    attempt += 1;
+                }
+            }
+        }
+    }};
+}
+
+#[async_trait::async_trait]
+impl<T> RandomAccess for RetryingStorage<T>
+where
+    T: RandomAccess<Error = BoxError> + Debug + Send + Sync,
+{
+    type Error = BoxError;
+
+    async fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), Self::Error> {
+        retry!(self, self.inner.write(offset, data))
+    }
+
+    async fn read(&mut self, offset: u64, length: u64) -> Result<Vec<u8>, Self::Error> {
+        retry!(self, self.inner.read(offset, length))
+    }
+
+    // Not retried: the writer may already have received part of the data.
+    async fn read_to_writer(
+        &mut self,
+        offset: u64,
+        length: u64,
+        buf: &mut (impl futures::io::AsyncWrite + Send),
+    ) -> Result<(), Self::Error> {
+        self.inner.read_to_writer(offset, length, buf).await
+    }
+
+    async fn del(&mut self, offset: u64, length: u64) -> Result<(), Self::Error> {
+        retry!(self, self.inner.del(offset, length))
+    }
+
+    async fn truncate(&mut self, length: u64) -> Result<(), Self::Error> {
+        retry!(self, self.inner.truncate(length))
+    }
+
+    async fn len(&self) -> Result<u64, Self::Error> {
+        self.inner.len().await
+    }
+
+    async fn is_empty(&mut self) -> Result<bool, Self::Error> {
+        retry!(self, self.inner.is_empty())
+    }
+
+    async fn sync_all(&mut self) -> Result<(), Self::Error> {
+        retry!(self, self.inner.sync_all())
+    }
+}
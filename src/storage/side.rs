@@ -0,0 +1,119 @@
+//! Stores beyond the SLEEP layout shared with the JavaScript implementation,
+//! opened on first use so feeds that never use them don't create them.
+
+use super::Store;
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use random_access_storage::RandomAccess;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+
+/// Callback creating a store, as passed to `Storage::new()`.
+pub(crate) type Create<T> = Box<dyn Fn(Store) -> BoxFuture<'static, Result<T>> + Send + Sync>;
+
+/// Callback checking whether a store was created before, so reading it
+/// doesn't create it.
+pub(crate) type Exists = Box<dyn Fn(Store) -> bool + Send + Sync>;
+
+/// The side stores of a `Storage`, opened as they are used.
+pub(crate) struct SideStores<T> {
+    create: Create<T>,
+    exists: Option<Exists>,
+    open: HashMap<Store, T>,
+}
+
+impl<T> Debug for SideStores<T>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SideStores")
+            .field("open", &self.open)
+            .finish()
+    }
+}
+
+impl<T> SideStores<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    /// Create a new instance. Without `exists`, reading a store opens it.
+    pub(crate) fn new(create: Create<T>, exists: Option<Exists>) -> Self {
+        Self {
+            create,
+            exists,
+            open: HashMap::new(),
+        }
+    }
+
+    /// Get a store if it was created, opening it if needed.
+    async fn get(&mut self, store: Store) -> Result<Option<&mut T>> {
+        if !self.open.contains_key(&store) {
+            if let Some(exists) = &self.exists {
+                if !exists(store) {
+                    return Ok(None);
+                }
+            }
+            let instance = (self.create)(store).await?;
+            self.open.insert(store, instance);
+        }
+        Ok(self.open.get_mut(&store))
+    }
+
+    /// Get a store, creating it if needed.
+    async fn create(&mut self, store: Store) -> Result<&mut T> {
+        if !self.open.contains_key(&store) {
+            let instance = (self.create)(store).await?;
+            self.open.insert(store, instance);
+        }
+        Ok(self.open.get_mut(&store).expect("store was just opened"))
+    }
+
+    /// Check whether a store was created.
+    pub(crate) fn exists(&self, store: Store) -> bool {
+        self.open.contains_key(&store) || matches!(&self.exists, Some(exists) if exists(store))
+    }
+
+    /// Get the length of a store. Stores never created are empty.
+    pub(crate) async fn len(&mut self, store: Store) -> Result<u64> {
+        match self.get(store).await? {
+            Some(instance) => instance.len().await.map_err(|e| anyhow!(e)),
+            None => Ok(0),
+        }
+    }
+
+    /// Read `len` bytes at `offset` from a store.
+    pub(crate) async fn read(&mut self, store: Store, offset: u64, len: u64) -> Result<Vec<u8>> {
+        match self.get(store).await? {
+            Some(instance) => instance.read(offset, len).await.map_err(|e| anyhow!(e)),
+            None => Err(anyhow!("Nothing was written to the {:?} store", store)),
+        }
+    }
+
+    /// Write `data` at `offset` to a store, creating it if needed.
+    pub(crate) async fn write(&mut self, store: Store, offset: u64, data: &[u8]) -> Result<()> {
+        self.create(store)
+            .await?
+            .write(offset, data)
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    /// Flush a store to its backing medium, if it was opened.
+    pub(crate) async fn sync(&mut self, store: Store) -> Result<()> {
+        match self.open.get_mut(&store) {
+            Some(instance) => instance.sync_all().await.map_err(|e| anyhow!(e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Iterate over the stores opened so far.
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (&Store, &mut T)> {
+        self.open.iter_mut()
+    }
+
+    /// Add a store opened elsewhere.
+    pub(crate) fn insert(&mut self, store: Store, instance: T) {
+        self.open.insert(store, instance);
+    }
+}
@@ -0,0 +1,59 @@
+//! Storing large blocks received in chunks.
+
+use crate::crypto::Hash;
+use crate::feed::Feed;
+use anyhow::{bail, ensure, Result};
+use futures::io::{AsyncRead, AsyncReadExt};
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
+
+/// Size of the chunks read by `Feed::put_reader()`.
+const CHUNK_LEN: usize = 64 * 1024;
+
+impl<T> Feed<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    /// Store the data of the block at `index` read from `reader`, hashing it
+    /// as it arrives and checking it against the block's tree node before
+    /// anything is written to the data store.
+    ///
+    /// The tree node must already be stored, such as by `.put()` with a
+    /// proof made with `include_hash` and no data. Its length bounds the
+    /// bytes buffered: reading stops with an error as soon as the reader
+    /// sends more than that, or ends short of it.
+    pub async fn put_reader<R>(&mut self, index: u64, mut reader: R) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+    {
+        ensure!(
+            self.tree.get(2 * index),
+            "The tree node of block {} must be stored before its data",
+            index
+        );
+        let node = self.storage.get_node(2 * index).await?;
+        self.ensure_space(node.length)?;
+        let mut hasher = Hash::leaf_hasher(node.length);
+        let mut data = Vec::with_capacity(node.length as usize);
+        let mut chunk = vec![0; CHUNK_LEN];
+        loop {
+            let read = reader.read(&mut chunk).await?;
+            if read == 0 {
+                break;
+            }
+            if (data.len() + read) as u64 > node.length {
+                self.verification_failure();
+                bail!("Block {} is longer than its {} bytes", index, node.length);
+            }
+            hasher.update(&chunk[..read]);
+            data.extend_from_slice(&chunk[..read]);
+        }
+        if data.len() as u64 != node.length
+            || Hash::from_hasher(hasher).as_bytes() != &node.hash[..]
+        {
+            self.verification_failure();
+            bail!("Block {} does not match its tree node", index);
+        }
+        self.write(index, Some(&data), &[], None).await
+    }
+}
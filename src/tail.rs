@@ -0,0 +1,72 @@
+//! Following a feed: its stored blocks, then the ones appended later.
+
+use crate::feed::Feed;
+use crate::watch::Watch;
+use anyhow::Result;
+use async_std::sync::Mutex;
+use futures::stream::{self, Stream, StreamExt};
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
+use std::sync::{Arc, Weak};
+
+/// Position of a tail in its feed.
+struct Tail<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug,
+{
+    feed: Weak<Mutex<Feed<T>>>,
+    next: u64,
+    watch: Option<Watch>,
+}
+
+impl<T> Feed<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send + 'static,
+{
+    /// Stream `(index, data)` for every block from index `from`, first the
+    /// ones already in the feed, then each one as it is appended or
+    /// downloaded, like `tail -f`.
+    ///
+    /// The feed is only locked while a block is read. Blocks below the
+    /// length of the feed that are not stored locally when the tail reaches
+    /// them are skipped. The stream ends once every other reference to the
+    /// feed is dropped.
+    pub fn tail(feed: &Arc<Mutex<Self>>, from: u64) -> impl Stream<Item = Result<(u64, Vec<u8>)>> {
+        let tail = Tail {
+            feed: Arc::downgrade(feed),
+            next: from,
+            watch: None,
+        };
+        stream::unfold(tail, next_block)
+    }
+}
+
+/// Read the next stored block, waiting for the feed to grow if the tail
+/// reached its end.
+async fn next_block<T>(mut tail: Tail<T>) -> Option<(Result<(u64, Vec<u8>)>, Tail<T>)>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    loop {
+        // Only hold the feed while reading, so dropping it ends the watch.
+        {
+            let feed = tail.feed.upgrade()?;
+            let mut feed = feed.lock().await;
+            // Subscribe while holding the lock, so no append is missed
+            // between reading the length and waiting for it to change.
+            if tail.watch.is_none() {
+                tail.watch = Some(feed.watch());
+            }
+            while tail.next < feed.len() {
+                let index = tail.next;
+                tail.next += 1;
+                match feed.get(index).await {
+                    Ok(Some(data)) => return Some((Ok((index, data)), tail)),
+                    Ok(None) => continue,
+                    Err(err) => return Some((Err(err), tail)),
+                }
+            }
+        }
+        tail.watch.as_mut()?.next().await?;
+    }
+}
@@ -0,0 +1,238 @@
+//! Metrics recorded through the `metrics` crate when the `metrics` feature
+//! is enabled. Without it, recording is a no-op.
+//!
+//! Per-stage timings of `Feed::append` are available regardless, see
+//! [`AppendTimings`], as are snapshots of a feed's activity, see
+//! [`FeedStats`].
+
+use crate::proof_cache::ProofCacheStats;
+use std::ops::AddAssign;
+use std::time::{Duration, Instant};
+
+/// Number of feeds served by gateways.
+pub const GATEWAY_FEEDS: &str = "hypercore_gateway_feeds";
+/// Number of blocks served by gateways.
+pub const GATEWAY_BLOCKS: &str = "hypercore_gateway_blocks_total";
+/// Number of block bytes served by gateways.
+pub const GATEWAY_BYTES: &str = "hypercore_gateway_bytes_total";
+/// Number of signatures, checksums and hashes that failed to verify.
+pub const VERIFICATION_FAILURES: &str = "hypercore_verification_failures_total";
+
+/// Record a signature, checksum or hash that failed to verify.
+pub(crate) fn verification_failure() {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(VERIFICATION_FAILURES).increment(1);
+}
+
+/// Record a feed being added to a gateway.
+#[cfg(feature = "gateway")]
+pub(crate) fn gateway_feed_added() {
+    #[cfg(feature = "metrics")]
+    metrics::gauge!(GATEWAY_FEEDS).increment(1.0);
+}
+
+/// Record blocks served by a gateway.
+#[cfg(feature = "gateway")]
+pub(crate) fn gateway_served(blocks: u64, bytes: u64) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::counter!(GATEWAY_BLOCKS).increment(blocks);
+        metrics::counter!(GATEWAY_BYTES).increment(bytes);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (blocks, bytes);
+}
+
+/// Time spent in each stage of `Feed::append`, summed over the appends since
+/// `Feed::record_append_timings()` was called. Each store is timed while it
+/// is written; staging the writes and updating in-memory state are not
+/// timed.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AppendTimings {
+    /// Number of blocks appended.
+    pub appends: u64,
+    /// Hashing the block and the new roots.
+    pub hashing: Duration,
+    /// Writing the block to the data store, and when it was stored and when
+    /// it expires if those are recorded.
+    pub data: Duration,
+    /// Signing the new roots.
+    pub signing: Duration,
+    /// Writing the checksum of the block, if checksums are enabled.
+    pub checksums: Duration,
+    /// Writing the new tree nodes.
+    pub tree: Duration,
+    /// Writing the signature.
+    pub signatures: Duration,
+    /// Writing the bitfield.
+    pub bitfield: Duration,
+}
+
+impl AppendTimings {
+    /// Get the time spent in all stages together.
+    pub fn total(&self) -> Duration {
+        self.hashing
+            + self.data
+            + self.signing
+            + self.checksums
+            + self.tree
+            + self.signatures
+            + self.bitfield
+    }
+}
+
+impl AddAssign for AppendTimings {
+    fn add_assign(&mut self, other: Self) {
+        self.appends += other.appends;
+        self.hashing += other.hashing;
+        self.data += other.data;
+        self.signing += other.signing;
+        self.checksums += other.checksums;
+        self.tree += other.tree;
+        self.signatures += other.signatures;
+        self.bitfield += other.bitfield;
+    }
+}
+
+/// Measures the time between consecutive laps.
+#[derive(Debug)]
+pub(crate) struct Stopwatch(Instant);
+
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self(Instant::now())
+    }
+
+    /// Get the time since the last lap, and start the next one.
+    pub(crate) fn lap(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now - self.0;
+        self.0 = now;
+        elapsed
+    }
+}
+
+/// Activity counters of a feed, sampled by `Feed::stats()`.
+#[derive(Debug)]
+pub(crate) struct Counters {
+    pub(crate) appended_blocks: u64,
+    pub(crate) read_blocks: u64,
+    pub(crate) read_bytes: u64,
+    pub(crate) verification_failures: u64,
+    /// When the previous sample was taken, and the blocks appended and read
+    /// by then.
+    sampled: (Instant, u64, u64),
+}
+
+impl Default for Counters {
+    fn default() -> Self {
+        Self {
+            appended_blocks: 0,
+            read_blocks: 0,
+            read_bytes: 0,
+            verification_failures: 0,
+            sampled: (Instant::now(), 0, 0),
+        }
+    }
+}
+
+impl Counters {
+    /// Get the append and read rates in blocks per second since the previous
+    /// sample, and the time since then, starting the next sample.
+    pub(crate) fn sample(&mut self) -> (f64, f64, Duration) {
+        let (at, appended, read) = self.sampled;
+        let now = Instant::now();
+        let interval = now - at;
+        let secs = interval.as_secs_f64();
+        let rate = |count: u64| match secs {
+            secs if secs > 0.0 => count as f64 / secs,
+            _ => 0.0,
+        };
+        let rates = (
+            rate(self.appended_blocks - appended),
+            rate(self.read_blocks - read),
+        );
+        self.sampled = (now, self.appended_blocks, self.read_blocks);
+        (rates.0, rates.1, interval)
+    }
+}
+
+/// Snapshot of the state and activity of a feed, returned by
+/// `Feed::stats()`.
+///
+/// Counters are totals since the feed was opened. Rates are averages since
+/// the previous snapshot, so a dashboard polling on an interval gets the
+/// rate over that interval.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedStats {
+    /// Number of blocks in the feed.
+    pub length: u64,
+    /// Number of bytes in the blocks of the feed.
+    pub byte_length: u64,
+    /// Number of bytes in all stores, including the tree, signatures and
+    /// bitfields.
+    pub stored_bytes: u64,
+    /// Number of blocks appended.
+    pub appended_blocks: u64,
+    /// Number of blocks read with `.get()`.
+    pub read_blocks: u64,
+    /// Number of bytes read with `.get()`.
+    pub read_bytes: u64,
+    /// Number of signatures, checksums and hashes that failed to verify.
+    pub verification_failures: u64,
+    /// Blocks appended per second since the previous snapshot.
+    pub append_rate: f64,
+    /// Blocks read per second since the previous snapshot.
+    pub read_rate: f64,
+    /// Time since the previous snapshot, or since the feed was opened.
+    pub interval: Duration,
+    /// Hit rate statistics of the proof cache, if enabled.
+    pub proof_cache: Option<ProofCacheStats>,
+    /// Number of connected peers.
+    pub peers: usize,
+}
+
+impl FeedStats {
+    /// Get the bytes stored per byte of block data, or 0 for an empty feed.
+    pub fn overhead(&self) -> f64 {
+        match self.byte_length {
+            0 => 0.0,
+            byte_length => self.stored_bytes as f64 / byte_length as f64,
+        }
+    }
+
+    /// Encode the snapshot as JSON, for dashboards.
+    pub fn to_json(&self) -> String {
+        let proof_cache = match &self.proof_cache {
+            Some(cache) => format!(
+                "{{\"hits\":{},\"misses\":{},\"entries\":{},\"hitRate\":{}}}",
+                cache.hits,
+                cache.misses,
+                cache.entries,
+                cache.hit_rate()
+            ),
+            None => "null".into(),
+        };
+        format!(
+            concat!(
+                "{{\"length\":{},\"byteLength\":{},\"storedBytes\":{},\"overhead\":{},",
+                "\"appendedBlocks\":{},\"readBlocks\":{},\"readBytes\":{},",
+                "\"verificationFailures\":{},\"appendRate\":{},\"readRate\":{},",
+                "\"intervalMs\":{},\"proofCache\":{},\"peers\":{}}}"
+            ),
+            self.length,
+            self.byte_length,
+            self.stored_bytes,
+            self.overhead(),
+            self.appended_blocks,
+            self.read_blocks,
+            self.read_bytes,
+            self.verification_failures,
+            self.append_rate,
+            self.read_rate,
+            self.interval.as_millis(),
+            proof_cache,
+            self.peers
+        )
+    }
+}
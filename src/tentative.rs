@@ -0,0 +1,39 @@
+//! Blocks stored before the signature proving them was verified.
+
+use crate::crypto::Signature;
+use crate::ranges::Ranges;
+
+/// A signature received in a proof, not verified yet.
+#[derive(Debug)]
+pub(crate) struct PendingSignature {
+    /// Length of the feed the signature covers.
+    pub(crate) length: u64,
+    /// Signed message: the hash of the roots at `length`, and the length.
+    pub(crate) message: Vec<u8>,
+    pub(crate) signature: Signature,
+}
+
+/// Blocks stored by `Feed::put()` with lazy signatures, see
+/// `FeedBuilder::lazy_signatures()`.
+#[derive(Debug)]
+pub(crate) struct Tentative {
+    /// Length and byte length of the feed before the first tentative block,
+    /// restored if a signature fails to verify.
+    pub(crate) confirmed: (u64, u64),
+    /// Signatures to verify, in the order they were received.
+    pub(crate) pending: Vec<PendingSignature>,
+    /// Blocks stored since the first pending signature.
+    pub(crate) blocks: Ranges,
+}
+
+impl Tentative {
+    /// Start tracking tentative blocks of a feed of `length` blocks and
+    /// `byte_length` bytes.
+    pub(crate) fn new(length: u64, byte_length: u64) -> Self {
+        Self {
+            confirmed: (length, byte_length),
+            pending: vec![],
+            blocks: Ranges::new(),
+        }
+    }
+}
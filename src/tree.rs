@@ -0,0 +1,80 @@
+//! Flat tree math used to address the merkle tree of a feed.
+//!
+//! Nodes are numbered in-order: leaves are even, the block at `index` sits at
+//! `2 * index`, and parents are odd. The functions of the [`flat_tree`] crate
+//! are re-exported as is, along with iterators for walks that come up when
+//! building proofs or drawing the tree.
+//!
+//! ```rust
+//! use hypercore::tree;
+//!
+//! // A feed of 3 blocks is covered by the roots 1 (blocks 0 and 1) and 4.
+//! assert_eq!(tree::roots(3).collect::<Vec<_>>(), vec![1, 4]);
+//! // The uncles needed to verify block 0 against a feed of 4 blocks.
+//! assert_eq!(tree::proof_path(0, 4).collect::<Vec<_>>(), vec![2, 5]);
+//! ```
+
+pub use flat_tree::{
+    children, depth, full_roots, index, left_child, left_span, offset, parent, right_child,
+    right_span, sibling, spans, uncle, Iterator as TreeIterator,
+};
+
+/// Tree index of the block at `index`.
+#[inline]
+pub fn leaf(index: u64) -> u64 {
+    2 * index
+}
+
+/// Roots of a feed of `length` blocks, from left to right.
+pub fn roots(length: u64) -> impl Iterator<Item = u64> {
+    let mut roots = Vec::new();
+    full_roots(leaf(length), &mut roots);
+    roots.into_iter()
+}
+
+/// Ancestors of a node, from its parent upwards. The iterator is unbounded,
+/// so callers should stop it, for example with `take_while`.
+pub fn ancestors(index: u64) -> impl Iterator<Item = u64> {
+    std::iter::successors(Some(parent(index)), |&index| Some(parent(index)))
+}
+
+/// Tree indexes of the leaves below a node, from left to right.
+pub fn leaves(index: u64) -> impl Iterator<Item = u64> {
+    (left_span(index)..=right_span(index)).step_by(2)
+}
+
+/// Siblings on the path from the block at `index` up to the root covering
+/// it in a feed of `length` blocks. Together with the other roots, these are
+/// the nodes a proof of the block against that length contains.
+pub fn proof_path(index: u64, length: u64) -> impl Iterator<Item = u64> {
+    let head = leaf(length);
+    std::iter::successors(Some(leaf(index)), |&index| Some(parent(index)))
+        .take_while(move |&index| index < head && right_span(parent(index)) < head)
+        .map(sibling)
+}
+
+#[test]
+fn should_iterate_roots() {
+    assert_eq!(roots(0).count(), 0);
+    assert_eq!(roots(1).collect::<Vec<_>>(), vec![0]);
+    assert_eq!(roots(4).collect::<Vec<_>>(), vec![3]);
+    assert_eq!(roots(7).collect::<Vec<_>>(), vec![3, 9, 12]);
+}
+
+#[test]
+fn should_iterate_ancestors_and_leaves() {
+    assert_eq!(ancestors(0).take(3).collect::<Vec<_>>(), vec![1, 3, 7]);
+    assert_eq!(ancestors(10).take(2).collect::<Vec<_>>(), vec![9, 11]);
+    assert_eq!(leaves(3).collect::<Vec<_>>(), vec![0, 2, 4, 6]);
+    assert_eq!(leaves(4).collect::<Vec<_>>(), vec![4]);
+}
+
+#[test]
+fn should_walk_proof_path() {
+    assert_eq!(proof_path(0, 1).count(), 0);
+    assert_eq!(proof_path(0, 4).collect::<Vec<_>>(), vec![2, 5]);
+    assert_eq!(proof_path(5, 8).collect::<Vec<_>>(), vec![8, 13, 3]);
+    // Block 4 of a feed of 5 blocks is a root on its own.
+    assert_eq!(proof_path(4, 5).count(), 0);
+    assert_eq!(proof_path(4, 6).collect::<Vec<_>>(), vec![10]);
+}
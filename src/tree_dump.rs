@@ -0,0 +1,119 @@
+//! Rendering the merkle tree of a feed, to debug verification failures.
+
+use crate::crypto::to_hex;
+use crate::feed::Feed;
+use crate::storage::Node;
+use crate::tree;
+use anyhow::Result;
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
+use std::io::Write;
+use std::ops::Range;
+
+/// A node of the tree, with its stored contents if present.
+struct DumpedNode {
+    index: u64,
+    root: bool,
+    node: Option<Node>,
+}
+
+impl<T> Feed<T>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    /// Write the nodes of the tree covering only blocks in `range` to
+    /// `writer`, one node per line in flat-tree order, indented by depth so
+    /// the tree reads sideways with the leaves on the left:
+    ///
+    /// ```text
+    /// 0 leaf 8a05…c1 (5 bytes)
+    ///   1 root 9f3e…02 (10 bytes)
+    /// 2 leaf missing
+    /// ```
+    ///
+    /// Hashes are shortened here, but written in full. Nodes not in the
+    /// local tree are written as missing.
+    pub async fn dump_tree<W: Write>(&mut self, range: Range<u64>, writer: &mut W) -> Result<()> {
+        for dumped in self.dumped_nodes(range).await? {
+            let indent = "  ".repeat(tree::depth(dumped.index) as usize);
+            let kind = match (dumped.root, dumped.index % 2) {
+                (true, _) => "root",
+                (false, 0) => "leaf",
+                (false, _) => "parent",
+            };
+            write!(writer, "{}{} {} ", indent, dumped.index, kind)?;
+            match dumped.node {
+                Some(node) => writeln!(writer, "{} ({} bytes)", to_hex(&node.hash), node.length)?,
+                None => writeln!(writer, "missing")?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the nodes of the tree covering only blocks in `range` to
+    /// `writer` as JSON, like `.dump_tree()`: `{"length":3,"nodes":[{"index":0,
+    /// "depth":0,"root":false,"hash":"…","length":5}, …]}`. Missing nodes
+    /// have no hash or length.
+    pub async fn dump_tree_json<W: Write>(
+        &mut self,
+        range: Range<u64>,
+        writer: &mut W,
+    ) -> Result<()> {
+        write!(writer, "{{\"length\":{},\"nodes\":[", self.len())?;
+        for (i, dumped) in self.dumped_nodes(range).await?.into_iter().enumerate() {
+            if i > 0 {
+                write!(writer, ",")?;
+            }
+            write!(
+                writer,
+                "{{\"index\":{},\"depth\":{},\"root\":{}",
+                dumped.index,
+                tree::depth(dumped.index),
+                dumped.root
+            )?;
+            if let Some(node) = dumped.node {
+                write!(
+                    writer,
+                    ",\"hash\":\"{}\",\"length\":{}",
+                    to_hex(&node.hash),
+                    node.length
+                )?;
+            }
+            write!(writer, "}}")?;
+        }
+        write!(writer, "]}}")?;
+        Ok(())
+    }
+
+    /// Read the nodes covering only blocks in `range`, as far as they exist
+    /// in a feed of the current length.
+    async fn dumped_nodes(&mut self, range: Range<u64>) -> Result<Vec<DumpedNode>> {
+        let end = range.end.min(self.len());
+        if range.start >= end {
+            return Ok(vec![]);
+        }
+        let (first, last) = (tree::leaf(range.start), tree::leaf(end - 1));
+        let roots: Vec<u64> = tree::roots(self.len()).collect();
+        let indexes: Vec<u64> = (first..=last)
+            .filter(|index| tree::left_span(*index) >= first && tree::right_span(*index) <= last)
+            .collect();
+        let tree_bits = &mut self.tree;
+        let present: Vec<u64> = indexes
+            .iter()
+            .copied()
+            .filter(|index| tree_bits.get(*index))
+            .collect();
+        let mut nodes = self.storage.get_nodes(&present).await?.into_iter();
+        Ok(indexes
+            .into_iter()
+            .map(|index| DumpedNode {
+                index,
+                root: roots.contains(&index),
+                node: match present.binary_search(&index) {
+                    Ok(_) => nodes.next(),
+                    Err(_) => None,
+                },
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,127 @@
+//! `hyper://` URIs naming a feed and the options to open it with.
+
+use crate::crypto::{decode_public_key, encode_key, KeyFormat};
+use crate::feed_builder::FeedBuilder;
+use crate::storage::Storage;
+use anyhow::{bail, Result};
+use ed25519_dalek::PublicKey;
+use random_access_storage::RandomAccess;
+use std::fmt::{self, Debug};
+use std::str::FromStr;
+
+/// Scheme of feed URIs.
+const SCHEME: &str = "hyper://";
+
+/// A feed key and open options, as passed around by applications.
+///
+/// URIs look like `hyper://<key>?sparse=true`, with the public key encoded
+/// as z-base-32. Keys in any format `decode_key()` accepts, with or without
+/// the scheme, parse too. Unknown options are ignored, so URIs written by newer
+/// versions still open.
+///
+/// ```rust
+/// use hypercore::{generate_keypair, FeedUri};
+///
+/// let mut uri = FeedUri::new(generate_keypair().public);
+/// uri.sparse = true;
+/// let s = uri.to_string();
+/// assert!(s.starts_with("hyper://") && s.ends_with("?sparse=true"));
+/// assert_eq!(s.parse::<FeedUri>().unwrap(), uri);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedUri {
+    /// Public key of the feed.
+    pub public_key: PublicKey,
+    /// Only download the blocks the application asks for. Hypercore
+    /// doesn't download on its own, so this is for the application to
+    /// honour.
+    pub sparse: bool,
+    /// Store and check block checksums, see `FeedBuilder::checksums()`.
+    pub checksums: bool,
+    /// Quarantine corrupted blocks, see `FeedBuilder::quarantine()`.
+    pub quarantine: bool,
+    /// Verify every block read, see `FeedBuilder::verify_reads()`.
+    pub verify_reads: bool,
+}
+
+impl FeedUri {
+    /// Create a URI for the feed with `public_key`, with default options.
+    pub fn new(public_key: PublicKey) -> Self {
+        Self {
+            public_key,
+            sparse: false,
+            checksums: false,
+            quarantine: false,
+            verify_reads: false,
+        }
+    }
+
+    /// Create a builder for the feed, with the options of the URI applied.
+    pub fn builder<T>(&self, storage: Storage<T>) -> FeedBuilder<T>
+    where
+        T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug,
+    {
+        FeedBuilder::new(self.public_key, storage)
+            .checksums(self.checksums)
+            .quarantine(self.quarantine)
+            .verify_reads(self.verify_reads)
+    }
+}
+
+impl fmt::Display for FeedUri {
+    /// Format as `hyper://<z-base-32 key>`, followed by the options that
+    /// aren't at their default.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = encode_key(self.public_key.as_bytes(), KeyFormat::ZBase32, false);
+        write!(f, "{}{}", SCHEME, key)?;
+        let options = [
+            ("sparse", self.sparse),
+            ("checksums", self.checksums),
+            ("quarantine", self.quarantine),
+            ("verify_reads", self.verify_reads),
+        ];
+        let mut separator = '?';
+        for (name, _) in options.iter().filter(|(_, value)| *value) {
+            write!(f, "{}{}=true", separator, name)?;
+            separator = '&';
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for FeedUri {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let rest = match s.get(..SCHEME.len()) {
+            Some(scheme) if scheme.eq_ignore_ascii_case(SCHEME) => &s[SCHEME.len()..],
+            _ => s,
+        };
+        let (key, query) = match rest.split_once('?') {
+            Some((key, query)) => (key, Some(query)),
+            None => (rest, None),
+        };
+        let public_key = decode_public_key(key.trim_end_matches('/'))?;
+        let mut uri = FeedUri::new(public_key);
+        for pair in query.into_iter().flat_map(|query| query.split('&')) {
+            if pair.is_empty() {
+                continue;
+            }
+            let (name, value) = pair.split_once('=').unwrap_or((pair, "true"));
+            let option = match name {
+                "sparse" => &mut uri.sparse,
+                "checksums" => &mut uri.checksums,
+                "quarantine" => &mut uri.quarantine,
+                "verify_reads" => &mut uri.verify_reads,
+                _ => continue,
+            };
+            *option = match value {
+                "true" | "1" => true,
+                "false" | "0" => false,
+                value => bail!("Option {} must be true or false, got {:?}", name, value),
+            };
+        }
+        Ok(uri)
+    }
+}
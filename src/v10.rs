@@ -0,0 +1,443 @@
+//! Conversion between the SLEEP layout and the layout written by hypercore
+//! v10 and later.
+//!
+//! A v10 directory holds four files:
+//! - `oplog`: two 4096 byte header slots, each holding the same framed header
+//!   with the key pair and the signed tree length.
+//! - `tree`: 40 byte nodes stored at `index * 40`, each a little-endian size
+//!   followed by the hash. There is no file header.
+//! - `bitfield`: one bit per block, least significant bit first, in 4096
+//!   byte pages.
+//! - `data`: the blocks, concatenated.
+//!
+//! v10 hashes sizes as little-endian and signs a namespaced message, so the
+//! tree and signature are recomputed on conversion, which requires the
+//! secret key. Only feeds holding all of their blocks can be converted.
+
+use crate::crypto::{sign, verify, PublicKey, SecretKey, Signature};
+use crate::feed::Feed;
+use crate::storage::{atomic_write, crc32, Storage};
+use anyhow::{anyhow, bail, ensure, Result};
+use blake2_rfc::blake2b::Blake2b;
+use flat_tree as flat;
+use random_access_disk::RandomAccessDisk;
+use random_access_storage::RandomAccess;
+use std::convert::TryInto;
+use std::fmt::Debug;
+use std::fs;
+use std::path::Path;
+
+const LEAF_TYPE: u8 = 0;
+const PARENT_TYPE: u8 = 1;
+const ROOT_TYPE: u8 = 2;
+
+const NODE_LEN: usize = 40;
+const BITFIELD_PAGE_LEN: usize = 4096;
+const HEADER_SLOT_LEN: usize = 4096;
+const FRAME_LEN: usize = 8;
+
+/// Copy a feed into `dir` using the hypercore v10 layout.
+pub async fn export_v10<T, P>(feed: &mut Feed<T>, dir: P) -> Result<()>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+    P: AsRef<Path>,
+{
+    let dir = dir.as_ref();
+    let secret_key = match feed.secret_key() {
+        Some(secret_key) => SecretKey::from_bytes(secret_key.as_bytes())?,
+        None => bail!("no secret key, cannot sign the v10 tree."),
+    };
+    let length = feed.len();
+    ensure!(
+        feed.has_all(0..length),
+        "Only feeds holding all of their blocks can be exported"
+    );
+
+    let mut data = Vec::with_capacity(feed.byte_len() as usize);
+    let mut nodes: Vec<Option<(u64, [u8; 32])>> = vec![None; 2 * length as usize];
+    for index in 0..length {
+        let block = match feed.get(index).await? {
+            Some(block) => block,
+            None => bail!("Missing block {}", index),
+        };
+        let mut node_index = 2 * index;
+        nodes[node_index as usize] = Some((block.len() as u64, leaf_hash(&block)));
+        data.extend_from_slice(&block);
+
+        // Complete every parent whose right child this is.
+        while flat::sibling(node_index) < node_index {
+            let (left_size, left) = nodes[flat::sibling(node_index) as usize].unwrap();
+            let (right_size, right) = nodes[node_index as usize].unwrap();
+            node_index = flat::parent(node_index);
+            let size = left_size + right_size;
+            nodes[node_index as usize] = Some((size, parent_hash(size, &left, &right)));
+        }
+    }
+
+    let mut tree = vec![0; NODE_LEN * nodes.len().saturating_sub(1)];
+    for (index, node) in nodes.iter().enumerate() {
+        if let Some((size, hash)) = node {
+            let offset = index * NODE_LEN;
+            tree[offset..offset + 8].copy_from_slice(&size.to_le_bytes());
+            tree[offset + 8..offset + NODE_LEN].copy_from_slice(hash);
+        }
+    }
+
+    let mut bitfield = vec![0; pages(length) * BITFIELD_PAGE_LEN];
+    for index in 0..length as usize {
+        bitfield[index / 8] |= 1 << (index % 8);
+    }
+
+    let root_hash = tree_hash(&roots(&nodes, length));
+    let signature = sign(
+        feed.public_key(),
+        &secret_key,
+        &tree_signable(&root_hash, length, 0),
+    );
+
+    let header = OplogHeader {
+        public_key: *feed.public_key(),
+        secret_key: Some(secret_key),
+        length,
+        root_hash,
+        signature: Some(signature),
+    };
+    let frame = header.encode_frame()?;
+    let mut oplog = vec![0; 2 * HEADER_SLOT_LEN];
+    oplog[..frame.len()].copy_from_slice(&frame);
+    oplog[HEADER_SLOT_LEN..HEADER_SLOT_LEN + frame.len()].copy_from_slice(&frame);
+
+    // The header goes last, so an interrupted export has no valid oplog.
+    fs::create_dir_all(dir)?;
+    atomic_write(&dir.join("tree"), &tree)?;
+    atomic_write(&dir.join("bitfield"), &bitfield)?;
+    atomic_write(&dir.join("data"), &data)?;
+    atomic_write(&dir.join("oplog"), &oplog)
+}
+
+/// Convert the hypercore v10 directory at `src` into a new feed at `dst`,
+/// verifying its tree and signature on the way.
+pub async fn import_v10<P, Q>(src: P, dst: Q) -> Result<Feed<RandomAccessDisk>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    let header = OplogHeader::decode_frame(&fs::read(src.join("oplog"))?)?;
+    let secret_key = match header.secret_key {
+        Some(secret_key) => secret_key,
+        None => bail!("no secret key in the v10 oplog, cannot sign the feed."),
+    };
+    let length = header.length;
+
+    let tree = fs::read(src.join("tree"))?;
+    let bitfield = fs::read(src.join("bitfield"))?;
+    let data = fs::read(src.join("data"))?;
+
+    let node_count = 2 * length as usize;
+    ensure!(
+        tree.len() >= NODE_LEN * node_count.saturating_sub(1),
+        "v10 tree is missing nodes"
+    );
+    let nodes: Vec<Option<(u64, [u8; 32])>> = (0..node_count)
+        .map(|index| {
+            let offset = index * NODE_LEN;
+            let buf = tree.get(offset..offset + NODE_LEN)?;
+            let size = u64::from_le_bytes(buf[..8].try_into().unwrap());
+            Some((size, buf[8..].try_into().unwrap()))
+        })
+        .collect();
+
+    let root_hash = tree_hash(&roots(&nodes, length));
+    ensure!(
+        root_hash == header.root_hash,
+        "v10 tree roots do not match the oplog"
+    );
+    verify(
+        &header.public_key,
+        &tree_signable(&root_hash, length, 0),
+        header.signature.as_ref(),
+    )?;
+
+    let mut blocks = Vec::with_capacity(length as usize);
+    let mut offset = 0;
+    for index in 0..length as usize {
+        let present = bitfield
+            .get(index / 8)
+            .is_some_and(|byte| byte & (1 << (index % 8)) != 0);
+        ensure!(present, "v10 feed is missing block {}", index);
+        let (size, hash) = nodes[2 * index].unwrap();
+        let end = offset + size as usize;
+        let block = data
+            .get(offset..end)
+            .ok_or_else(|| anyhow!("v10 data is missing block {}", index))?;
+        ensure!(
+            leaf_hash(block) == hash,
+            "v10 block {} does not match its tree node",
+            index
+        );
+        blocks.push(block);
+        offset = end;
+    }
+
+    let mut storage = Storage::new_disk(dst).await?;
+    ensure!(
+        storage.signature_count().await? == 0,
+        format!("A feed already exists at {:?}", dst)
+    );
+    storage.write_public_key(&header.public_key).await?;
+    storage.write_secret_key(&secret_key).await?;
+    drop(storage);
+
+    let mut feed = Feed::open(dst).await?;
+    for block in blocks {
+        feed.append(block).await?;
+    }
+    Ok(feed)
+}
+
+/// The parts of the v10 oplog header needed to move a feed between layouts.
+#[derive(Debug)]
+struct OplogHeader {
+    public_key: PublicKey,
+    secret_key: Option<SecretKey>,
+    length: u64,
+    root_hash: [u8; 32],
+    signature: Option<Signature>,
+}
+
+impl OplogHeader {
+    /// Encode the header as an oplog entry, framed by its checksum and length.
+    fn encode_frame(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        write_uint(&mut buf, 0); // version
+        write_buffer(&mut buf, b"blake2b");
+        write_buffer(&mut buf, b"raw");
+        write_buffer(&mut buf, b"ed25519");
+        write_uint(&mut buf, 0); // user data
+        write_uint(&mut buf, 0); // fork
+        write_uint(&mut buf, self.length);
+        buf.extend_from_slice(&self.root_hash);
+        match &self.signature {
+            Some(signature) => write_buffer(&mut buf, &signature.to_bytes()),
+            None => write_buffer(&mut buf, &[]),
+        }
+        buf.extend_from_slice(self.public_key.as_bytes());
+        match &self.secret_key {
+            Some(secret_key) => {
+                let keypair = [&secret_key.as_bytes()[..], self.public_key.as_bytes()].concat();
+                write_buffer(&mut buf, &keypair);
+            }
+            None => write_buffer(&mut buf, &[]),
+        }
+        write_uint(&mut buf, 0); // reorg hints
+
+        ensure!(
+            buf.len() + FRAME_LEN <= HEADER_SLOT_LEN,
+            "v10 oplog header does not fit its slot"
+        );
+        // The low bits flag a header entry that is not partial.
+        let mut entry = ((buf.len() as u32) << 2 | 1).to_le_bytes().to_vec();
+        entry.extend_from_slice(&buf);
+        let mut frame = crc32(&entry).to_le_bytes().to_vec();
+        frame.extend_from_slice(&entry);
+        Ok(frame)
+    }
+
+    /// Decode the header from the first valid slot of an oplog.
+    fn decode_frame(oplog: &[u8]) -> Result<Self> {
+        let mut last_err = anyhow!("v10 oplog is too short");
+        for slot in 0..2 {
+            let start = slot * HEADER_SLOT_LEN;
+            match oplog.get(start..).map(Self::decode_slot) {
+                Some(Ok(header)) => return Ok(header),
+                Some(Err(err)) => last_err = err,
+                None => break,
+            }
+        }
+        Err(last_err)
+    }
+
+    fn decode_slot(buf: &[u8]) -> Result<Self> {
+        ensure!(buf.len() >= FRAME_LEN, "v10 oplog is too short");
+        let checksum = u32::from_le_bytes(buf[..4].try_into().unwrap());
+        let bits = u32::from_le_bytes(buf[4..8].try_into().unwrap());
+        let len = (bits >> 2) as usize;
+        ensure!(bits & 2 == 0, "v10 oplog header is partial");
+        ensure!(
+            buf.len() >= FRAME_LEN + len && crc32(&buf[4..FRAME_LEN + len]) == checksum,
+            "v10 oplog header checksum mismatch"
+        );
+
+        let mut reader = Reader::new(&buf[FRAME_LEN..FRAME_LEN + len]);
+        ensure!(reader.uint()? == 0, "unsupported v10 oplog version");
+        ensure!(reader.buffer()? == b"blake2b", "unsupported v10 tree hash");
+        reader.buffer()?; // bitfield type
+        ensure!(reader.buffer()? == b"ed25519", "unsupported v10 signer");
+        for _ in 0..reader.uint()? {
+            reader.buffer()?;
+            reader.buffer()?;
+        }
+        ensure!(reader.uint()? == 0, "forked v10 feeds are not supported");
+        let length = reader.uint()?;
+        let root_hash = reader.fixed32()?;
+        let signature = match reader.buffer()? {
+            [] => None,
+            bytes => Some(Signature::from_bytes(bytes)?),
+        };
+        let public_key = PublicKey::from_bytes(&reader.fixed32()?)?;
+        let secret_key = match reader.buffer()? {
+            [] => None,
+            bytes => {
+                ensure!(bytes.len() == 64, "v10 secret key should be 64 bytes");
+                Some(SecretKey::from_bytes(&bytes[..32])?)
+            }
+        };
+
+        Ok(Self {
+            public_key,
+            secret_key,
+            length,
+            root_hash,
+            signature,
+        })
+    }
+}
+
+/// Number of bitfield pages needed to hold `length` blocks.
+fn pages(length: u64) -> usize {
+    (length as usize).div_ceil(BITFIELD_PAGE_LEN * 8)
+}
+
+/// Collect the root nodes for `length` blocks as `(index, size, hash)`.
+fn roots(nodes: &[Option<(u64, [u8; 32])>], length: u64) -> Vec<(u64, u64, [u8; 32])> {
+    let mut indexes = vec![];
+    flat::full_roots(2 * length, &mut indexes);
+    indexes
+        .into_iter()
+        .filter_map(|index| {
+            let (size, hash) = nodes.get(index as usize).copied().flatten()?;
+            Some((index, size, hash))
+        })
+        .collect()
+}
+
+fn leaf_hash(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Blake2b::new(32);
+    hasher.update(&[LEAF_TYPE]);
+    hasher.update(&(data.len() as u64).to_le_bytes());
+    hasher.update(data);
+    to_array(hasher.finalize().as_bytes())
+}
+
+fn parent_hash(size: u64, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Blake2b::new(32);
+    hasher.update(&[PARENT_TYPE]);
+    hasher.update(&size.to_le_bytes());
+    hasher.update(left);
+    hasher.update(right);
+    to_array(hasher.finalize().as_bytes())
+}
+
+fn tree_hash(roots: &[(u64, u64, [u8; 32])]) -> [u8; 32] {
+    let mut hasher = Blake2b::new(32);
+    hasher.update(&[ROOT_TYPE]);
+    for (index, size, hash) in roots {
+        hasher.update(hash);
+        hasher.update(&index.to_le_bytes());
+        hasher.update(&size.to_le_bytes());
+    }
+    to_array(hasher.finalize().as_bytes())
+}
+
+/// The message v10 signs: the tree namespace, tree hash, length and fork.
+fn tree_signable(hash: &[u8; 32], length: u64, fork: u64) -> Vec<u8> {
+    let mut namespace = Blake2b::new(32);
+    namespace.update(b"hypercore");
+    let mut tree = Blake2b::new(32);
+    tree.update(namespace.finalize().as_bytes());
+    tree.update(&[0]);
+
+    let mut buf = tree.finalize().as_bytes().to_vec();
+    buf.extend_from_slice(hash);
+    buf.extend_from_slice(&length.to_le_bytes());
+    buf.extend_from_slice(&fork.to_le_bytes());
+    buf
+}
+
+fn to_array(bytes: &[u8]) -> [u8; 32] {
+    bytes.try_into().unwrap()
+}
+
+/// Append a compact-encoding unsigned integer.
+fn write_uint(buf: &mut Vec<u8>, value: u64) {
+    if value < 0xfd {
+        buf.push(value as u8);
+    } else if value <= 0xffff {
+        buf.push(0xfd);
+        buf.extend_from_slice(&(value as u16).to_le_bytes());
+    } else if value <= 0xffff_ffff {
+        buf.push(0xfe);
+        buf.extend_from_slice(&(value as u32).to_le_bytes());
+    } else {
+        buf.push(0xff);
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Append a compact-encoding length-prefixed buffer.
+fn write_buffer(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_uint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Cursor over a compact-encoded buffer.
+#[derive(Debug)]
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(len <= self.buf.len(), "unexpected end of v10 oplog header");
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self) -> Result<u64> {
+        Ok(match self.take(1)?[0] {
+            0xfd => u64::from(u16::from_le_bytes(self.take(2)?.try_into()?)),
+            0xfe => u64::from(u32::from_le_bytes(self.take(4)?.try_into()?)),
+            0xff => u64::from_le_bytes(self.take(8)?.try_into()?),
+            byte => u64::from(byte),
+        })
+    }
+
+    fn buffer(&mut self) -> Result<&'a [u8]> {
+        let len = self.uint()? as usize;
+        self.take(len)
+    }
+
+    fn fixed32(&mut self) -> Result<[u8; 32]> {
+        Ok(to_array(self.take(32)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uint_roundtrip() {
+        for value in &[0, 0xfc, 0xfd, 0xffff, 0x10000, 0xffff_ffff, u64::MAX] {
+            let mut buf = vec![];
+            write_uint(&mut buf, *value);
+            assert_eq!(Reader::new(&buf).uint().unwrap(), *value);
+        }
+    }
+}
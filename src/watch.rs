@@ -0,0 +1,111 @@
+//! Channel publishing the latest length of a feed.
+
+use futures::stream::Stream;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// State shared by the feed and its watchers.
+#[derive(Debug)]
+struct Shared {
+    /// Latest `(length, fork)`.
+    value: (u64, u64),
+    /// Bumped on every change, so watchers can tell what they've seen.
+    version: u64,
+    /// Whether the feed was dropped.
+    closed: bool,
+    wakers: Vec<Waker>,
+}
+
+/// Sending half, held by the feed.
+#[derive(Debug)]
+pub(crate) struct Sender {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Sender {
+    /// Create a channel holding `value`.
+    pub(crate) fn new(value: (u64, u64)) -> Self {
+        let shared = Shared {
+            value,
+            version: 0,
+            closed: false,
+            wakers: vec![],
+        };
+        Self {
+            shared: Arc::new(Mutex::new(shared)),
+        }
+    }
+
+    /// Publish `value`, waking the watchers if it changed.
+    pub(crate) fn send(&self, value: (u64, u64)) {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.value == value {
+            return;
+        }
+        shared.value = value;
+        shared.version += 1;
+        for waker in shared.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// Create a watcher that has seen the current value.
+    pub(crate) fn subscribe(&self) -> Watch {
+        let version = self.shared.lock().unwrap().version;
+        Watch {
+            shared: self.shared.clone(),
+            seen: version,
+        }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.closed = true;
+        for waker in shared.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// Watcher of the length of a feed, created by `Feed::watch()`.
+///
+/// As a `Stream`, it yields the latest `(length, fork)` whenever it changed
+/// since the last item. Changes in between are coalesced, so a slow
+/// consumer only sees the most recent value. The stream ends when the feed
+/// is dropped.
+#[derive(Debug, Clone)]
+pub struct Watch {
+    shared: Arc<Mutex<Shared>>,
+    seen: u64,
+}
+
+impl Watch {
+    /// Get the latest `(length, fork)`, marking it as seen.
+    pub fn borrow(&mut self) -> (u64, u64) {
+        let shared = self.shared.lock().unwrap();
+        self.seen = shared.version;
+        shared.value
+    }
+}
+
+impl Stream for Watch {
+    type Item = (u64, u64);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.version != self.seen {
+            let (version, value) = (shared.version, shared.value);
+            drop(shared);
+            self.seen = version;
+            Poll::Ready(Some(value))
+        } else if shared.closed {
+            Poll::Ready(None)
+        } else {
+            shared.wakers.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
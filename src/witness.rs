@@ -0,0 +1,95 @@
+//! Co-signatures of feed checkpoints by independent witnesses.
+
+use crate::crypto::{sign, verify, PublicKey, SecretKey, Signature};
+use anyhow::{ensure, Result};
+use ed25519_dalek::{PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+
+/// Domain separation for checkpoint signatures.
+const DOMAIN: &[u8] = b"hypercore witness";
+
+/// Length of an encoded witness.
+pub(crate) const WITNESS_LEN: usize = 8 + PUBLIC_KEY_LENGTH + SIGNATURE_LENGTH;
+
+/// A third party's signature over a checkpoint of a feed: its length and the
+/// hash of its roots at that length.
+///
+/// Feeds are append-only, so a witness of a checkpoint vouches for every
+/// shorter one too. A writer that forks the feed can't make a witness's
+/// signature cover both histories, so readers that require a witness they
+/// trust detect the fork. Witnesses are exchanged by the application, using
+/// `.encode()` and `.decode()`, and stored with `Feed::add_witness()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Witness {
+    /// Length of the feed at the checkpoint.
+    pub length: u64,
+    /// Public key of the witness.
+    pub public_key: PublicKey,
+    /// Signature of the checkpoint by the witness.
+    pub signature: Signature,
+}
+
+impl Witness {
+    /// Sign a checkpoint, as returned by `Feed::checkpoint()`.
+    pub fn sign(length: u64, checkpoint: &[u8], public: &PublicKey, secret: &SecretKey) -> Self {
+        Self {
+            length,
+            public_key: *public,
+            signature: sign(public, secret, checkpoint),
+        }
+    }
+
+    /// Verify the signature over a checkpoint.
+    pub fn verify(&self, checkpoint: &[u8]) -> Result<()> {
+        verify(&self.public_key, checkpoint, Some(&self.signature))
+    }
+
+    /// Encode the witness, to send it to other peers.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(WITNESS_LEN);
+        buf.extend_from_slice(&self.length.to_be_bytes());
+        buf.extend_from_slice(self.public_key.as_bytes());
+        buf.extend_from_slice(&self.signature.to_bytes());
+        buf
+    }
+
+    /// Decode a witness encoded by `.encode()`.
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        ensure!(
+            buf.len() == WITNESS_LEN,
+            "Witness must be {} bytes",
+            WITNESS_LEN
+        );
+        let mut length = [0; 8];
+        length.copy_from_slice(&buf[..8]);
+        let (public_key, signature) = buf[8..].split_at(PUBLIC_KEY_LENGTH);
+        Ok(Self {
+            length: u64::from_be_bytes(length),
+            public_key: PublicKey::from_bytes(public_key)?,
+            signature: Signature::from_bytes(signature)?,
+        })
+    }
+}
+
+/// Build the message witnesses sign for a checkpoint of the feed with
+/// `feed_key`, from the hash of its roots and its length.
+pub(crate) fn checkpoint(feed_key: &PublicKey, roots_hash: &[u8], length: u64) -> Vec<u8> {
+    [
+        DOMAIN,
+        feed_key.as_bytes(),
+        roots_hash,
+        &length.to_be_bytes(),
+    ]
+    .concat()
+}
+
+#[test]
+fn should_encode_witnesses() {
+    let keypair = crate::crypto::generate_keypair();
+    let checkpoint = checkpoint(&keypair.public, &[1; 32], 3);
+    let witness = Witness::sign(3, &checkpoint, &keypair.public, &keypair.secret);
+    let decoded = Witness::decode(&witness.encode()).unwrap();
+    assert_eq!(decoded, witness);
+    assert!(decoded.verify(&checkpoint).is_ok());
+    assert!(decoded.verify(&checkpoint[1..]).is_err());
+    assert!(Witness::decode(&witness.encode()[1..]).is_err());
+}
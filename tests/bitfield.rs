@@ -1,5 +1,3 @@
-use rand;
-
 use hypercore::bitfield::{Bitfield, Change::*};
 use rand::Rng;
 
@@ -7,15 +5,15 @@ use rand::Rng;
 fn set_and_get() {
     let mut b = Bitfield::new();
 
-    assert_eq!(b.get(0), false);
+    assert!(!b.get(0));
     assert_eq!(b.set(0, true), Changed);
     assert_eq!(b.set(0, true), Unchanged);
-    assert_eq!(b.get(0), true);
+    assert!(b.get(0));
 
-    assert_eq!(b.get(1_424_244), false);
+    assert!(!b.get(1_424_244));
     assert_eq!(b.set(1_424_244, true), Changed);
     assert_eq!(b.set(1_424_244, true), Unchanged);
-    assert_eq!(b.get(1_424_244), true);
+    assert!(b.get(1_424_244));
 }
 
 #[test]
@@ -25,19 +23,19 @@ fn set_and_get_tree() {
     {
         let tree = &mut b.tree;
 
-        assert_eq!(tree.get(0), false);
+        assert!(!tree.get(0));
         assert_eq!(tree.set(0, true), Changed);
         assert_eq!(tree.set(0, true), Unchanged);
-        assert_eq!(tree.get(0), true);
+        assert!(tree.get(0));
 
-        assert_eq!(tree.get(1_424_244), false);
+        assert!(!tree.get(1_424_244));
         assert_eq!(tree.set(1_424_244, true), Changed);
         assert_eq!(tree.set(1_424_244, true), Unchanged);
-        assert_eq!(tree.get(1_424_244), true);
+        assert!(tree.get(1_424_244));
     }
 
-    assert_eq!(b.get(0), false);
-    assert_eq!(b.get(1_424_244), false);
+    assert!(!b.get(0));
+    assert!(!b.get(1_424_244));
 }
 
 #[test]
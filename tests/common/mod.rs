@@ -1,12 +1,60 @@
-use hypercore;
-
 use anyhow::Error;
+use async_std::channel;
 use futures::future::FutureExt;
-use hypercore::{Feed, Storage, Store};
+use hypercore::{Feed, Proof, Storage, Store};
 use random_access_memory as ram;
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
 
 pub async fn create_feed(page_size: usize) -> Result<Feed<ram::RandomAccessMemory>, Error> {
-    let create = |_store: Store| async move { Ok(ram::RandomAccessMemory::new(page_size)) }.boxed();
+    let create =
+        move |_store: Store| async move { Ok(ram::RandomAccessMemory::new(page_size)) }.boxed();
     let storage = Storage::new(create).await?;
     Feed::with_storage(storage).await
 }
+
+/// Create an empty in-memory replica of `feed`, holding only its public key.
+#[allow(dead_code)]
+pub async fn create_replica<T>(feed: &Feed<T>) -> Result<Feed<ram::RandomAccessMemory>, Error>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    let storage = Storage::new_memory().await?;
+    Feed::builder(*feed.public_key(), storage).build()
+}
+
+/// Copy the blocks `to` is missing from `from`, sending each one as an
+/// encoded `Data` message over an in-process channel. Returns the number of
+/// blocks copied.
+#[allow(dead_code)]
+pub async fn replicate<T, U>(from: &mut Feed<T>, to: &mut Feed<U>) -> Result<u64, Error>
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+    U: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    let (sender, receiver) = channel::bounded(16);
+    let missing: Vec<u64> = (0..from.len()).filter(|index| !to.has(*index)).collect();
+
+    let send = async {
+        for index in missing {
+            let data = from.get(index).await?;
+            let proof = from.proof(index, false).await?;
+            sender.send(proof.encode(data.as_deref())).await?;
+        }
+        sender.close();
+        Ok::<_, Error>(())
+    };
+    // Owns the receiver, so a failed put also stops the sender.
+    let receive = async move {
+        let mut copied = 0;
+        while let Ok(message) = receiver.recv().await {
+            let (proof, data) = Proof::decode(&message)?;
+            to.put(proof.index, data.as_deref(), proof).await?;
+            copied += 1;
+        }
+        Ok::<_, Error>(copied)
+    };
+    let (sent, copied) = futures::join!(send, receive);
+    sent?;
+    copied
+}
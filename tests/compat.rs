@@ -1,16 +1,12 @@
 //! Based on https://github.com/mafintosh/hypercore/blob/cf08d8c907e302cf4b699738f229b050eba41b59/test/compat.js
 
-use ed25519_dalek;
-
-use tempfile;
-
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Read;
 use std::path::{Path, PathBuf};
 
 use data_encoding::HEXLOWER;
 use ed25519_dalek::{Keypair, Signature};
-use hypercore::Feed;
+use hypercore::{export_v10, import_v10, CompatReport, Deviation, Feed};
 use hypercore::{Storage, Store};
 use random_access_disk::RandomAccessDisk;
 use remove_dir_all::remove_dir_all;
@@ -115,6 +111,112 @@ async fn deterministic_signatures() {
     }
 }
 
+#[async_std::test]
+async fn open_js_dir() {
+    let dir = mk_js_dir();
+    let report = CompatReport::for_dir(&dir).unwrap();
+    assert!(report.is_compatible(), "{:?}", report.deviations);
+    assert_eq!(report.length, 3);
+
+    let mut feed = Feed::open(&dir).await.unwrap();
+    assert_eq!(feed.len(), 3);
+    assert_eq!(feed.byte_len(), 3);
+    assert_eq!(feed.get(2).await.unwrap(), Some(b"c".to_vec()));
+
+    // Appending continues the tree exactly like the JavaScript implementation.
+    for &b in b"def" {
+        feed.append(&[b]).await.unwrap();
+    }
+    assert_eq!(read_bytes(&dir, Store::Data), b"abcdef");
+    assert_eq!(read_bytes(&dir, Store::Tree), expected_tree());
+
+    remove_dir_all(dir).unwrap()
+}
+
+#[async_std::test]
+async fn compat_report_deviations() {
+    let dir = mk_js_dir();
+    fs::write(dir.join("data"), b"ab").unwrap();
+    let report = CompatReport::for_dir(&dir).unwrap();
+    assert_eq!(
+        report.deviations,
+        vec![Deviation::DataLength {
+            expected: 3,
+            actual: 2
+        }]
+    );
+    remove_dir_all(dir).unwrap();
+
+    // Feeds written by this crate keep the secret key in the `key` file.
+    let (dir, storage) = mk_storage().await;
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    feed.append(b"hello").await.unwrap();
+    let report = CompatReport::for_dir(&dir).unwrap();
+    assert_eq!(report.deviations, vec![Deviation::KeyLength(64)]);
+    remove_dir_all(dir).unwrap()
+}
+
+#[async_std::test]
+async fn v10_roundtrip() {
+    let (dir, storage) = mk_storage().await;
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    for &b in b"abcdef" {
+        feed.append(&[b]).await.unwrap();
+    }
+
+    let v10_dir = tempfile::tempdir().unwrap().keep();
+    export_v10(&mut feed, &v10_dir).await.unwrap();
+    assert_eq!(fs::read(v10_dir.join("data")).unwrap(), b"abcdef");
+    assert_eq!(fs::read(v10_dir.join("tree")).unwrap().len(), 40 * 11);
+    let bitfield = fs::read(v10_dir.join("bitfield")).unwrap();
+    assert_eq!(bitfield.len(), 4096);
+    assert_eq!(bitfield[0], 0b0011_1111);
+
+    let imported_dir = tempfile::tempdir().unwrap().keep();
+    let mut imported = import_v10(&v10_dir, &imported_dir).await.unwrap();
+    assert_eq!(imported.public_key(), feed.public_key());
+    assert_eq!(imported.len(), 6);
+    assert_eq!(imported.get(5).await.unwrap(), Some(b"f".to_vec()));
+    assert_eq!(read_bytes(&imported_dir, Store::Tree), expected_tree());
+
+    // Corrupt data is rejected against the v10 tree.
+    fs::write(v10_dir.join("data"), b"abcdeg").unwrap();
+    let other_dir = tempfile::tempdir().unwrap().keep();
+    import_v10(&v10_dir, &other_dir).await.unwrap_err();
+
+    for dir in [dir, v10_dir, imported_dir, other_dir] {
+        remove_dir_all(dir).unwrap();
+    }
+}
+
+#[async_std::test]
+#[ignore = "needs tests/fixtures/v10, written by tests/fixtures/v10.js"]
+async fn v10_js_fixture() {
+    let (key, _) = compat_keys();
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/v10");
+    let dir = tempfile::tempdir().unwrap().keep();
+    let mut feed = import_v10(&fixture, &dir).await.unwrap();
+    assert_eq!(feed.public_key().as_bytes()[..], key[..]);
+    assert_eq!(feed.len(), 3);
+    assert_eq!(read_bytes(&dir, Store::Data), b"abc");
+
+    // The imported feed is signed like the JavaScript v9 fixtures.
+    let signatures = compat_v9_signatures();
+    let signature = Signature::from_bytes(&signatures[signatures.len() - 64..]).unwrap();
+    feed.verify(2, &signature).await.unwrap();
+
+    // Exporting it again gives back the tree and blocks hypercore v10 wrote.
+    let exported = tempfile::tempdir().unwrap().keep();
+    export_v10(&mut feed, &exported).await.unwrap();
+    for name in &["tree", "data"] {
+        let expected = fs::read(fixture.join(name)).unwrap();
+        assert_eq!(fs::read(exported.join(name)).unwrap(), expected, "{}", name);
+    }
+    for dir in [dir, exported] {
+        remove_dir_all(dir).unwrap();
+    }
+}
+
 #[test]
 #[ignore]
 fn compat_signatures_work() {
@@ -129,6 +231,88 @@ fn deterministic_signatures_after_replication() {
     unimplemented!();
 }
 
+/// Tree written by the JavaScript implementation for `b"abcdef"`.
+fn expected_tree() -> Vec<u8> {
+    hex_bytes(concat!(
+        "0502570200002807424c414b4532620000000000000000000000000000000000ab27d45f509274",
+        "ce0d08f4f09ba2d0e0d8df61a0c2a78932e81b5ef26ef398df0000000000000001064321a8413b",
+        "e8c604599689e2c7a59367b031b598bceeeb16556a8f3252e0de000000000000000294c1705400",
+        "5942a002c7c39fbb9c6183518691fb401436f1a2f329b380230af800000000000000018dfe81d5",
+        "76464773f848b9aba1c886fde57a49c283ab57f4a297d976d986651e00000000000000041d2fad",
+        "c9ce604c7e592949edc964e45aaa10990d7ee53328439ef9b2cf8aa6ff00000000000000013a8d",
+        "cc74e80b8314e8e13e1e462358cf58cf5fc4413a9b18a891ffacc551c395000000000000000228",
+        "28647a654a712738e35f49d1c05c676010be0b33882affc1d1e7e9fee59d400000000000000001",
+        "000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        "00baac70b6d38243efa028ee977c462e4bec73d21d09ceb8cc16f4d4b1ee228a45000000000000",
+        "0001d1b021632c7fab84544053379112ca7b165bb21283821816c5b6c89ff7f78e2d0000000000",
+        "000002d2ab421cece792033058787a5ba72f3a701fddc25540d5924e9819d7c12e02f200000000",
+        "00000001"
+    ))
+}
+
+/// Signatures written by the JavaScript implementation for `b"abc"`.
+fn compat_v9_signatures() -> Vec<u8> {
+    hex_bytes(concat!(
+        "050257010000400745643235353139000000000000000000000000000000000084684e8dd76c339",
+        "d6f5754e813204906ee818e6c6cdc6a816a2ac785a3e0d926ac08641a904013194fe6121847b7da",
+        "d4e361965d47715428eb0a0ededbdd5909d037ff3c3614fa0100ed9264a712d3b77cbe7a4f6eadd",
+        "8f342809be99dfb9154a19e278d7a5de7d2b4d890f7701a38b006469f6bab1aff66ac6125d48baf",
+        "dc0711057675ed57d445ce7ed4613881be37ebc56bb40556b822e431bb4dc3517421f9a5e3ed124",
+        "eb5c4db8367386d9ce12b2408613b9fec2837022772a635ffd807",
+    ))
+}
+
+/// Returns `(public_key, keypair)` used for the JavaScript fixtures.
+fn compat_keys() -> (Vec<u8>, Vec<u8>) {
+    let key = hex_bytes("9718a1ff1c4ca79feac551c0c7212a65e4091278ec886b88be01ee4039682238");
+    let keypair_bytes = hex_bytes(concat!(
+        "53729c0311846cca9cc0eded07aaf9e6689705b6a0b1bb8c3a2a839b72fda383",
+        "9718a1ff1c4ca79feac551c0c7212a65e4091278ec886b88be01ee4039682238"
+    ));
+    (key, keypair_bytes)
+}
+
+/// Write a feed directory holding `b"abc"` the way the JavaScript
+/// implementation lays it out.
+fn mk_js_dir() -> PathBuf {
+    let dir = tempfile::tempdir().unwrap().keep();
+    let (key, keypair_bytes) = compat_keys();
+    let mut bitfield = sleep_parser::create_bitfield().to_vec();
+    bitfield.push(0b1110_0000);
+    // Nodes 0..=4, without node 3 which only exists once there are 4 blocks.
+    let mut tree = expected_tree()[..32 + 40 * 5].to_vec();
+    for byte in &mut tree[32 + 40 * 3..32 + 40 * 4] {
+        *byte = 0;
+    }
+
+    fs::write(dir.join("key"), key).unwrap();
+    fs::write(dir.join("secret_key"), keypair_bytes).unwrap();
+    fs::write(dir.join("data"), b"abc").unwrap();
+    fs::write(dir.join("tree"), tree).unwrap();
+    fs::write(dir.join("signatures"), compat_v9_signatures()).unwrap();
+    fs::write(dir.join("bitfield"), bitfield).unwrap();
+    dir
+}
+
+#[cfg(feature = "encryption")]
+#[async_std::test]
+async fn encrypt_js_secret_key() {
+    let dir = mk_js_dir();
+    let mut feed = Feed::open(&dir).await.unwrap();
+    assert!(feed.secret_key().is_some());
+    feed.encrypt_secret_key(b"hunter2").await.unwrap();
+    assert!(!dir.join("secret_key").exists());
+    drop(feed);
+
+    let mut feed = Feed::open(&dir).await.unwrap();
+    assert!(feed.secret_key().is_none());
+    assert!(feed.append(b"locked").await.is_err());
+    feed.unlock(b"hunter2").await.unwrap();
+    feed.append(b"d").await.unwrap();
+    assert_eq!(feed.len(), 4);
+    remove_dir_all(dir).unwrap();
+}
+
 fn hex_bytes(hex: &str) -> Vec<u8> {
     HEXLOWER.decode(hex.as_bytes()).unwrap()
 }
@@ -140,15 +324,26 @@ fn storage_path<P: AsRef<Path>>(dir: P, s: Store) -> PathBuf {
         Store::Bitfield => "bitfield",
         Store::Signatures => "signatures",
         Store::Keypair => "key",
+        Store::Checksums => "checksums",
+        Store::Offsets => "offsets",
+        Store::Timestamps => "timestamps",
+        Store::Expiry => "expiry",
+        Store::Pins => "pins",
+        Store::Version => "version",
+        Store::Witnesses => "witnesses",
+        Store::Quarantine => "quarantine",
+        Store::Flags => "flags",
+        store => panic!("no file name for {:?}", store),
     };
     dir.as_ref().join(filename)
 }
 
 async fn mk_storage() -> (PathBuf, Storage<RandomAccessDisk>) {
     let temp_dir = tempfile::tempdir().unwrap();
-    let dir = temp_dir.into_path();
-    let storage = Storage::new(|s| {
-        let dir = dir.clone();
+    let dir = temp_dir.keep();
+    let root = dir.clone();
+    let storage = Storage::new(move |s| {
+        let dir = root.clone();
         Box::pin(async move { RandomAccessDisk::open(storage_path(dir, s)).await })
     })
     .await
@@ -164,7 +359,7 @@ fn read_bytes<P: AsRef<Path>>(dir: P, s: Store) -> Vec<u8> {
 }
 
 fn mk_keypair(keypair_bytes: &[u8], public_key: &[u8]) -> Keypair {
-    let keypair = Keypair::from_bytes(&keypair_bytes).unwrap();
+    let keypair = Keypair::from_bytes(keypair_bytes).unwrap();
     assert_eq!(
         keypair.secret.as_bytes().as_ref(),
         &keypair_bytes[..ed25519_dalek::SECRET_KEY_LENGTH]
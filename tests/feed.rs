@@ -2,13 +2,21 @@ extern crate random_access_memory as ram;
 
 mod common;
 
-use common::create_feed;
-use hypercore::{generate_keypair, Feed, NodeTrait, PublicKey, SecretKey, Storage};
+use async_std::sync::Mutex;
+use common::{create_feed, create_replica};
+use futures::stream::StreamExt;
+use hypercore::{
+    generate_keypair, BlockStatus, Feed, FeedUri, GroupCommit, Header, KeyMismatch, LengthProof,
+    NodeTrait, OutOfSpace, PrefetchPolicy, Proof, PublicKey, Ranges, Request, Retention,
+    ScrubFinding, SecretKey, Source, Storage, Tier, Witness,
+};
 use random_access_storage::RandomAccess;
 use std::env::temp_dir;
 use std::fmt::Debug;
 use std::fs;
-use std::io::Write;
+use std::io::{Seek, SeekFrom, Write};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 #[async_std::test]
 async fn create_with_key() {
@@ -62,13 +70,75 @@ async fn append() {
     );
 }
 
+#[async_std::test]
+/// Verify `.set_header()` and `.get_header()` work.
+async fn header() {
+    let mut feed = create_feed(50).await.unwrap();
+    assert_eq!(feed.get_header().await.unwrap(), None);
+    assert_eq!(feed.content_start().await.unwrap(), 0);
+
+    let header = Header::new("hyperdrive").metadata(b"content-feed".to_vec());
+    feed.set_header(&header).await.unwrap();
+    feed.append(b"hello").await.unwrap();
+
+    assert_eq!(feed.get_header().await.unwrap(), Some(header.clone()));
+    assert_eq!(feed.content_start().await.unwrap(), 1);
+    assert!(feed.set_header(&header).await.is_err());
+}
+
+#[async_std::test]
+/// Verify plain blocks at index 0 are not taken for a header.
+async fn header_absent() {
+    for data in &[&b""[..], b"hello"] {
+        let mut feed = create_feed(50).await.unwrap();
+        feed.append(data).await.unwrap();
+        assert_eq!(feed.content_start().await.unwrap(), 0);
+        assert!(feed.get_header().await.is_err());
+        assert_eq!(feed.signature_scheme().await.unwrap().name(), "ed25519");
+    }
+}
+
+#[async_std::test]
+/// Verify headers without the magic field, as written by JavaScript, can be
+/// read but are not skipped as one.
+async fn header_untagged() {
+    let mut feed = create_feed(50).await.unwrap();
+    let header = Header::new("hyperdrive").metadata(b"content-feed".to_vec());
+    feed.append(&header.encode()).await.unwrap();
+    assert_eq!(feed.get_header().await.unwrap(), Some(header));
+    assert_eq!(feed.content_start().await.unwrap(), 0);
+    assert_eq!(feed.signature_scheme().await.unwrap().name(), "ed25519");
+}
+
+#[async_std::test]
+/// Verify the signature scheme is read from the header.
+async fn signature_scheme() {
+    let mut feed = create_feed(50).await.unwrap();
+    assert_eq!(feed.signature_scheme().await.unwrap().name(), "ed25519");
+
+    let header = Header::new("hyperdrive").with_signature_scheme("dilithium3");
+    assert!(feed.set_header(&header).await.is_err());
+    let header = Header::new("hyperdrive").with_signature_scheme("ed25519");
+    feed.set_header(&header).await.unwrap();
+    assert_eq!(feed.signature_scheme().await.unwrap().name(), "ed25519");
+
+    // Feeds written by a newer version with another scheme are refused.
+    let mut feed = create_feed(50).await.unwrap();
+    let header = Header::new("hyperdrive").with_signature_scheme("dilithium3");
+    feed.append(&header.encode_tagged()).await.unwrap();
+    assert!(feed.signature_scheme().await.is_err());
+}
+
 #[async_std::test]
 /// Verify the `.root_hashes()` method returns the right nodes.
 async fn root_hashes() {
     // If no roots exist we should get an error.
     let mut feed = create_feed(50).await.unwrap();
     let res = feed.root_hashes(0).await;
-    assert!(res.is_err());
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "No roots cover block 0, the feed has 0 blocks"
+    );
 
     // If 1 entry exists, [0] should be the root.
     feed.append(b"data").await.unwrap();
@@ -79,6 +149,7 @@ async fn root_hashes() {
     // If we query out of bounds, we should get an error.
     let res = feed.root_hashes(6).await;
     assert!(res.is_err());
+    assert!(feed.root_hashes(1).await.is_err());
 
     // If 3 entries exist, [2,4] should be the roots.
     feed.append(b"data").await.unwrap();
@@ -185,6 +256,29 @@ async fn put_with_data() {
     }
 }
 
+#[async_std::test]
+async fn put_with_coalesced_proofs() {
+    let mut a = create_feed(50).await.unwrap();
+    for i in 0..32u8 {
+        a.append(&[i]).await.unwrap();
+    }
+    let mut b = create_replica(&a).await.unwrap();
+
+    // Proof nodes for these indexes lie close together and far apart in
+    // the tree store, so they are read both in shared runs and on their own.
+    for &i in &[31, 0, 17, 16, 5, 30, 9, 24] {
+        let proof = a.proof(i, false).await.unwrap();
+        assert!(proof.nodes.len() > 1);
+        for node in proof.nodes() {
+            let stored = a.nodes(node.index()..node.index() + 1).await.unwrap();
+            assert_eq!(stored, vec![node.clone()]);
+        }
+        let data = a.get(i).await.unwrap();
+        b.put(i, data.as_deref(), proof).await.unwrap();
+        assert_eq!(b.get(i).await.unwrap(), data);
+    }
+}
+
 #[async_std::test]
 async fn create_with_storage() {
     let storage = Storage::new_memory().await.unwrap();
@@ -217,6 +311,106 @@ async fn create_with_stored_keys() {
     );
 }
 
+#[async_std::test]
+async fn load_into_memory() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut feed = Feed::open(dir.path()).await.unwrap();
+    feed.append(b"hello").await.unwrap();
+    feed.append(b"world").await.unwrap();
+
+    let mut memory = feed.load_into_memory().await.unwrap();
+    assert_eq!(memory.len(), 2);
+    assert_eq!(memory.byte_len(), 10);
+    assert_eq!(memory.get(1).await.unwrap(), Some(b"world".to_vec()));
+    let sig = memory.signature(1).await.unwrap();
+    memory.verify(1, &sig).await.unwrap();
+    assert!(memory.append(b"!").await.is_err());
+}
+
+#[async_std::test]
+async fn single_writer_lock() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut writer = Feed::open(dir.path()).await.unwrap();
+    assert!(Feed::open(dir.path()).await.is_err());
+
+    let storage = Storage::new_disk(dir.path()).await.unwrap();
+    let mut reader = Feed::builder(*writer.public_key(), storage)
+        .build()
+        .unwrap();
+    reader.append(b"nope").await.unwrap_err();
+
+    assert!(!writer.has_external_changes().await.unwrap());
+    writer.append(b"hello").await.unwrap();
+    assert!(!writer.has_external_changes().await.unwrap());
+
+    drop(writer);
+    let mut writer = Feed::open(dir.path()).await.unwrap();
+    writer.append(b"world").await.unwrap();
+}
+
+#[async_std::test]
+async fn single_writer_lock_replica() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = create_feed(50).await.unwrap();
+    let mut storage = Storage::new_disk(dir.path()).await.unwrap();
+    storage.write_public_key(source.public_key()).await.unwrap();
+    drop(storage);
+
+    let replica = Feed::open(dir.path()).await.unwrap();
+    assert!(replica.secret_key().is_none());
+    assert!(Feed::open(dir.path()).await.is_err());
+    assert!(Feed::open_read_only(dir.path()).await.is_ok());
+}
+
+#[async_std::test]
+async fn single_writer_lock_leaves_directory_unchanged() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut feed = Feed::open(dir.path()).await.unwrap();
+    feed.append(b"hello").await.unwrap();
+
+    let snapshot = || {
+        let mut files: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let modified = fs::metadata(&path).unwrap().modified().unwrap();
+                (path.clone(), fs::read(&path).unwrap(), modified)
+            })
+            .collect();
+        files.sort();
+        files
+    };
+    let before = snapshot();
+    async_std::task::sleep(Duration::from_millis(10)).await;
+    assert!(Feed::open(dir.path()).await.is_err());
+    assert_eq!(snapshot(), before);
+}
+
+#[async_std::test]
+async fn read_only_refresh() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut writer = Feed::open(dir.path()).await.unwrap();
+    writer.append(b"hello").await.unwrap();
+    writer.append(b"world").await.unwrap();
+
+    let mut reader = Feed::open_read_only(dir.path()).await.unwrap();
+    assert_eq!(reader.len(), 2);
+    assert_eq!(reader.byte_len(), 10);
+    assert_eq!(reader.get(1).await.unwrap(), Some(b"world".to_vec()));
+    assert!(reader.append(b"nope").await.is_err());
+    assert!(!reader.has_external_changes().await.unwrap());
+
+    writer.append(b"!").await.unwrap();
+    assert!(reader.has_external_changes().await.unwrap());
+    assert!(reader.refresh().await.unwrap());
+    assert!(!reader.has_external_changes().await.unwrap());
+    assert_eq!(reader.len(), 3);
+    assert_eq!(reader.get(2).await.unwrap(), Some(b"!".to_vec()));
+    let sig = reader.signature(2).await.unwrap();
+    reader.verify(2, &sig).await.unwrap();
+    assert!(!reader.refresh().await.unwrap());
+}
+
 fn copy_keys(
     feed: &Feed<impl RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send>,
 ) -> (PublicKey, SecretKey) {
@@ -245,7 +439,7 @@ async fn audit() {
             assert_eq!(audit_report.invalid_blocks, 0);
         }
         Err(e) => {
-            panic!(e);
+            panic!("{}", e);
         }
     }
 }
@@ -288,13 +482,1323 @@ async fn audit_bad_data() {
                 Err(e) => {
                     fs::remove_dir_all(dir)
                         .expect("Should be able to remove our temporary directory");
-                    panic!(e);
+                    panic!("{}", e);
                 }
             }
         }
         Err(e) => {
             fs::remove_dir_all(dir).expect("Should be able to remove our temporary directory");
-            panic!(e);
+            panic!("{}", e);
         }
     }
 }
+
+#[async_std::test]
+async fn checksums_detect_corruption() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = Storage::new_disk(dir.path()).await.unwrap();
+    let keypair = generate_keypair();
+    let mut feed = Feed::builder(keypair.public, storage)
+        .secret_key(keypair.secret)
+        .checksums(true)
+        .build()
+        .unwrap();
+    feed.append(b"hello").await.unwrap();
+    feed.append(b"world").await.unwrap();
+
+    let mut data = fs::OpenOptions::new()
+        .write(true)
+        .open(dir.path().join("data"))
+        .unwrap();
+    data.write_all(b"yello").unwrap();
+
+    assert!(feed.get(0).await.is_err());
+    assert!(!feed.has(0));
+    assert_eq!(feed.get(0).await.unwrap(), None);
+    assert_eq!(feed.get(1).await.unwrap(), Some(b"world".to_vec()));
+}
+
+#[async_std::test]
+async fn seek() {
+    let mut feed = create_feed(50).await.unwrap();
+    for data in &[&b"hello"[..], b"world", b"!", b"verified", b"log"] {
+        feed.append(data).await.unwrap();
+    }
+
+    assert_eq!(feed.seek(0).await.unwrap(), (0, 0));
+    assert_eq!(feed.seek(4).await.unwrap(), (0, 4));
+    assert_eq!(feed.seek(5).await.unwrap(), (1, 0));
+    assert_eq!(feed.seek(10).await.unwrap(), (2, 0));
+    assert_eq!(feed.seek(11).await.unwrap(), (3, 0));
+    assert_eq!(feed.seek(21).await.unwrap(), (4, 2));
+    assert!(feed.seek(22).await.is_err());
+}
+
+#[async_std::test]
+async fn get_and_proof_at_length() {
+    let mut a = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj", b"salut", b"hej", b"ciao"] {
+        a.append(data).await.unwrap();
+    }
+
+    assert_eq!(a.get_at(2, 3).await.unwrap(), Some(b"ahoj".to_vec()));
+    assert_eq!(a.get_at(3, 3).await.unwrap(), None);
+    assert!(a.get_at(0, 7).await.is_err());
+    assert!(a.proof_at(3, 3, false).await.is_err());
+
+    let latest = a.proof(1, false).await.unwrap();
+    let at_latest = a.proof_at(1, 6, false).await.unwrap();
+    assert_eq!(latest.nodes, at_latest.nodes);
+    assert_eq!(latest.signature, at_latest.signature);
+
+    // A replica only given the first three blocks ends up at that version.
+    let (public, secret) = copy_keys(&a);
+    let storage = Storage::new_memory().await.unwrap();
+    let mut b = Feed::builder(public, storage)
+        .secret_key(secret)
+        .build()
+        .unwrap();
+    let proof = a.proof_at(1, 3, false).await.unwrap();
+    let data = a.get_at(1, 3).await.unwrap();
+    b.put(1, data.as_deref(), proof).await.unwrap();
+    assert_eq!(b.len(), 3);
+    assert_eq!(b.get(1).await.unwrap(), Some(b"ola".to_vec()));
+    let signature = a.signature(2).await.unwrap();
+    b.verify(2, &signature).await.unwrap();
+}
+
+#[async_std::test]
+async fn byte_range_len() {
+    let mut feed = create_feed(50).await.unwrap();
+    for data in &[&b"hello"[..], b"world", b"!", b"verified", b"log"] {
+        feed.append(data).await.unwrap();
+    }
+
+    assert_eq!(feed.byte_range_len(0..5).await.unwrap(), 22);
+    assert_eq!(feed.byte_range_len(0..1).await.unwrap(), 5);
+    assert_eq!(feed.byte_range_len(1..4).await.unwrap(), 14);
+    assert_eq!(feed.byte_range_len(3..5).await.unwrap(), 11);
+    assert_eq!(feed.byte_range_len(2..2).await.unwrap(), 0);
+    assert!(feed.byte_range_len(2..6).await.is_err());
+}
+
+#[async_std::test]
+async fn byte_offset() {
+    let mut feed = create_feed(50).await.unwrap();
+    for data in &[&b"hello"[..], b"world", b"!", b"verified", b"log"] {
+        feed.append(data).await.unwrap();
+    }
+
+    assert_eq!(feed.byte_offset(0).await.unwrap(), (0, 5));
+    assert_eq!(feed.byte_offset(2).await.unwrap(), (10, 1));
+    assert_eq!(feed.byte_offset(4).await.unwrap(), (19, 3));
+    assert!(feed.byte_offset(5).await.is_err());
+}
+
+#[async_std::test]
+async fn block_metadata() {
+    let mut a = create_feed(50).await.unwrap();
+    for data in &[&b"hello"[..], b"world", b"!"] {
+        a.append(data).await.unwrap();
+    }
+
+    let block = a.block(2).await.unwrap();
+    assert_eq!(
+        (block.index(), block.byte_offset(), block.len()),
+        (2, 10, 1)
+    );
+    assert_eq!(block.hash(), a.nodes(4..5).await.unwrap()[0].hash());
+    assert!(block.is_downloaded());
+    assert_eq!(block.data(&mut a).await.unwrap(), Some(b"!".to_vec()));
+    assert!(a.block(3).await.is_err());
+
+    let (public, _) = copy_keys(&a);
+    let storage = Storage::new_memory().await.unwrap();
+    let mut b = Feed::builder(public, storage).build().unwrap();
+    let proof = a.proof(2, false).await.unwrap();
+    b.put(2, Some(b"!"), proof).await.unwrap();
+    assert!(b.block(1).await.is_err());
+    let proof = a.proof(1, true).await.unwrap();
+    b.put(1, None, proof).await.unwrap();
+    let block = b.block(1).await.unwrap();
+    assert_eq!((block.byte_offset(), block.len()), (5, 5));
+    assert!(!block.is_downloaded());
+    assert_eq!(block.data(&mut b).await.unwrap(), None);
+}
+
+#[async_std::test]
+async fn watch_length() {
+    let mut a = create_feed(50).await.unwrap();
+    let mut watch = a.watch();
+    assert_eq!(watch.borrow(), (0, 0));
+
+    a.append(b"hi").await.unwrap();
+    a.append(b"ola").await.unwrap();
+    assert_eq!(watch.next().await, Some((2, 0)));
+
+    let mut b = common::create_replica(&a).await.unwrap();
+    let mut replica = b.watch();
+    let proof = a.proof(1, false).await.unwrap();
+    b.put(1, Some(b"ola"), proof).await.unwrap();
+    assert_eq!(replica.next().await, Some((2, 0)));
+
+    drop(b);
+    assert_eq!(replica.next().await, None);
+}
+
+#[async_std::test]
+async fn verify_range() {
+    let dir = tempfile::Builder::new()
+        .prefix("verify_range")
+        .tempdir()
+        .unwrap();
+    let storage = Storage::new_disk(dir.path()).await.unwrap();
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    for data in &[&b"hello"[..], b"world", b"!", b"log"] {
+        feed.append(data).await.unwrap();
+    }
+    let reports = feed.verify_range(0..4).await.unwrap();
+    assert!(reports.iter().all(|r| r.status == BlockStatus::Verified));
+    assert!(feed.verify_range(1..1).await.unwrap().is_empty());
+    assert!(feed.verify_range(2..5).await.is_err());
+
+    // Corrupt the data of block 0, and the tree node of block 3.
+    let mut data = fs::OpenOptions::new()
+        .write(true)
+        .open(dir.path().join("data"))
+        .unwrap();
+    data.write_all(b"yello").unwrap();
+    let mut tree = fs::OpenOptions::new()
+        .write(true)
+        .open(dir.path().join("tree"))
+        .unwrap();
+    tree.seek(SeekFrom::Start(32 + 40 * 6)).unwrap();
+    tree.write_all(&[0xff; 32]).unwrap();
+
+    let reports = feed.verify_range(0..4).await.unwrap();
+    let statuses: Vec<_> = reports.into_iter().map(|r| r.status).collect();
+    assert!(matches!(statuses[0], BlockStatus::Invalid(_)));
+    assert_eq!(statuses[1], BlockStatus::Verified);
+    let mismatch = BlockStatus::Invalid("Tree node 5 does not match its children".into());
+    assert_eq!(statuses[2], mismatch);
+    let mismatch = BlockStatus::Invalid("Data does not match the tree".into());
+    assert_eq!(statuses[3], mismatch);
+    assert!(feed.has(0));
+}
+
+#[async_std::test]
+async fn witness_checkpoints() {
+    let mut a = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj"] {
+        a.append(data).await.unwrap();
+    }
+    let mut b = common::create_replica(&a).await.unwrap();
+    common::replicate(&mut a, &mut b).await.unwrap();
+
+    // The witness signs the checkpoint of its own replica.
+    let notary = generate_keypair();
+    let checkpoint = b.checkpoint(3).await.unwrap();
+    let witness = Witness::sign(3, &checkpoint, &notary.public, &notary.secret);
+    let witness = Witness::decode(&witness.encode()).unwrap();
+    a.add_witness(&witness).await.unwrap();
+    assert_eq!(a.witnesses().await.unwrap(), vec![witness]);
+    assert!(a.is_witnessed_by(&notary.public, 2).await.unwrap());
+    assert!(!a.is_witnessed_by(&notary.public, 4).await.unwrap());
+
+    // Checkpoints of other histories are rejected.
+    let mut fork = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"hej"] {
+        fork.append(data).await.unwrap();
+    }
+    let checkpoint = fork.checkpoint(3).await.unwrap();
+    let forged = Witness::sign(3, &checkpoint, &notary.public, &notary.secret);
+    assert!(a.add_witness(&forged).await.is_err());
+    assert!(a.checkpoint(4).await.is_err());
+    assert_eq!(a.witnesses().await.unwrap().len(), 1);
+}
+
+#[async_std::test]
+async fn quarantine_corrupt_blocks() {
+    let dir = tempfile::Builder::new()
+        .prefix("quarantine")
+        .tempdir()
+        .unwrap();
+    let storage = Storage::new_disk(dir.path()).await.unwrap();
+    let keypair = generate_keypair();
+    let mut feed = Feed::builder(keypair.public, storage)
+        .secret_key(keypair.secret)
+        .quarantine(true)
+        .build()
+        .unwrap();
+    feed.append(b"hello").await.unwrap();
+    feed.append(b"world").await.unwrap();
+    let expected_hash = feed.nodes(0..1).await.unwrap()[0].hash().to_vec();
+    let mut data = fs::OpenOptions::new()
+        .write(true)
+        .open(dir.path().join("data"))
+        .unwrap();
+    data.write_all(b"yello").unwrap();
+
+    assert_eq!(feed.audit().await.unwrap().invalid_blocks, 1);
+    assert!(!feed.has(0));
+    let quarantined = feed.quarantined().await.unwrap();
+    assert_eq!(quarantined.len(), 1);
+    assert_eq!(quarantined[0].index, 0);
+    assert_eq!(quarantined[0].expected_hash, expected_hash);
+    assert_eq!(quarantined[0].data, b"yello".to_vec());
+}
+
+#[async_std::test]
+async fn dump_tree() {
+    let mut feed = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj"] {
+        feed.append(data).await.unwrap();
+    }
+    let mut out = vec![];
+    feed.dump_tree(0..3, &mut out).await.unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("0 leaf ") && lines[0].ends_with(" (2 bytes)"));
+    assert!(lines[1].starts_with("  1 root ") && lines[1].ends_with(" (5 bytes)"));
+    assert!(lines[3].starts_with("4 root "));
+
+    let mut replica = common::create_replica(&feed).await.unwrap();
+    let proof = feed.proof(2, false).await.unwrap();
+    replica.put(2, Some(b"ahoj"), proof).await.unwrap();
+    let mut out = vec![];
+    replica.dump_tree_json(0..1, &mut out).await.unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        r#"{"length":3,"nodes":[{"index":0,"depth":0,"root":false}]}"#
+    );
+}
+
+#[async_std::test]
+async fn lazy_signatures() {
+    let mut a = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj", b"salut"] {
+        a.append(data).await.unwrap();
+    }
+    let storage = Storage::new_memory().await.unwrap();
+    let mut b = Feed::builder(*a.public_key(), storage)
+        .lazy_signatures(true)
+        .build()
+        .unwrap();
+    for index in 0..3 {
+        let proof = a.proof_at(index, 3, false).await.unwrap();
+        let data = a.get(index).await.unwrap();
+        b.put(index, data.as_deref(), proof).await.unwrap();
+    }
+    assert_eq!(b.len(), 3);
+    assert_eq!(b.tentative().to_string(), "0..3");
+    assert_eq!(b.get(1).await.unwrap(), Some(b"ola".to_vec()));
+    assert_eq!(b.confirm_tentative().await.unwrap().to_string(), "0..3");
+    assert!(b.tentative().is_empty());
+    assert!(b.confirm_tentative().await.unwrap().is_empty());
+
+    // A forged signature is only caught on confirmation, which rolls back.
+    let mut proof = a.proof(3, false).await.unwrap();
+    let forger = generate_keypair();
+    proof.signature = Some(hypercore::sign(&forger.public, &forger.secret, b"roots"));
+    b.put(3, Some(b"salut"), proof).await.unwrap();
+    assert_eq!(b.len(), 4);
+    assert_eq!(b.tentative().to_string(), "3..4");
+    assert!(b.confirm_tentative().await.is_err());
+    assert_eq!(b.len(), 3);
+    assert!(!b.has(3) && b.has(2));
+
+    let proof = a.proof(3, false).await.unwrap();
+    b.put(3, Some(b"salut"), proof).await.unwrap();
+    assert_eq!(b.confirm_tentative().await.unwrap().to_string(), "3..4");
+    assert_eq!(b.signature(3).await.unwrap(), a.signature(3).await.unwrap());
+}
+
+#[async_std::test]
+async fn verify_reads() {
+    let dir = tempfile::Builder::new()
+        .prefix("verify_reads")
+        .tempdir()
+        .unwrap();
+    let storage = Storage::new_disk(dir.path()).await.unwrap();
+    let keypair = generate_keypair();
+    let mut feed = Feed::builder(keypair.public, storage)
+        .secret_key(keypair.secret)
+        .verify_reads(true)
+        .build()
+        .unwrap();
+    feed.append(b"hello").await.unwrap();
+    feed.append(b"world").await.unwrap();
+    assert_eq!(feed.get(0).await.unwrap(), Some(b"hello".to_vec()));
+    let mut data = fs::OpenOptions::new()
+        .write(true)
+        .open(dir.path().join("data"))
+        .unwrap();
+    data.write_all(b"yello").unwrap();
+
+    let err = feed.get(0).await.unwrap_err().to_string();
+    assert_eq!(err, "Block 0 does not match its tree node");
+    assert!(!feed.has(0));
+    assert_eq!(feed.get(1).await.unwrap(), Some(b"world".to_vec()));
+}
+
+#[async_std::test]
+async fn feed_uri() {
+    let keypair = generate_keypair();
+    let storage = Storage::new_memory().await.unwrap();
+    let feed = Feed::builder(keypair.public, storage)
+        .checksums(true)
+        .build()
+        .unwrap();
+    let uri = feed.to_uri();
+    assert!(uri.checksums && !uri.sparse && !uri.quarantine);
+    assert!(uri.to_string().ends_with("?checksums=true"));
+
+    let parsed: FeedUri = format!("{}&sparse&v=2", uri).parse().unwrap();
+    assert_eq!(parsed.public_key, keypair.public);
+    assert!(parsed.sparse && parsed.checksums);
+    let hex: String = keypair
+        .public
+        .as_bytes()
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect();
+    assert_eq!(
+        hex.parse::<FeedUri>().unwrap(),
+        FeedUri::new(keypair.public)
+    );
+
+    let storage = Storage::new_memory().await.unwrap();
+    let replica = parsed.builder(storage).build().unwrap();
+    assert_eq!(replica.public_key(), &keypair.public);
+    assert_eq!(replica.to_uri().to_string(), uri.to_string());
+
+    assert!("hyper://abc".parse::<FeedUri>().is_err());
+    assert!(format!("{}?sparse=yes", FeedUri::new(keypair.public))
+        .parse::<FeedUri>()
+        .is_err());
+}
+
+#[async_std::test]
+async fn put_encoded_data() {
+    let mut a = create_feed(50).await.unwrap();
+    let (public, secret) = copy_keys(&a);
+    let storage = Storage::new_memory().await.unwrap();
+    let mut b = Feed::builder(public, storage)
+        .secret_key(secret)
+        .build()
+        .unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj"] {
+        a.append(data).await.unwrap();
+    }
+
+    let data = a.get(1).await.unwrap();
+    let message = a.proof(1, false).await.unwrap().encode(data.as_deref());
+    let (proof, data) = Proof::decode(&message).unwrap();
+    b.put(proof.index, data.as_deref(), proof).await.unwrap();
+    assert_eq!(b.get(1).await.unwrap(), Some(b"ola".to_vec()));
+}
+
+#[async_std::test]
+async fn replicate_over_loopback() {
+    let mut a = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj", b"salut", b"hej"] {
+        a.append(data).await.unwrap();
+    }
+
+    let mut b = common::create_replica(&a).await.unwrap();
+    assert_eq!(common::replicate(&mut a, &mut b).await.unwrap(), 5);
+    assert_eq!(b.len(), 5);
+    assert_eq!(b.get(3).await.unwrap(), Some(b"salut".to_vec()));
+
+    a.append(b"ciao").await.unwrap();
+    assert_eq!(common::replicate(&mut a, &mut b).await.unwrap(), 1);
+    assert_eq!(b.get(5).await.unwrap(), Some(b"ciao".to_vec()));
+}
+
+#[async_std::test]
+async fn proof_size() {
+    let mut a = create_feed(50).await.unwrap();
+    for _ in 0..10u8 {
+        a.append(b"foo").await.unwrap();
+    }
+    let mut b = common::create_replica(&a).await.unwrap();
+
+    for index in 0..10 {
+        let size = a.proof_size(index, 0).unwrap();
+        let proof = a.proof(index, false).await.unwrap();
+        assert_eq!(size.nodes, proof.nodes.len());
+        assert_eq!(size.signed, proof.signature.is_some());
+        assert!(size.bytes() >= proof.encode(None).len() as u64 - 2);
+    }
+
+    let proof = a.proof(0, false).await.unwrap();
+    let data = a.get(0).await.unwrap();
+    b.put(0, data.as_deref(), proof).await.unwrap();
+    let size = a.proof_size(4, b.digest(4)).unwrap();
+    let proof = a.proof_with_digest(4, b.digest(4), false).await.unwrap();
+    assert_eq!(size.nodes, proof.nodes.len());
+    assert!(size.nodes < a.proof_size(4, 0).unwrap().nodes);
+}
+
+#[async_std::test]
+async fn nodes_and_missing_nodes() {
+    let mut a = create_feed(50).await.unwrap();
+    for _ in 0..8u8 {
+        a.append(b"foo").await.unwrap();
+    }
+    let mut b = common::create_replica(&a).await.unwrap();
+    assert_eq!(a.nodes(0..15).await.unwrap().len(), 15);
+    assert_eq!(b.nodes(0..15).await.unwrap().len(), 0);
+    assert_eq!(a.missing_nodes(14), 0);
+    assert_eq!(b.missing_nodes(14), 0);
+
+    let proof = a.proof(0, false).await.unwrap();
+    let data = a.get(0).await.unwrap();
+    b.put(0, data.as_deref(), proof).await.unwrap();
+    let indexes: Vec<u64> = b
+        .nodes(0..15)
+        .await
+        .unwrap()
+        .iter()
+        .map(|n| n.index())
+        .collect();
+    assert_eq!(indexes, vec![0, 1, 2, 3, 5, 7, 11]);
+    assert_eq!(b.missing_nodes(0), 0);
+    assert_eq!(b.missing_nodes(4), 1);
+    assert_eq!(b.missing_nodes(14), 2);
+}
+
+#[async_std::test]
+async fn request_with_digest() {
+    let mut a = create_feed(50).await.unwrap();
+    for _ in 0..8u8 {
+        a.append(b"foo").await.unwrap();
+    }
+    let mut b = common::create_replica(&a).await.unwrap();
+
+    for index in &[0, 1, 6] {
+        let message = b.request(*index).encode();
+        let request = Request::decode(&message).unwrap();
+        let proof = a.proof_for(&request).await.unwrap();
+        assert!(proof.nodes.len() <= a.proof(*index, false).await.unwrap().nodes.len());
+        let data = a.get(*index).await.unwrap();
+        b.put(*index, data.as_deref(), proof).await.unwrap();
+    }
+    // The replica already has the leaf of block 1, so no nodes are needed.
+    let request = b.request(1);
+    assert_eq!(a.proof_for(&request).await.unwrap().nodes.len(), 0);
+}
+
+#[async_std::test]
+async fn append_timings() {
+    let mut feed = create_feed(50).await.unwrap();
+    feed.append(b"before").await.unwrap();
+    assert_eq!(feed.append_timings(), None);
+
+    feed.record_append_timings();
+    for _ in 0..3u8 {
+        feed.append(b"foo").await.unwrap();
+    }
+    let timings = feed.append_timings().unwrap();
+    assert_eq!(timings.appends, 3);
+    assert!(timings.total() > std::time::Duration::from_secs(0));
+    assert!(timings.signing > std::time::Duration::from_secs(0));
+    assert!(timings.data > std::time::Duration::from_secs(0));
+    // Checksums are off, so no time is spent writing them.
+    assert_eq!(timings.checksums, std::time::Duration::from_secs(0));
+}
+
+#[async_std::test]
+async fn append_batch() {
+    let mut feed = create_feed(50).await.unwrap();
+    feed.append(b"first").await.unwrap();
+    feed.append_batch(&[&b"hello"[..], b"world", b"!"])
+        .await
+        .unwrap();
+    assert_eq!(feed.len(), 4);
+    assert_eq!(feed.byte_len(), 16);
+    assert_eq!(feed.get(2).await.unwrap(), Some(b"world".to_vec()));
+
+    // Blocks within the batch are covered by the signature after it.
+    let signature = feed.signature(1).await.unwrap();
+    assert_eq!(signature, feed.signature(3).await.unwrap());
+    feed.verify(3, &signature).await.unwrap();
+    assert!(feed.verify(1, &signature).await.is_err());
+
+    let mut replica = common::create_replica(&feed).await.unwrap();
+    assert_eq!(common::replicate(&mut feed, &mut replica).await.unwrap(), 4);
+    assert_eq!(replica.get(3).await.unwrap(), Some(b"!".to_vec()));
+}
+
+#[async_std::test]
+/// Verify blocks in the middle of a batch are proven against the signature
+/// of the batch tip.
+async fn append_batch_proof_of_middle_index() {
+    let mut feed = create_feed(50).await.unwrap();
+    feed.append(b"first").await.unwrap();
+    feed.append_batch(&[&b"hello"[..], b"world", b"!"])
+        .await
+        .unwrap();
+    let tip = feed.signature(3).await.unwrap();
+
+    for digest in &[0, 2, 4] {
+        let proof = feed.proof_with_digest(2, *digest, false).await.unwrap();
+        assert_eq!(proof.signature(), Some(&tip), "digest {}", digest);
+    }
+
+    let proof = feed.proof(2, false).await.unwrap();
+    let mut replica = common::create_replica(&feed).await.unwrap();
+    replica.put(2, Some(b"world"), proof).await.unwrap();
+    assert_eq!(replica.len(), 4);
+    assert_eq!(replica.get(2).await.unwrap(), Some(b"world".to_vec()));
+}
+
+#[async_std::test]
+async fn group_commit() {
+    let feed = Arc::new(Mutex::new(create_feed(50).await.unwrap()));
+    let commit = GroupCommit::new(feed.clone(), 4, Duration::from_millis(20));
+
+    let appends = (0..10u8).map(|i| {
+        let commit = commit.clone();
+        async move { (i, commit.append(vec![i]).await.unwrap()) }
+    });
+    let indexes = futures::future::join_all(appends).await;
+
+    let mut feed = feed.lock().await;
+    assert_eq!(feed.len(), 10);
+    for (data, index) in indexes {
+        assert_eq!(feed.get(index).await.unwrap(), Some(vec![data]));
+    }
+    // The first four blocks were committed together.
+    assert_eq!(
+        feed.signature(0).await.unwrap(),
+        feed.signature(3).await.unwrap()
+    );
+}
+
+#[async_std::test]
+async fn group_commit_shutdown() {
+    let feed = Arc::new(Mutex::new(create_feed(50).await.unwrap()));
+    let commit = GroupCommit::new(feed.clone(), 100, Duration::from_secs(60));
+
+    // The block is queued before the shutdown starts, and committed before
+    // it completes, long before the interval.
+    let (queued, shutdown) = futures::join!(commit.append(b"queued".to_vec()), commit.shutdown());
+    assert_eq!(queued.unwrap(), 0);
+    shutdown.unwrap();
+    assert_eq!(feed.lock().await.len(), 1);
+    assert!(commit.append(b"late".to_vec()).await.is_err());
+}
+
+#[async_std::test]
+async fn download_to_path() {
+    let mut source = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj", b"salut", b"hej"] {
+        source.append(data).await.unwrap();
+    }
+    let dir = tempfile::Builder::new()
+        .prefix("download")
+        .tempdir()
+        .unwrap();
+    let key = *source.public_key();
+
+    let (mut feed, stats) = Feed::download_to_path(key, dir.path(), &mut source, Some(1..3))
+        .await
+        .unwrap();
+    assert_eq!((stats.blocks, stats.bytes), (2, 7));
+    assert!(!feed.has(0));
+    drop(feed);
+
+    let (mut feed, stats) = Feed::download_to_path(key, dir.path(), &mut source, None)
+        .await
+        .unwrap();
+    assert_eq!((stats.blocks, stats.bytes), (3, 10));
+    assert_eq!(feed.len(), 5);
+    assert_eq!(feed.byte_len(), 17);
+    assert_eq!(feed.get(3).await.unwrap(), Some(b"salut".to_vec()));
+    drop(feed);
+
+    let other = *create_feed(50).await.unwrap().public_key();
+    assert!(Feed::download_to_path(other, dir.path(), &mut source, None)
+        .await
+        .is_err());
+}
+
+#[async_std::test]
+async fn download_progress() {
+    let mut source = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj", b"salut", b"hej"] {
+        source.append(data).await.unwrap();
+    }
+    let dir = tempfile::Builder::new()
+        .prefix("progress")
+        .tempdir()
+        .unwrap();
+    let key = *source.public_key();
+    Feed::download_to_path(key, dir.path(), &mut source, Some(0..1))
+        .await
+        .unwrap();
+
+    let mut reports = vec![];
+    let (_, stats) = Feed::download_to_path_with_progress(
+        key,
+        dir.path(),
+        &mut source,
+        None,
+        Duration::ZERO,
+        |progress| reports.push(progress.clone()),
+    )
+    .await
+    .unwrap();
+    let blocks: Vec<_> = reports.iter().map(|p| (p.blocks, p.total)).collect();
+    assert_eq!(blocks, vec![(1, 4), (2, 4), (3, 4), (4, 4)]);
+    let last = reports.last().unwrap();
+    assert!(last.is_done());
+    assert_eq!(last.bytes, stats.bytes);
+    assert_eq!(last.eta(), Some(Duration::ZERO));
+}
+
+#[async_std::test]
+async fn side_stores_created_on_use() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut feed = Feed::open(dir.path()).await.unwrap();
+    feed.append(b"hello").await.unwrap();
+    assert_eq!(feed.gc().await.unwrap(), 0);
+    assert_eq!(feed.flags(0).await.unwrap(), 0);
+    for name in &["checksums", "timestamps", "expiry", "pins", "flags"] {
+        assert!(!dir.path().join(name).exists(), "{} was created", name);
+    }
+
+    feed.set_retention(vec![Retention::Age(Duration::from_secs(3600))]);
+    feed.append(b"world").await.unwrap();
+    feed.set_flags(0..1, 1).await.unwrap();
+    assert!(dir.path().join("timestamps").exists());
+    assert!(dir.path().join("flags").exists());
+    assert_eq!(feed.flags(0).await.unwrap(), 1);
+}
+
+#[async_std::test]
+/// Verify blocks appended after reopening record when they were stored,
+/// though the age policy is not persisted.
+async fn gc_age_after_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut feed = Feed::open(dir.path()).await.unwrap();
+    feed.set_retention(vec![Retention::Age(Duration::from_secs(3600))]);
+    feed.append(b"hello").await.unwrap();
+    drop(feed);
+
+    let mut feed = Feed::open(dir.path()).await.unwrap();
+    feed.append(b"world").await.unwrap();
+    feed.set_retention(vec![Retention::Age(Duration::ZERO)]);
+    assert_eq!(feed.gc().await.unwrap(), 2);
+    assert!(!feed.has(0) && !feed.has(1));
+}
+
+#[async_std::test]
+async fn gc_with_retention() {
+    let mut feed = create_feed(50).await.unwrap();
+    for data in &[&b"hello"[..], b"world", b"!", b"verified", b"log"] {
+        feed.append(data).await.unwrap();
+    }
+    assert_eq!(feed.gc().await.unwrap(), 0);
+
+    feed.set_retention(vec![Retention::Age(Duration::from_secs(3600))]);
+    assert_eq!(feed.gc().await.unwrap(), 0);
+    assert!(feed.has_all(0..5));
+
+    feed.set_retention(vec![Retention::Blocks(4)]);
+    assert_eq!(feed.gc().await.unwrap(), 1);
+    assert!(!feed.has(0));
+    assert!(feed.has_all(1..5));
+
+    // "log" and "verified" fit in 11 bytes, "!" does not.
+    feed.set_retention(vec![Retention::Blocks(4), Retention::Bytes(11)]);
+    assert_eq!(feed.gc().await.unwrap(), 2);
+    assert!(!feed.has(1) && !feed.has(2));
+    assert_eq!(feed.get(3).await.unwrap(), Some(b"verified".to_vec()));
+    assert_eq!(feed.get(4).await.unwrap(), Some(b"log".to_vec()));
+
+    // Cleared blocks can still be proven.
+    let proof = feed.proof(0, false).await.unwrap();
+    assert_eq!(proof.index, 0);
+    assert_eq!(feed.gc().await.unwrap(), 0);
+}
+
+#[async_std::test]
+async fn expire_blocks() {
+    let mut feed = create_feed(50).await.unwrap();
+    let past = SystemTime::now() - Duration::from_secs(60);
+    let future = SystemTime::now() + Duration::from_secs(3600);
+    feed.append_with_expiry(b"hello", past).await.unwrap();
+    feed.append_with_expiry(b"world", future).await.unwrap();
+    feed.append(b"!").await.unwrap();
+    feed.append_with_expiry(b"pinned", past).await.unwrap();
+    feed.append_with_expiry(b"log", past).await.unwrap();
+    feed.pin(3..4).await.unwrap();
+
+    assert_eq!(feed.expire_now().await.unwrap(), 2);
+    assert!(!feed.has(0) && !feed.has(4));
+    assert!(feed.has_all(1..4));
+    assert_eq!(feed.expire_now().await.unwrap(), 0);
+
+    // Expired blocks are cleared along with the retention policies.
+    feed.unpin(3..4).await.unwrap();
+    feed.set_retention(vec![Retention::Blocks(10)]);
+    assert_eq!(feed.gc().await.unwrap(), 1);
+    assert!(!feed.has(3));
+    assert!(feed.has_all(1..3));
+}
+
+#[async_std::test]
+async fn feed_stats() {
+    let mut feed = create_feed(50).await.unwrap();
+    feed.append_batch(&[&b"hello"[..], b"world"]).await.unwrap();
+    feed.append(b"!").await.unwrap();
+    feed.get(0).await.unwrap();
+    feed.get(2).await.unwrap();
+    let signature = feed.signature(1).await.unwrap();
+    assert!(feed.verify(2, &signature).await.is_err());
+
+    let stats = feed.stats().await.unwrap();
+    assert_eq!((stats.length, stats.byte_length), (3, 11));
+    assert_eq!((stats.appended_blocks, stats.read_blocks), (3, 2));
+    assert_eq!((stats.read_bytes, stats.verification_failures), (6, 1));
+    assert!(stats.stored_bytes > stats.byte_length);
+    assert!(stats.overhead() > 1.0);
+    assert_eq!((stats.proof_cache, stats.peers), (None, 0));
+    assert!(stats
+        .to_json()
+        .starts_with(r#"{"length":3,"byteLength":11,"#));
+
+    // Rates cover the time since the previous snapshot.
+    let stats = feed.stats().await.unwrap();
+    assert_eq!((stats.append_rate, stats.read_rate), (0.0, 0.0));
+    assert_eq!(stats.appended_blocks, 3);
+}
+
+#[async_std::test]
+async fn proof_cache() {
+    let mut feed = create_feed(50).await.unwrap();
+    for data in &[&b"hello"[..], b"world", b"!"] {
+        feed.append(data).await.unwrap();
+    }
+    assert_eq!(feed.proof_cache_stats(), None);
+    feed.set_proof_cache(2);
+
+    let proof = feed.proof(0, false).await.unwrap();
+    assert_eq!(feed.proof(0, false).await.unwrap(), proof);
+    feed.proof(1, false).await.unwrap();
+    let stats = feed.proof_cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+
+    // The oldest proof is evicted to make room.
+    feed.proof(2, false).await.unwrap();
+    feed.proof(0, false).await.unwrap();
+    let stats = feed.proof_cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.entries), (1, 4, 2));
+
+    // Appending changes the roots and signature, so cached proofs are stale.
+    feed.append(b"more").await.unwrap();
+    assert_eq!(feed.proof_cache_stats().unwrap().entries, 0);
+    let proof = feed.proof(0, false).await.unwrap();
+    let stats = feed.proof_cache_stats().unwrap();
+    assert_eq!((stats.hits, stats.misses), (1, 5));
+    assert_eq!(stats.hit_rate(), 1.0 / 6.0);
+    assert_eq!(proof.signature, Some(feed.signature(3).await.unwrap()));
+}
+
+#[async_std::test]
+async fn reject_mismatched_key_pair() {
+    let (public, _) = copy_keys(&create_feed(50).await.unwrap());
+    let (_, secret) = copy_keys(&create_feed(50).await.unwrap());
+    let mut storage = Storage::new_memory().await.unwrap();
+    storage.write_public_key(&public).await.unwrap();
+    storage.write_secret_key(&secret).await.unwrap();
+
+    let err = Feed::with_storage(storage).await.unwrap_err();
+    assert_eq!(err.downcast_ref::<KeyMismatch>(), Some(&KeyMismatch));
+
+    let feed = create_feed(50).await.unwrap();
+    assert!(feed.validate_key_pair().is_ok());
+}
+
+/// Serves a feed, corrupting one block and failing to find another.
+struct Unreliable(Feed<ram::RandomAccessMemory>);
+
+#[async_trait::async_trait]
+impl Source for Unreliable {
+    async fn remote_length(&mut self) -> anyhow::Result<u64> {
+        Ok(self.0.len())
+    }
+
+    async fn request(&mut self, request: &Request) -> anyhow::Result<Vec<u8>> {
+        match request.index {
+            1 => Ok(self.0.proof_for(request).await?.encode(Some(b"forged"))),
+            2 => anyhow::bail!("Block not found"),
+            _ => Source::request(&mut self.0, request).await,
+        }
+    }
+}
+
+#[async_std::test]
+async fn block_flags() {
+    const PROCESSED: u8 = 1;
+    const TOMBSTONED: u8 = 2;
+    let mut feed = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj", b"salut", b"hej"] {
+        feed.append(data).await.unwrap();
+    }
+    assert_eq!(feed.flags(3).await.unwrap(), 0);
+
+    feed.set_flags(0..4, PROCESSED).await.unwrap();
+    feed.set_flags(2..3, TOMBSTONED).await.unwrap();
+    feed.clear_flags(1..2, PROCESSED).await.unwrap();
+    assert_eq!(feed.flags(2).await.unwrap(), PROCESSED | TOMBSTONED);
+    assert_eq!(feed.flags(1).await.unwrap(), 0);
+    assert_eq!(
+        feed.find_flags(0..5, PROCESSED).await.unwrap().to_string(),
+        "0..1,2..4"
+    );
+    assert_eq!(
+        feed.find_flags(0..5, PROCESSED | TOMBSTONED)
+            .await
+            .unwrap()
+            .to_string(),
+        "2..3"
+    );
+    assert!(feed.find_flags(4..5, PROCESSED).await.unwrap().is_empty());
+}
+
+#[async_std::test]
+async fn tail() {
+    let mut feed = create_feed(50).await.unwrap();
+    feed.append(b"hi").await.unwrap();
+    feed.append(b"ola").await.unwrap();
+    let feed = Arc::new(Mutex::new(feed));
+    let mut tail = Box::pin(Feed::tail(&feed, 1));
+    assert_eq!(tail.next().await.unwrap().unwrap(), (1, b"ola".to_vec()));
+
+    let writer = feed.clone();
+    let append = async_std::task::spawn(async move {
+        for data in &[&b"ahoj"[..], b"salut"] {
+            writer.lock().await.append(data).await.unwrap();
+        }
+    });
+    assert_eq!(tail.next().await.unwrap().unwrap(), (2, b"ahoj".to_vec()));
+    assert_eq!(tail.next().await.unwrap().unwrap(), (3, b"salut".to_vec()));
+    append.await;
+
+    drop(feed);
+    assert!(tail.next().await.is_none());
+}
+
+#[async_std::test]
+async fn append_packed() {
+    let mut feed = create_feed(50).await.unwrap();
+    let records: Vec<Vec<u8>> = (0..100u8).map(|i| vec![i; 20]).collect();
+    let outcome = feed.append_packed(&records, 512).await.unwrap();
+    assert_eq!(outcome.index, 0);
+    // 24 records of 21 bytes, with their count, fit in each segment.
+    assert_eq!(feed.len(), 5);
+    assert_eq!(feed.get_packed(0, 0).await.unwrap(), Some(vec![0; 20]));
+    assert_eq!(feed.get_packed(1, 0).await.unwrap(), Some(vec![24; 20]));
+    assert_eq!(feed.get_packed(4, 3).await.unwrap(), Some(vec![99; 20]));
+    assert_eq!(feed.get_packed(4, 4).await.unwrap(), None);
+    assert_eq!(feed.get_packed(5, 0).await.unwrap(), None);
+
+    feed.append(b"not a segment").await.unwrap();
+    assert!(feed.get_packed(5, 0).await.is_err());
+}
+
+#[async_std::test]
+async fn get_by_hash() {
+    let mut a = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"hi"] {
+        a.append(data).await.unwrap();
+    }
+    let hashes: Vec<Vec<u8>> = a
+        .nodes(0..5)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|node| node.index() % 2 == 0)
+        .map(|node| node.hash().to_vec())
+        .collect();
+    assert!(a.index_of_hash(&hashes[1]).is_err());
+
+    a.set_hash_index(true).await.unwrap();
+    assert_eq!(a.index_of_hash(&hashes[1]).unwrap(), Some(1));
+    assert_eq!(a.index_of_hash(&hashes[2]).unwrap(), Some(0));
+    a.append(b"salut").await.unwrap();
+    let salut = a.nodes(6..7).await.unwrap()[0].hash().to_vec();
+    assert_eq!(
+        a.get_by_hash(&salut).await.unwrap(),
+        Some(b"salut".to_vec())
+    );
+    assert_eq!(a.get_by_hash(&[0; 32]).await.unwrap(), None);
+
+    let mut b = common::create_replica(&a).await.unwrap();
+    b.set_hash_index(true).await.unwrap();
+    let proof = a.proof(1, false).await.unwrap();
+    b.put(1, Some(b"ola"), proof).await.unwrap();
+    assert_eq!(
+        b.get_by_hash(&hashes[1]).await.unwrap(),
+        Some(b"ola".to_vec())
+    );
+    assert_eq!(b.index_of_hash(&salut).unwrap(), None);
+}
+
+#[async_std::test]
+async fn absorb_partial_replicas() {
+    let mut source = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj", b"salut", b"hej"] {
+        source.append(data).await.unwrap();
+    }
+    let mut a = common::create_replica(&source).await.unwrap();
+    let mut b = common::create_replica(&source).await.unwrap();
+    for index in 0..2 {
+        let proof = source.proof(index, false).await.unwrap();
+        let data = source.get(index).await.unwrap();
+        a.put(index, data.as_deref(), proof).await.unwrap();
+    }
+    for index in 2..5 {
+        let proof = source.proof(index, false).await.unwrap();
+        let data = source.get(index).await.unwrap();
+        b.put(index, data.as_deref(), proof).await.unwrap();
+    }
+
+    assert_eq!(a.absorb(&mut b).await.unwrap(), 3);
+    assert_eq!(a.len(), 5);
+    assert!((0..5).all(|index| a.has(index)));
+    assert_eq!(a.get(3).await.unwrap(), Some(b"salut".to_vec()));
+    assert_eq!(a.absorb(&mut b).await.unwrap(), 0);
+
+    let mut other = create_feed(50).await.unwrap();
+    other.append(b"hi").await.unwrap();
+    assert!(a.absorb(&mut other).await.is_err());
+}
+
+#[async_std::test]
+async fn clone_verify() {
+    let mut source = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj", b"salut"] {
+        source.append(data).await.unwrap();
+    }
+    let key = *source.public_key();
+    let dir = tempfile::Builder::new()
+        .prefix("archive")
+        .tempdir()
+        .unwrap();
+
+    let (_, report) = Feed::clone_verify(key, dir.path().join("good"), &mut source)
+        .await
+        .unwrap();
+    assert!(report.is_complete());
+    assert_eq!(report.blocks[3].bytes, Some(5));
+
+    let mut source = Unreliable(source);
+    let (mut feed, report) = Feed::clone_verify(key, dir.path().join("bad"), &mut source)
+        .await
+        .unwrap();
+    assert_eq!(report.verified(), 2);
+    assert!(report.signature_verified);
+    assert!(!report.is_complete());
+    assert!(matches!(report.blocks[1].status, BlockStatus::Invalid(_)));
+    assert!(matches!(report.blocks[2].status, BlockStatus::Missing(_)));
+    assert!(report
+        .to_json()
+        .contains(r#"{"index":2,"status":"missing","error":"Block not found"}"#));
+    assert!(feed.has(0) && !feed.has(1) && !feed.has(2) && feed.has(3));
+
+    assert!(
+        Feed::clone_verify(key, dir.path().join("good"), &mut source)
+            .await
+            .is_err()
+    );
+}
+
+#[async_std::test]
+async fn test_with_seed() {
+    let mut a = Feed::test_with_seed("alpha", 3).await.unwrap();
+    let mut b = Feed::test_with_seed("alpha", 3).await.unwrap();
+    let c = Feed::test_with_seed("beta", 3).await.unwrap();
+    assert_eq!(a.public_key(), b.public_key());
+    assert_ne!(a.public_key(), c.public_key());
+    assert_eq!(a.len(), 3);
+    assert_eq!(a.get(1).await.unwrap(), Some(b"alpha-1".to_vec()));
+    assert_eq!(
+        a.signature(2).await.unwrap().to_bytes()[..],
+        b.signature(2).await.unwrap().to_bytes()[..]
+    );
+}
+
+#[async_std::test]
+async fn freeze_for_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let backup = tempfile::tempdir().unwrap();
+    let mut feed = Feed::open(dir.path()).await.unwrap();
+    feed.append(b"hello").await.unwrap();
+    feed.append(b"world").await.unwrap();
+
+    {
+        let guard = feed.freeze_for_backup().await.unwrap();
+        assert_eq!((guard.len(), guard.byte_len()), (2, 10));
+        for entry in fs::read_dir(dir.path()).unwrap() {
+            let path = entry.unwrap().path();
+            fs::copy(&path, backup.path().join(path.file_name().unwrap())).unwrap();
+        }
+    }
+    feed.append(b"!").await.unwrap();
+
+    let mut restored = Feed::open(backup.path()).await.unwrap();
+    assert_eq!(restored.len(), 2);
+    assert_eq!(restored.public_key(), feed.public_key());
+    assert_eq!(restored.get(1).await.unwrap(), Some(b"world".to_vec()));
+    restored.append(b"again").await.unwrap();
+}
+
+#[async_std::test]
+async fn min_free_space() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut feed = Feed::open(dir.path()).await.unwrap();
+    feed.set_min_free_space(Some(0));
+    feed.append(b"hello").await.unwrap();
+
+    feed.set_min_free_space(Some(u64::MAX / 2));
+    let err = feed.append_batch(&[b"a", b"b"]).await.unwrap_err();
+    let out_of_space = err.downcast_ref::<OutOfSpace>().unwrap();
+    assert!(out_of_space.needed > out_of_space.available);
+    assert_eq!(feed.len(), 1);
+
+    feed.set_min_free_space(None);
+    feed.append(b"world").await.unwrap();
+    assert_eq!(feed.get(1).await.unwrap(), Some(b"world".to_vec()));
+}
+
+#[async_std::test]
+async fn scrub() {
+    let dir = tempfile::Builder::new().prefix("scrub").tempdir().unwrap();
+    let storage = Storage::new_disk(dir.path()).await.unwrap();
+    let keypair = generate_keypair();
+    let mut feed = Feed::builder(keypair.public, storage)
+        .secret_key(keypair.secret)
+        .build()
+        .unwrap();
+    feed.append(b"hello").await.unwrap();
+    feed.append(b"world").await.unwrap();
+    let replica = feed.load_into_memory().await.unwrap();
+    let mut data = fs::OpenOptions::new()
+        .write(true)
+        .open(dir.path().join("data"))
+        .unwrap();
+    data.write_all(b"yello").unwrap();
+
+    let feed = Arc::new(Mutex::new(feed));
+    let findings: Vec<ScrubFinding> = Feed::scrub(&feed, 1000, Some(Box::new(replica)))
+        .await
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    assert_eq!(
+        findings,
+        vec![ScrubFinding {
+            index: 0,
+            repaired: true
+        }]
+    );
+    let mut feed = feed.lock().await;
+    assert_eq!(feed.get(0).await.unwrap(), Some(b"hello".to_vec()));
+    assert_eq!(feed.audit().await.unwrap().invalid_blocks, 0);
+}
+
+#[async_std::test]
+async fn get_slice() {
+    let mut feed = create_feed(50).await.unwrap();
+    feed.append(b"hello").await.unwrap();
+    feed.append(b"wonderful world").await.unwrap();
+    assert_eq!(
+        feed.get_slice(1, 10, 5).await.unwrap(),
+        Some(b"world".to_vec())
+    );
+    assert_eq!(feed.get_slice(0, 5, 0).await.unwrap(), Some(vec![]));
+    assert!(feed.get_slice(1, 10, 6).await.is_err());
+    assert!(feed.get_slice(1, u64::MAX, 2).await.is_err());
+    assert_eq!(feed.get_slice(2, 0, 1).await.unwrap(), None);
+
+    let keypair = generate_keypair();
+    let storage = Storage::new_memory().await.unwrap();
+    let mut feed = Feed::builder(keypair.public, storage)
+        .secret_key(keypair.secret)
+        .checksums(true)
+        .build()
+        .unwrap();
+    feed.append(b"wonderful world").await.unwrap();
+    assert_eq!(
+        feed.get_slice(0, 0, 9).await.unwrap(),
+        Some(b"wonderful".to_vec())
+    );
+    assert!(feed.get_slice(0, 10, 6).await.is_err());
+}
+
+#[async_std::test]
+async fn empty_feed() {
+    let mut feed = create_feed(50).await.unwrap();
+    let mut other = create_feed(50).await.unwrap();
+    other.append(b"hello").await.unwrap();
+    let signature = other.signature(0).await.unwrap();
+
+    assert!(feed.is_empty());
+    assert!(feed.signature(0).await.is_err());
+    assert!(feed.verify(0, &signature).await.is_err());
+    assert!(feed.proof(0, false).await.is_err());
+    assert!(feed.checkpoint(0).await.is_err());
+    assert!(feed.seek(0).await.is_err());
+    assert_eq!(feed.get(0).await.unwrap(), None);
+    assert_eq!(feed.head().await.unwrap(), None);
+    assert_eq!(feed.audit().await.unwrap().valid_blocks, 0);
+    assert!(feed.verify_range(0..0).await.unwrap().is_empty());
+
+    let replica = feed.load_into_memory().await.unwrap();
+    assert!(replica.is_empty());
+}
+
+#[async_std::test]
+async fn prefetch() {
+    let dir = tempfile::Builder::new()
+        .prefix("prefetch")
+        .tempdir()
+        .unwrap();
+    let mut feed = Feed::open(dir.path()).await.unwrap();
+    let blocks: Vec<Vec<u8>> = (0..20u8).map(|i| vec![i; i as usize + 1]).collect();
+    for block in &blocks {
+        feed.append(block).await.unwrap();
+    }
+    feed.clear(6..7).await.unwrap();
+
+    for policy in &[
+        PrefetchPolicy::default(),
+        PrefetchPolicy::Sequential(4),
+        PrefetchPolicy::Stride(3),
+    ] {
+        feed.set_prefetch(*policy);
+        for index in (0..20).chain((0..20).step_by(2)) {
+            let expected = (index != 6).then(|| blocks[index as usize].clone());
+            assert_eq!(feed.get(index).await.unwrap(), expected, "{:?}", policy);
+        }
+    }
+}
+
+#[async_std::test]
+async fn length_proof() {
+    let mut feed = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj"] {
+        feed.append(data).await.unwrap();
+    }
+    feed.append_batch(&[&b"salut"[..], b"hej"]).await.unwrap();
+    let public_key = *feed.public_key();
+
+    let proof = feed.length_proof(2).await.unwrap();
+    assert_eq!(proof.length, 2);
+    assert_eq!(proof.roots.len(), 1);
+    proof.verify(&public_key).unwrap();
+
+    // Blocks appended in a batch only have a signature at its end.
+    let proof = feed.length_proof(4).await.unwrap();
+    assert_eq!(proof.length, 5);
+    let decoded = LengthProof::decode(&proof.encode()).unwrap();
+    assert_eq!(decoded, proof);
+    decoded.verify(&public_key).unwrap();
+    assert_eq!(decoded.root_hash(), proof.root_hash());
+
+    let other = create_feed(50).await.unwrap();
+    assert!(decoded.verify(other.public_key()).is_err());
+    let mut tampered = decoded.clone();
+    tampered.length = 6;
+    assert!(tampered.verify(&public_key).is_err());
+    assert!(LengthProof::decode(&proof.encode()[1..]).is_err());
+    assert!(feed.length_proof(0).await.is_err());
+    assert!(feed.length_proof(6).await.is_err());
+}
+
+#[async_std::test]
+async fn access_log() {
+    let mut feed = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj", b"salut"] {
+        feed.append(data).await.unwrap();
+    }
+    assert_eq!(
+        feed.cold_blocks(Duration::from_secs(60)),
+        Ranges::from(0..4)
+    );
+
+    feed.record_accesses(2);
+    feed.get(1).await.unwrap();
+    feed.get(1).await.unwrap();
+    feed.get_slice(2, 0, 1).await.unwrap();
+    assert_eq!(feed.block_access(1).unwrap().count, 2);
+    assert_eq!(feed.block_access(0), None);
+    assert_eq!(feed.hot_blocks(2), Ranges::from(1..2));
+    assert_eq!(
+        feed.cold_blocks(Duration::from_secs(60)),
+        Ranges::from(vec![0..1, 3..4])
+    );
+
+    // The least recently read block is forgotten once the log is full.
+    feed.get(3).await.unwrap();
+    assert_eq!(feed.block_access(1), None);
+    assert!(feed.cold_blocks(Duration::from_secs(60)).contains(1));
+}
+
+#[async_std::test]
+async fn tier_change_hook() {
+    let mut feed = create_feed(50).await.unwrap();
+    for data in &[&b"hi"[..], b"ola", b"ahoj"] {
+        feed.append(data).await.unwrap();
+    }
+    assert!(feed.on_tier_change(2, |_, _| {}).is_err());
+
+    feed.record_accesses(2);
+    let changes = Arc::new(std::sync::Mutex::new(vec![]));
+    let seen = changes.clone();
+    feed.on_tier_change(2, move |index, tier| {
+        seen.lock().unwrap().push((index, tier))
+    })
+    .unwrap();
+    for index in &[0, 0, 0, 1, 2] {
+        feed.get(*index).await.unwrap();
+    }
+    // Block 0 turns hot once, and cold when block 2 pushes it out of the log.
+    assert_eq!(
+        *changes.lock().unwrap(),
+        vec![(0, Tier::Hot), (0, Tier::Cold)]
+    );
+}
+
+#[async_std::test]
+async fn put_reader() {
+    let mut feed = create_feed(50).await.unwrap();
+    let block: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+    feed.append(b"small").await.unwrap();
+    feed.append(&block).await.unwrap();
+    let mut replica = create_replica(&feed).await.unwrap();
+
+    assert!(replica.put_reader(1, &block[..]).await.is_err());
+    let proof = feed.proof(1, true).await.unwrap();
+    replica.put(1, None, proof).await.unwrap();
+    assert!(!replica.has(1));
+
+    let mut longer = block.clone();
+    longer.push(0);
+    assert!(replica.put_reader(1, &longer[..]).await.is_err());
+    assert!(replica.put_reader(1, &block[1..]).await.is_err());
+    let mut corrupt = block.clone();
+    corrupt[100_000] ^= 1;
+    let err = replica.put_reader(1, &corrupt[..]).await.unwrap_err();
+    assert_eq!(err.to_string(), "Block 1 does not match its tree node");
+    assert!(!replica.has(1));
+
+    replica.put_reader(1, &block[..]).await.unwrap();
+    assert_eq!(replica.get(1).await.unwrap(), Some(block));
+    assert!(!replica.has(0));
+}
@@ -0,0 +1,25 @@
+// Writes `tests/fixtures/v10` with hypercore 10, for the `v10_js_fixture`
+// test in `tests/compat.rs`. The key pair is the one of the other JavaScript
+// fixtures, and the blocks are `a`, `b` and `c`.
+//
+//   npm install hypercore@10 && node tests/fixtures/v10.js
+
+const path = require('path')
+const Hypercore = require('hypercore')
+
+const keyPair = {
+  publicKey: Buffer.from('9718a1ff1c4ca79feac551c0c7212a65e4091278ec886b88be01ee4039682238', 'hex'),
+  secretKey: Buffer.from(
+    '53729c0311846cca9cc0eded07aaf9e6689705b6a0b1bb8c3a2a839b72fda383' +
+    '9718a1ff1c4ca79feac551c0c7212a65e4091278ec886b88be01ee4039682238',
+    'hex'
+  )
+}
+
+async function main () {
+  const core = new Hypercore(path.join(__dirname, 'v10'), { keyPair })
+  await core.append([Buffer.from('a'), Buffer.from('b'), Buffer.from('c')])
+  await core.close()
+}
+
+main()
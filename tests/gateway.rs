@@ -0,0 +1,89 @@
+#![cfg(feature = "gateway")]
+
+use hypercore::{Feed, Gateway, GatewayState};
+use tide::http::{Method, Request, Response, Url};
+
+async fn get(server: &tide::Server<GatewayState>, path: &str, range: Option<&str>) -> Response {
+    let url = Url::parse("http://localhost").unwrap().join(path).unwrap();
+    let mut req = Request::new(Method::Get, url);
+    if let Some(range) = range {
+        req.insert_header("range", range);
+    }
+    server.respond(req).await.unwrap()
+}
+
+#[async_std::test]
+async fn serve_feed() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut writer = Feed::open(dir.path()).await.unwrap();
+    writer.append(b"hello").await.unwrap();
+    writer.append(b"world").await.unwrap();
+
+    let mut gateway = Gateway::new();
+    let key = gateway.add_dir(dir.path()).await.unwrap();
+    let server = gateway.into_server();
+
+    let mut res = get(&server, &format!("/feed/{}/info", key), None).await;
+    assert_eq!(res.status(), 200);
+    let info: serde_json::Value = res.body_json().await.unwrap();
+    assert_eq!(info["length"], 2);
+    assert_eq!(info["byteLength"], 10);
+
+    let mut res = get(&server, &format!("/feed/{}/block/1", key), None).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body_bytes().await.unwrap(), b"world");
+    let res = get(&server, &format!("/feed/{}/block/2", key), None).await;
+    assert_eq!(res.status(), 404);
+    let res = get(&server, "/feed/00/block/0", None).await;
+    assert_eq!(res.status(), 404);
+
+    let mut res = get(&server, &format!("/feed/{}/bytes", key), None).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body_bytes().await.unwrap(), b"helloworld");
+
+    // Blocks appended by the writer are picked up.
+    writer.append(b"!").await.unwrap();
+    let mut res = get(&server, &format!("/feed/{}/bytes", key), Some("bytes=3-7")).await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res["content-range"], "bytes 3-7/11");
+    assert_eq!(res.body_bytes().await.unwrap(), b"lowor");
+    let mut res = get(&server, &format!("/feed/{}/bytes", key), Some("bytes=-3")).await;
+    assert_eq!(res.body_bytes().await.unwrap(), b"ld!");
+    let res = get(&server, &format!("/feed/{}/bytes", key), Some("bytes=11-")).await;
+    assert_eq!(res.status(), 416);
+
+    let mut res = get(&server, &format!("/feed/{}/proof/0", key), None).await;
+    assert_eq!(res.status(), 200);
+    let proof: serde_json::Value = res.body_json().await.unwrap();
+    assert_eq!(proof["index"], 0);
+    assert!(proof["signature"].is_string());
+    assert!(!proof["nodes"].as_array().unwrap().is_empty());
+}
+
+#[cfg(feature = "prometheus")]
+#[async_std::test]
+async fn serve_metrics() {
+    let handle = metrics_exporter_prometheus::PrometheusBuilder::new()
+        .install_recorder()
+        .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut writer = Feed::open(dir.path()).await.unwrap();
+    writer.append(b"hello").await.unwrap();
+
+    let mut gateway = Gateway::new();
+    let key = gateway.add_dir(dir.path()).await.unwrap();
+    let server = gateway.prometheus(handle).into_server();
+
+    get(&server, &format!("/feed/{}/block/0", key), None).await;
+    let mut res = get(&server, "/metrics", None).await;
+    assert_eq!(res.status(), 200);
+    let body = res.body_string().await.unwrap();
+    assert!(body.contains("hypercore_gateway_feeds 1"), "{}", body);
+    assert!(
+        body.contains("hypercore_gateway_blocks_total 1"),
+        "{}",
+        body
+    );
+    assert!(body.contains("hypercore_gateway_bytes_total 5"), "{}", body);
+}
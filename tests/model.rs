@@ -4,7 +4,6 @@ use common::create_feed;
 use quickcheck::{quickcheck, Arbitrary, Gen};
 use rand::seq::SliceRandom;
 use rand::Rng;
-use std::u8;
 
 const MAX_FILE_SIZE: u64 = 5 * 10; // 5mb
 
@@ -1,5 +1,17 @@
 use ed25519_dalek::PublicKey;
-use hypercore::{generate_keypair, sign, verify, Signature, Storage};
+#[cfg(target_os = "linux")]
+use hypercore::DirectDisk;
+use hypercore::{
+    generate_keypair, is_retryable, sign, verify, Feed, Node, NodeTrait, Retention, RetryPolicy,
+    RetryingStorage, Signature, Storage, Store, FORMAT_VERSION,
+};
+use random_access_memory::RandomAccessMemory;
+use random_access_storage::RandomAccess;
+use std::fmt::Debug;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 #[async_std::test]
 async fn should_write_and_read_keypair() {
@@ -49,3 +61,586 @@ async fn should_read_empty_public_key() {
     let mut storage = Storage::new_memory().await.unwrap();
     assert!(storage.read_public_key().await.is_err());
 }
+
+/// A store that fails the first `failures` writes with a transient error.
+#[derive(Debug)]
+struct Flaky {
+    inner: RandomAccessMemory,
+    failures: u32,
+}
+
+#[async_trait::async_trait]
+impl RandomAccess for Flaky {
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), Self::Error> {
+        if self.failures > 0 {
+            self.failures -= 1;
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "flaky").into());
+        }
+        self.inner.write(offset, data).await
+    }
+
+    async fn read(&mut self, offset: u64, length: u64) -> Result<Vec<u8>, Self::Error> {
+        self.inner.read(offset, length).await
+    }
+
+    async fn read_to_writer(
+        &mut self,
+        offset: u64,
+        length: u64,
+        buf: &mut (impl futures::io::AsyncWrite + Send),
+    ) -> Result<(), Self::Error> {
+        self.inner.read_to_writer(offset, length, buf).await
+    }
+
+    async fn del(&mut self, offset: u64, length: u64) -> Result<(), Self::Error> {
+        self.inner.del(offset, length).await
+    }
+
+    async fn truncate(&mut self, length: u64) -> Result<(), Self::Error> {
+        self.inner.truncate(length).await
+    }
+
+    async fn len(&self) -> Result<u64, Self::Error> {
+        self.inner.len().await
+    }
+
+    async fn is_empty(&mut self) -> Result<bool, Self::Error> {
+        self.inner.is_empty().await
+    }
+
+    async fn sync_all(&mut self) -> Result<(), Self::Error> {
+        self.inner.sync_all().await
+    }
+}
+
+fn flaky(failures: u32, max_retries: u32) -> RetryingStorage<Flaky> {
+    let policy = RetryPolicy {
+        max_retries,
+        initial_backoff: Duration::from_millis(1),
+        ..RetryPolicy::default()
+    };
+    let inner = Flaky {
+        inner: RandomAccessMemory::default(),
+        failures,
+    };
+    RetryingStorage::new(inner, policy)
+}
+
+#[async_std::test]
+async fn should_retry_transient_errors() {
+    let mut store = flaky(3, 5);
+    store.write(0, b"hello").await.unwrap();
+    assert_eq!(store.retries(), 3);
+    assert_eq!(store.read(0, 5).await.unwrap(), b"hello".to_vec());
+
+    let mut store = flaky(3, 2);
+    assert!(store.write(0, b"hello").await.is_err());
+    assert_eq!(store.retries(), 2);
+}
+
+#[test]
+fn should_extend_default_retry_classification() {
+    fn is_busy_or_retryable(err: &(dyn std::error::Error + 'static)) -> bool {
+        err.to_string() == "busy" || is_retryable(err)
+    }
+    let busy = io::Error::other("busy");
+    let timeout = io::Error::from(io::ErrorKind::TimedOut);
+    assert!(!is_retryable(&busy));
+    assert!(is_retryable(&timeout));
+
+    let policy = RetryPolicy {
+        is_retryable: is_busy_or_retryable,
+        ..RetryPolicy::default()
+    };
+    assert!((policy.is_retryable)(&busy));
+    assert!((policy.is_retryable)(&timeout));
+}
+
+#[async_std::test]
+async fn should_open_feed_with_retrying_storage() {
+    let storage = Storage::new(|_| Box::pin(async { Ok(flaky(1, 3)) }))
+        .await
+        .unwrap();
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    feed.append(b"hello").await.unwrap();
+    assert_eq!(feed.get(0).await.unwrap(), Some(b"hello".to_vec()));
+}
+
+#[async_std::test]
+async fn should_roll_back_failed_appends() {
+    let storage = Storage::new(|store| {
+        let failures = match store {
+            Store::Data => 1,
+            _ => 0,
+        };
+        Box::pin(async move { Ok(flaky(failures, 0)) })
+    })
+    .await
+    .unwrap();
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    assert!(feed.append(b"hello").await.is_err());
+    assert_eq!(feed.len(), 0);
+
+    let outcome = feed.append(b"world").await.unwrap();
+    assert_eq!(outcome.index, 0);
+    assert!(feed.verify(0, &outcome.signature).await.is_ok());
+    assert_eq!(feed.get(0).await.unwrap(), Some(b"world".to_vec()));
+}
+
+#[async_std::test]
+async fn should_append_expiry_with_block() {
+    let storage = Storage::new(|store| {
+        let failures = match store {
+            Store::Expiry => 1,
+            _ => 0,
+        };
+        Box::pin(async move { Ok(flaky(failures, 0)) })
+    })
+    .await
+    .unwrap();
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    let expiry = SystemTime::now() - Duration::from_secs(60);
+    assert!(feed.append_with_expiry(b"hello", expiry).await.is_err());
+    assert_eq!(feed.len(), 0);
+
+    feed.append_with_expiry(b"hello", expiry).await.unwrap();
+    assert_eq!(feed.expire_now().await.unwrap(), 1);
+}
+
+#[async_std::test]
+async fn should_not_index_failed_appends() {
+    let storage = Storage::new(|store| {
+        let failures = match store {
+            Store::Data => 1,
+            _ => 0,
+        };
+        Box::pin(async move { Ok(flaky(failures, 0)) })
+    })
+    .await
+    .unwrap();
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    feed.set_hash_index(true).await.unwrap();
+    assert!(feed.append(b"hello").await.is_err());
+    feed.append(b"world").await.unwrap();
+
+    // Leaf hashes depend only on the data, so take them from another feed.
+    let mut other = Feed::default();
+    other.append(b"hello").await.unwrap();
+    other.append(b"world").await.unwrap();
+    let nodes = other.nodes(0..3).await.unwrap();
+    assert_eq!(feed.index_of_hash(nodes[0].hash()).unwrap(), None);
+    assert_eq!(feed.index_of_hash(nodes[2].hash()).unwrap(), Some(0));
+}
+
+#[async_std::test]
+async fn should_commit_batches_in_order() {
+    let mut storage = Storage::open(|store| {
+        let failures = match store {
+            Store::Tree => 1,
+            _ => 0,
+        };
+        Box::pin(async move { Ok(flaky(failures, 0)) })
+    })
+    .await
+    .unwrap();
+    let keypair = generate_keypair();
+    let signature = sign(&keypair.public, &keypair.secret, b"roots");
+    let node = Node::new(0, vec![1; 32], 5);
+
+    // The signature is staged first, but only written after the node.
+    let mut batch = storage.begin_batch();
+    batch.put_signature(0, signature);
+    batch.append_data(0, 0, b"hello");
+    batch.put_node(&node);
+    assert_eq!(batch.len(), 3);
+    assert!(batch.commit().await.is_err());
+    assert!(storage.get_signature(0).await.is_err());
+
+    let mut batch = storage.begin_batch();
+    batch.put_signature(0, signature);
+    batch.put_node(&node);
+    batch.commit().await.unwrap();
+    assert_eq!(storage.get_signature(0).await.unwrap(), signature);
+    assert_eq!(storage.get_node(0).await.unwrap(), node);
+    assert_eq!(storage.get_data(0).await.unwrap(), b"hello".to_vec());
+}
+
+#[async_std::test]
+async fn should_align_data_blocks() {
+    let mut storage = Storage::new_memory().await.unwrap();
+    storage.set_alignment(64).await.unwrap();
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    for block in &[&b"hello"[..], b"", b"world", &[1; 100]] {
+        feed.append(block).await.unwrap();
+    }
+    assert_eq!(feed.get(0).await.unwrap(), Some(b"hello".to_vec()));
+    assert_eq!(feed.get(1).await.unwrap(), Some(vec![]));
+    assert_eq!(feed.get(2).await.unwrap(), Some(b"world".to_vec()));
+    assert_eq!(feed.get(3).await.unwrap(), Some(vec![1; 100]));
+    assert_eq!(feed.byte_len(), 110);
+
+    let mut storage = Storage::new_memory().await.unwrap();
+    storage.write_data(0, b"hello").await.unwrap();
+    assert!(storage.set_alignment(64).await.is_err());
+    assert!(storage.set_alignment(100).await.is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[async_std::test]
+async fn should_read_and_write_with_direct_io() {
+    let dir = tempfile::Builder::new().prefix("direct").tempdir().unwrap();
+    let storage = match direct_storage(dir.path()).await {
+        Some(storage) => storage,
+        None => return,
+    };
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    feed.append(b"hello").await.unwrap();
+    feed.append(&[7; 5000]).await.unwrap();
+    feed.append(b"world").await.unwrap();
+    drop(feed);
+
+    let storage = Storage::new_disk_direct(dir.path(), 4096).await.unwrap();
+    assert_eq!(storage.alignment(), 4096);
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    assert_eq!(feed.len(), 3);
+    assert_eq!(feed.get(0).await.unwrap(), Some(b"hello".to_vec()));
+    assert_eq!(feed.get(1).await.unwrap(), Some(vec![7; 5000]));
+    assert_eq!(feed.get(2).await.unwrap(), Some(b"world".to_vec()));
+    assert!(Storage::new_disk_direct(dir.path(), 512).await.is_err());
+}
+
+/// Create a storage with a direct IO data store in `dir`. Not every
+/// filesystem supports `O_DIRECT`, tmpfs before Linux 6.6 for one, so the
+/// caller is told to skip if it fails, unless the filesystem is known to
+/// support it.
+#[cfg(target_os = "linux")]
+async fn direct_storage(dir: &std::path::Path) -> Option<Storage<DirectDisk>> {
+    let err = match Storage::new_disk_direct(dir, 4096).await {
+        Ok(storage) => return Some(storage),
+        Err(err) => err,
+    };
+    let existing = dir.ancestors().find(|dir| dir.exists()).unwrap();
+    let output = std::process::Command::new("stat")
+        .args(["-f", "-c", "%T"])
+        .arg(existing)
+        .output()
+        .unwrap();
+    let filesystem = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    assert!(
+        !["ext2/ext3", "xfs", "btrfs"].contains(&filesystem.as_str()),
+        "O_DIRECT failed on {}: {}",
+        filesystem,
+        err
+    );
+    eprintln!("skipping direct IO on {}: {}", filesystem, err);
+    None
+}
+
+#[async_std::test]
+async fn should_compact_cleared_blocks() {
+    let dir = tempfile::Builder::new()
+        .prefix("compact")
+        .tempdir()
+        .unwrap();
+    let mut storage = Storage::new_disk(dir.path()).await.unwrap();
+    storage.set_alignment(1).await.unwrap();
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    for block in &[&b"hello"[..], b"big", b"world", b"!"] {
+        feed.append(block).await.unwrap();
+    }
+
+    feed.clear(1..2).await.unwrap();
+    assert_eq!(feed.get(1).await.unwrap(), None);
+    assert_eq!(feed.compact().await.unwrap(), 3);
+    assert_eq!(feed.get(0).await.unwrap(), Some(b"hello".to_vec()));
+    assert_eq!(feed.get(2).await.unwrap(), Some(b"world".to_vec()));
+    assert_eq!(feed.get(3).await.unwrap(), Some(b"!".to_vec()));
+    assert!(feed.proof(1, false).await.is_ok());
+
+    feed.append(b"more").await.unwrap();
+    assert_eq!(feed.get(4).await.unwrap(), Some(b"more".to_vec()));
+    assert_eq!(feed.compact().await.unwrap(), 0);
+    drop(feed);
+
+    let storage = Storage::new_disk(dir.path()).await.unwrap();
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    assert_eq!(feed.get(1).await.unwrap(), None);
+    assert_eq!(feed.get(3).await.unwrap(), Some(b"!".to_vec()));
+
+    let mut feed = Feed::with_storage(Storage::new_memory().await.unwrap())
+        .await
+        .unwrap();
+    feed.append(b"hello").await.unwrap();
+    feed.clear(0..1).await.unwrap();
+    assert!(feed.compact().await.is_err());
+}
+
+/// An in-memory store whose writes fail once a shared budget is spent, as if
+/// the process crashed.
+#[derive(Debug)]
+struct Crashing {
+    inner: RandomAccessMemory,
+    writes: Arc<AtomicU64>,
+}
+
+#[async_trait::async_trait]
+impl RandomAccess for Crashing {
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), Self::Error> {
+        let spent = self
+            .writes
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_err();
+        if spent {
+            return Err(io::Error::other("crashed").into());
+        }
+        self.inner.write(offset, data).await
+    }
+
+    async fn read(&mut self, offset: u64, length: u64) -> Result<Vec<u8>, Self::Error> {
+        self.inner.read(offset, length).await
+    }
+
+    async fn read_to_writer(
+        &mut self,
+        offset: u64,
+        length: u64,
+        buf: &mut (impl futures::io::AsyncWrite + Send),
+    ) -> Result<(), Self::Error> {
+        self.inner.read_to_writer(offset, length, buf).await
+    }
+
+    async fn del(&mut self, offset: u64, length: u64) -> Result<(), Self::Error> {
+        self.inner.del(offset, length).await
+    }
+
+    // `RandomAccessMemory` doesn't implement truncating, so copy what's kept.
+    async fn truncate(&mut self, length: u64) -> Result<(), Self::Error> {
+        let data = self.inner.read(0, length).await?;
+        self.inner = RandomAccessMemory::default();
+        self.inner.write(0, &data).await
+    }
+
+    async fn len(&self) -> Result<u64, Self::Error> {
+        self.inner.len().await
+    }
+
+    async fn is_empty(&mut self) -> Result<bool, Self::Error> {
+        self.inner.is_empty().await
+    }
+
+    async fn sync_all(&mut self) -> Result<(), Self::Error> {
+        self.inner.sync_all().await
+    }
+}
+
+#[async_std::test]
+async fn should_keep_blocks_when_compacting_fails() {
+    let mut completed = false;
+    for budget in 0..10 {
+        let writes = Arc::new(AtomicU64::new(u64::MAX));
+        let shared = writes.clone();
+        let mut storage = Storage::new(move |_| {
+            let inner = RandomAccessMemory::default();
+            let writes = shared.clone();
+            Box::pin(async move { Ok(Crashing { inner, writes }) })
+        })
+        .await
+        .unwrap();
+        storage.set_alignment(1).await.unwrap();
+        let mut feed = Feed::with_storage(storage).await.unwrap();
+        for block in &[&b"hello"[..], b"big", b"world", b"!"] {
+            feed.append(block).await.unwrap();
+        }
+        // "world" moves from byte 8 to 5, overlapping where it was.
+        feed.clear(1..2).await.unwrap();
+
+        writes.store(budget, Ordering::SeqCst);
+        completed |= feed.compact().await.is_ok();
+        writes.store(u64::MAX, Ordering::SeqCst);
+        assert_eq!(feed.get(0).await.unwrap(), Some(b"hello".to_vec()));
+        assert_eq!(feed.get(2).await.unwrap(), Some(b"world".to_vec()));
+        assert_eq!(feed.get(3).await.unwrap(), Some(b"!".to_vec()));
+    }
+    assert!(completed);
+}
+
+#[async_std::test]
+async fn should_persist_pins() {
+    let dir = tempfile::Builder::new().prefix("pins").tempdir().unwrap();
+    let mut storage = Storage::new_disk(dir.path()).await.unwrap();
+    storage.set_alignment(1).await.unwrap();
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    for block in &[&b"hello"[..], b"big", b"world", b"!"] {
+        feed.append(block).await.unwrap();
+    }
+
+    feed.pin(0..2).await.unwrap();
+    feed.pin(8..10).await.unwrap();
+    feed.unpin(1..2).await.unwrap();
+    assert_eq!(feed.pins().as_slice(), &[0..1, 8..10][..]);
+    drop(feed);
+
+    let storage = Storage::new_disk(dir.path()).await.unwrap();
+    let mut feed = Feed::with_storage(storage).await.unwrap();
+    assert_eq!(feed.pins().as_slice(), &[0..1, 8..10][..]);
+
+    feed.clear(0..2).await.unwrap();
+    assert_eq!(feed.get(0).await.unwrap(), Some(b"hello".to_vec()));
+    assert_eq!(feed.get(1).await.unwrap(), None);
+
+    feed.set_retention(vec![Retention::Blocks(1)]);
+    assert_eq!(feed.gc().await.unwrap(), 1);
+    assert_eq!(feed.compact().await.unwrap(), 8);
+    assert_eq!(feed.get(0).await.unwrap(), Some(b"hello".to_vec()));
+    assert_eq!(feed.get(3).await.unwrap(), Some(b"!".to_vec()));
+}
+
+/// Check that every append is visible to the next read, with the outcome
+/// describing the feed after it.
+async fn assert_read_your_writes<T>(mut feed: Feed<T>)
+where
+    T: RandomAccess<Error = Box<dyn std::error::Error + Send + Sync>> + Debug + Send,
+{
+    let mut byte_length = 0;
+    for index in 0..20u64 {
+        let data = vec![index as u8; index as usize * 300];
+        let outcome = feed.append(&data).await.unwrap();
+        byte_length += data.len() as u64;
+        assert_eq!(outcome.index, index);
+        assert_eq!(outcome.length, index + 1);
+        assert_eq!(outcome.byte_length, byte_length);
+        assert_eq!(feed.get(outcome.index).await.unwrap(), Some(data));
+        feed.verify(index, &outcome.signature).await.unwrap();
+    }
+
+    let outcome = feed.append_batch(&[b"a", b"b"]).await.unwrap();
+    assert_eq!((outcome.index, outcome.length), (20, 22));
+    assert_eq!(feed.get(21).await.unwrap(), Some(b"b".to_vec()));
+}
+
+#[async_std::test]
+async fn should_read_your_writes() {
+    let storage = Storage::new_memory().await.unwrap();
+    assert_read_your_writes(Feed::with_storage(storage).await.unwrap()).await;
+
+    let mut storage = Storage::new_memory().await.unwrap();
+    storage.set_alignment(512).await.unwrap();
+    assert_read_your_writes(Feed::with_storage(storage).await.unwrap()).await;
+
+    let storage = Storage::new(|_| Box::pin(async { Ok(flaky(1, 3)) }))
+        .await
+        .unwrap();
+    assert_read_your_writes(Feed::with_storage(storage).await.unwrap()).await;
+
+    let dir = tempfile::Builder::new()
+        .prefix("read-your-writes")
+        .tempdir()
+        .unwrap();
+    assert_read_your_writes(Feed::open(dir.path().join("disk")).await.unwrap()).await;
+
+    #[cfg(target_os = "linux")]
+    {
+        let storage = Storage::new_disk_positioned(&dir.path().join("positioned"))
+            .await
+            .unwrap();
+        assert_read_your_writes(Feed::with_storage(storage).await.unwrap()).await;
+        if let Some(storage) = direct_storage(&dir.path().join("direct")).await {
+            assert_read_your_writes(Feed::with_storage(storage).await.unwrap()).await;
+        }
+    }
+}
+
+#[async_std::test]
+async fn should_migrate_format_versions() {
+    let dir = tempfile::Builder::new()
+        .prefix("version")
+        .tempdir()
+        .unwrap();
+    let mut feed = Feed::open(dir.path()).await.unwrap();
+    feed.append(b"hello").await.unwrap();
+    assert_eq!(feed_version(dir.path()).await, FORMAT_VERSION);
+    drop(feed);
+
+    // Directories from before versioning are read as they are, and
+    // migrated when opened for writing.
+    std::fs::remove_file(dir.path().join("version")).unwrap();
+    assert_eq!(feed_version(dir.path()).await, 0);
+    let mut feed = Feed::open(dir.path()).await.unwrap();
+    assert_eq!(feed.get(0).await.unwrap(), Some(b"hello".to_vec()));
+    assert_eq!(feed_version(dir.path()).await, FORMAT_VERSION);
+    drop(feed);
+
+    std::fs::write(dir.path().join("version"), 99u64.to_be_bytes()).unwrap();
+    assert!(Storage::open_disk(dir.path()).await.is_err());
+    assert!(Feed::open(dir.path()).await.is_err());
+}
+
+#[async_std::test]
+async fn should_store_zero_checksums() {
+    let mut storage = Storage::new_memory().await.unwrap();
+    storage.put_checksum(1, 0).await.unwrap();
+    assert_eq!(storage.get_checksum(0).await.unwrap(), None);
+    assert_eq!(storage.get_checksum(1).await.unwrap(), Some(0));
+    assert_eq!(storage.get_checksum(2).await.unwrap(), None);
+}
+
+#[async_std::test]
+async fn should_migrate_unmarked_checksums() {
+    let dir = tempfile::Builder::new()
+        .prefix("checksums")
+        .tempdir()
+        .unwrap();
+    drop(Storage::new_disk(dir.path()).await.unwrap());
+
+    // Version 1 stored bare checksums, with zeroes for missing ones.
+    let mut unmarked = vec![0; 4];
+    unmarked.extend_from_slice(&0xdead_beefu32.to_be_bytes());
+    std::fs::write(dir.path().join("checksums"), &unmarked).unwrap();
+    std::fs::write(dir.path().join("version"), 1u64.to_be_bytes()).unwrap();
+    let mut storage = Storage::open_disk(dir.path()).await.unwrap();
+    assert_eq!(storage.get_checksum(0).await.unwrap(), None);
+    assert_eq!(storage.get_checksum(1).await.unwrap(), Some(0xdead_beef));
+    drop(storage);
+
+    let mut storage = Storage::new_disk(dir.path()).await.unwrap();
+    assert_eq!(storage.format_version().await.unwrap(), FORMAT_VERSION);
+    assert_eq!(storage.get_checksum(0).await.unwrap(), None);
+    assert_eq!(storage.get_checksum(1).await.unwrap(), Some(0xdead_beef));
+    storage.put_checksum(2, 0).await.unwrap();
+    assert_eq!(storage.get_checksum(2).await.unwrap(), Some(0));
+}
+
+async fn feed_version(dir: &std::path::Path) -> u64 {
+    let mut storage = Storage::open_disk(dir).await.unwrap();
+    storage.format_version().await.unwrap()
+}
+
+#[cfg(feature = "encryption")]
+#[async_std::test]
+async fn should_encrypt_secret_key() {
+    let dir = tempfile::Builder::new()
+        .prefix("encrypted")
+        .tempdir()
+        .unwrap();
+    let mut feed = Feed::open(dir.path()).await.unwrap();
+    feed.append(b"hello").await.unwrap();
+    feed.encrypt_secret_key(b"hunter2").await.unwrap();
+    feed.append(b"world").await.unwrap();
+    drop(feed);
+
+    let mut storage = Storage::open_disk(dir.path()).await.unwrap();
+    assert!(storage.is_secret_key_encrypted().await.unwrap());
+    assert!(storage.read_secret_key().await.is_err());
+
+    let mut feed = Feed::open(dir.path()).await.unwrap();
+    assert_eq!(feed.get(1).await.unwrap(), Some(b"world".to_vec()));
+    assert!(feed.append(b"locked").await.is_err());
+    assert!(feed.unlock(b"hunter3").await.is_err());
+    feed.unlock(b"hunter2").await.unwrap();
+    feed.append(b"unlocked").await.unwrap();
+    assert_eq!(feed.len(), 3);
+}